@@ -0,0 +1,50 @@
+/* Segment Tree C API
+ *
+ * Build the library with `cargo build --release --features ffi` and link against
+ * the `segment_tree` shared library.
+ *
+ * Ownership: segtree_new allocates a tree and hands it to the caller, who owns it until it is
+ * passed to segtree_free exactly once. The other functions only borrow the tree. The input array
+ * of segtree_new is copied, so the caller keeps ownership of it.
+ */
+
+#ifndef SEGMENT_TREE_H
+#define SEGMENT_TREE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by every function except segtree_free */
+#define SEGTREE_OK 0
+#define SEGTREE_NULL_POINTER 1
+#define SEGTREE_EMPTY_INPUT 2
+#define SEGTREE_INPUT_TOO_LARGE 3
+#define SEGTREE_INDEX_OUT_OF_BOUNDS 4
+#define SEGTREE_INVALID_RANGE 5
+#define SEGTREE_OVERFLOW 6
+#define SEGTREE_INVALID_INPUT 7
+
+/* Opaque sum segment tree */
+typedef struct SegmentTree SegmentTree;
+
+/* Create a tree from len values; *out receives the tree on success */
+int segtree_new(const intptr_t *values, size_t len, SegmentTree **out);
+
+/* Sum of the values from start to end, inclusive; *out receives the sum on success */
+int segtree_query(const SegmentTree *tree, size_t start, size_t end, intptr_t *out);
+
+/* Set the value at index */
+int segtree_update(SegmentTree *tree, size_t index, intptr_t value);
+
+/* Free a tree; null is ignored */
+void segtree_free(SegmentTree *tree);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SEGMENT_TREE_H */
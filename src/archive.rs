@@ -0,0 +1,284 @@
+//! Archived Segment Trees
+//!
+//! An archive stores every node of a sum segment tree in its flat heap layout, so a read-only
+//! view can answer queries straight from the bytes (for example a memory-mapped file)
+//! without rebuilding the tree.
+//!
+//! Layout, all integers little endian:
+//! - Magic bytes `SEGA`
+//! - Format version, `u16`
+//! - Overflow policy, `u8`
+//! - Padding, `u8`
+//! - Number of leaves, `u64`
+//! - Number of leaf slots, a power of two, `u64`
+//! - Node sums in heap order, `i128` each
+
+use crate::{OverflowPolicy, SegmentTree, SegmentTreeError};
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = *b"SEGA";
+const VERSION: u16 = 1;
+pub(crate) const HEADER_LEN: usize = 24;
+pub(crate) const NODE_LEN: usize = 16;
+
+/// Archive Header
+/// Dimensions and configuration stored at the start of an archive
+/// len: Number of leaves
+/// leaf_capacity: Number of leaf slots, a power of two
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) len: usize,
+    pub(crate) leaf_capacity: usize,
+    pub(crate) overflow_policy: OverflowPolicy,
+}
+
+/// Build an error for a malformed archive
+/// message: Description of the problem
+fn invalid_archive(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl Header {
+    /// Encode the header
+    /// Returns the header bytes
+    pub(crate) fn encode(&self) -> [u8; HEADER_LEN] {
+        let policy: u8 = match self.overflow_policy {
+            OverflowPolicy::Checked => 0,
+            OverflowPolicy::Saturating => 1,
+            OverflowPolicy::Wrapping => 2,
+        };
+
+        let mut header = [0u8; HEADER_LEN];
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        header[6] = policy;
+        header[8..16].copy_from_slice(&(self.len as u64).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.leaf_capacity as u64).to_le_bytes());
+        header
+    }
+
+    /// Decode and check a header
+    /// bytes: Bytes starting with the header
+    /// Returns the header, or an error of kind `InvalidData` if the bytes do not start with a valid header
+    pub(crate) fn decode(bytes: &[u8]) -> io::Result<Header> {
+        if bytes.len() < HEADER_LEN || bytes[0..4] != MAGIC {
+            return Err(invalid_archive("Not a segment tree archive"));
+        }
+        if u16::from_le_bytes([bytes[4], bytes[5]]) != VERSION {
+            return Err(invalid_archive("Unsupported segment tree archive version"));
+        }
+        let overflow_policy = match bytes[6] {
+            0 => OverflowPolicy::Checked,
+            1 => OverflowPolicy::Saturating,
+            2 => OverflowPolicy::Wrapping,
+            _ => return Err(invalid_archive("Unknown overflow policy")),
+        };
+
+        let len = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let leaf_capacity = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+        let (Ok(len), Ok(leaf_capacity)) = (usize::try_from(len), usize::try_from(leaf_capacity)) else {
+            return Err(invalid_archive("Archive is too large for this platform"));
+        };
+        if !leaf_capacity.is_power_of_two() || len > leaf_capacity {
+            return Err(invalid_archive("Invalid archive dimensions"));
+        }
+
+        Ok(Header { len, leaf_capacity, overflow_policy })
+    }
+
+    /// Get the total length of an archive with this header
+    /// Returns the length in bytes, or `None` if it does not fit in `u64`
+    pub(crate) fn archive_len(&self) -> Option<u64> {
+        (self.leaf_capacity as u64)
+            .checked_mul(2 * NODE_LEN as u64)
+            .map(|nodes_len| nodes_len - NODE_LEN as u64)
+            .and_then(|nodes_len| nodes_len.checked_add(HEADER_LEN as u64))
+    }
+
+    /// Query an archive with this header
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// node_value: Function reading the sum of a node, by its index in heap order
+    /// Returns the sum of the range or a segment tree error, or the error of `node_value`
+    pub(crate) fn query<E, F>(&self, start: usize, end: usize, mut node_value: F) -> Result<Result<isize, SegmentTreeError>, E>
+    where
+        F: FnMut(usize) -> Result<i128, E>,
+    {
+        if start > end {
+            return Ok(Err(SegmentTreeError::InvalidRange { start, end, len: self.len }));
+        }
+        if start >= self.len {
+            return Ok(Err(SegmentTreeError::IndexOutOfBounds { index: start, len: self.len }));
+        }
+        if end >= self.len {
+            return Ok(Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len }));
+        }
+
+        // Walk up from both boundary leaves, using one-based heap positions so that
+        // left children are even and right children are odd.
+        // The stored sums are not trusted, so corrupted sums report an overflow instead of panicking
+        let mut sum: Option<i128> = Some(0);
+        let mut left = start + self.leaf_capacity;
+        let mut right = end + self.leaf_capacity + 1;
+        while left < right {
+            if left % 2 == 1 {
+                let value = node_value(left - 1)?;
+                sum = sum.and_then(|sum| sum.checked_add(value));
+                left += 1;
+            }
+            if right % 2 == 1 {
+                right -= 1;
+                let value = node_value(right - 1)?;
+                sum = sum.and_then(|sum| sum.checked_add(value));
+            }
+            left /= 2;
+            right /= 2;
+        }
+
+        Ok(match sum {
+            Some(sum) => self.overflow_policy.narrow(sum, start, end),
+            None => Err(SegmentTreeError::Overflow { start, end }),
+        })
+    }
+}
+
+/// Implementation of archiving the sum segment tree
+impl SegmentTree {
+    /// Write the segment tree as an archive that `ArchivedSegmentTree` can query in place
+    /// writer: Destination of the bytes
+    /// Returns `Ok(())` if the archive was written, otherwise the I/O error
+    pub fn archive_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = Header {
+            len: self.leaf_len,
+            leaf_capacity: self.leaf_capacity,
+            overflow_policy: self.overflow_policy(),
+        };
+        writer.write_all(&header.encode())?;
+
+        let mut buffer = Vec::with_capacity(NODE_LEN * 4096);
+        for chunk in self.nodes.chunks(4096) {
+            buffer.clear();
+            for node in chunk {
+                buffer.extend_from_slice(&node.value.to_le_bytes());
+            }
+            writer.write_all(&buffer)?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// Archived Segment Tree
+/// Read-only sum segment tree borrowing the bytes written by `SegmentTree::archive_to`
+/// Queries read node sums directly from the bytes, nothing is decoded up front
+/// bytes: Archive bytes
+/// header: Dimensions and configuration of the archive
+#[derive(Debug, Clone, Copy)]
+pub struct ArchivedSegmentTree<'a> {
+    bytes: &'a [u8],
+    header: Header,
+}
+
+impl<'a> ArchivedSegmentTree<'a> {
+    /// Create a view of an archive
+    /// Only the header and the total length are checked, in O(1)
+    /// bytes: Archive bytes
+    /// Returns the view, or an error of kind `InvalidData` if the bytes are not an archive
+    pub fn from_bytes(bytes: &'a [u8]) -> io::Result<ArchivedSegmentTree<'a>> {
+        let header = Header::decode(bytes)?;
+        if header.archive_len() != Some(bytes.len() as u64) {
+            return Err(invalid_archive("Archive length does not match its header"));
+        }
+
+        Ok(ArchivedSegmentTree { bytes, header })
+    }
+
+    /// Get the number of leaves
+    pub fn len(&self) -> usize {
+        self.header.len
+    }
+
+    /// Check if the archived tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// Get the overflow policy of the archived tree
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.header.overflow_policy
+    }
+
+    /// Query the archived tree
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the sum of the range or an error
+    pub fn query(&self, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        let Ok(result) = self.header.query(start, end, |node_idx| {
+            let offset = HEADER_LEN + node_idx * NODE_LEN;
+            Ok::<_, std::convert::Infallible>(i128::from_le_bytes(self.bytes[offset..offset + NODE_LEN].try_into().unwrap()))
+        });
+        result
+    }
+
+    /// Get a single leaf value
+    /// index: Index of the leaf
+    /// Returns the value or an error
+    pub fn get(&self, index: usize) -> Result<isize, SegmentTreeError> {
+        self.query(index, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArchivedSegmentTree, OverflowPolicy, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_archived_queries() -> Result<(), SegmentTreeError> {
+        let input = vec![5, -3, 8, 1, 0, 12, -7];
+        let segment_tree = SegmentTree::new(&input)?;
+
+        let mut bytes = Vec::new();
+        segment_tree.archive_to(&mut bytes).unwrap();
+        let archived = ArchivedSegmentTree::from_bytes(&bytes).unwrap();
+        assert_eq!(archived.len(), input.len());
+        assert_eq!(archived.overflow_policy(), OverflowPolicy::Checked);
+
+        for start in 0..input.len() {
+            for end in start..input.len() {
+                assert_eq!(archived.query(start, end)?, segment_tree.query(start, end)?);
+            }
+        }
+        assert_eq!(archived.get(5)?, 12);
+        assert_eq!(archived.query(3, 2), Err(SegmentTreeError::InvalidRange { start: 3, end: 2, len: 7 }));
+        assert_eq!(archived.query(0, 7), Err(SegmentTreeError::IndexOutOfBounds { index: 7, len: 7 }));
+
+        let overflowing = SegmentTree::new(&[isize::MAX, 1])?;
+        let mut bytes = Vec::new();
+        overflowing.archive_to(&mut bytes).unwrap();
+        let archived = ArchivedSegmentTree::from_bytes(&bytes).unwrap();
+        assert_eq!(archived.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_invalid() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        let mut bytes = Vec::new();
+        segment_tree.archive_to(&mut bytes).unwrap();
+
+        assert!(ArchivedSegmentTree::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ArchivedSegmentTree::from_bytes(&bytes[..10]).is_err());
+
+        let mut bad_capacity = bytes.clone();
+        bad_capacity[16] = 3;
+        assert!(ArchivedSegmentTree::from_bytes(&bad_capacity).is_err());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[3] = b'T';
+        assert!(ArchivedSegmentTree::from_bytes(&bad_magic).is_err());
+
+        Ok(())
+    }
+}
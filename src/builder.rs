@@ -0,0 +1,174 @@
+//! Segment Tree Builder
+
+#[cfg(feature = "lazy")]
+use crate::{ActedMonoid, LazySegmentTree};
+use crate::{FnMonoid, Monoid, OverflowPolicy, SegmentTree, SegmentTreeError, Sum};
+
+/// Segment Tree Builder
+/// Configuration of a segment tree, set one option at a time before calling `build`
+/// Options can be given in any order: the overflow policy is kept when the monoid is replaced and
+/// only set on the monoid by `build`
+/// monoid: Operation used to combine values, `Sum` unless another one is chosen
+/// overflow_policy: Overflow policy to set on the monoid, or `None` to keep the policy of the monoid
+#[derive(Debug, Clone, Default)]
+pub struct SegmentTreeBuilder<M: Monoid = Sum> {
+    monoid: M,
+    overflow_policy: Option<OverflowPolicy>,
+}
+
+impl SegmentTreeBuilder {
+    /// Create a builder for a sum segment tree with checked overflow
+    pub fn new() -> SegmentTreeBuilder {
+        SegmentTreeBuilder::default()
+    }
+}
+
+impl<M: Monoid> SegmentTreeBuilder<M> {
+    /// Set how results that do not fit are handled, such as sums that do not fit in `isize`
+    /// Monoids whose results cannot overflow, such as `FnMonoid`, ignore it
+    /// overflow_policy: Overflow policy of the results
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> SegmentTreeBuilder<M> {
+        self.overflow_policy = Some(overflow_policy);
+        self
+    }
+
+    /// Combine values with a monoid
+    /// monoid: Operation used to combine values
+    pub fn monoid<N: Monoid>(self, monoid: N) -> SegmentTreeBuilder<N> {
+        SegmentTreeBuilder { monoid, overflow_policy: self.overflow_policy }
+    }
+
+    /// Combine values with a function
+    /// identity: Value of an empty range
+    /// combine: Associative function combining the values of two adjacent ranges
+    pub fn operation<T, F>(self, identity: T, combine: F) -> SegmentTreeBuilder<FnMonoid<T, F>>
+    where
+        T: Clone,
+        F: Fn(&T, &T) -> T,
+    {
+        self.monoid(FnMonoid::new(identity, combine))
+    }
+
+    /// Build a lazy segment tree with range updates instead, keeping the overflow policy
+    /// Requires the `lazy` feature
+    /// monoid: Operation used to combine values and apply maps
+    #[cfg(feature = "lazy")]
+    pub fn lazy<A: ActedMonoid>(self, monoid: A) -> LazySegmentTreeBuilder<A> {
+        LazySegmentTreeBuilder { monoid, overflow_policy: self.overflow_policy }
+    }
+
+    /// Build the configured segment tree
+    /// input: Vector of input values
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn build(self, input: &[M::Item]) -> Result<SegmentTree<M>, SegmentTreeError> {
+        let mut monoid = self.monoid;
+        if let Some(overflow_policy) = self.overflow_policy {
+            monoid.set_overflow_policy(overflow_policy);
+        }
+        SegmentTree::with_monoid(input, monoid)
+    }
+}
+
+/// Lazy Segment Tree Builder
+/// Configuration of a lazy segment tree, returned by `SegmentTreeBuilder::lazy`
+/// Requires the `lazy` feature
+/// monoid: Operation used to combine values and apply maps
+/// overflow_policy: Overflow policy to set on the monoid, or `None` to keep the policy of the monoid
+#[cfg(feature = "lazy")]
+#[derive(Debug, Clone)]
+pub struct LazySegmentTreeBuilder<A: ActedMonoid> {
+    monoid: A,
+    overflow_policy: Option<OverflowPolicy>,
+}
+
+#[cfg(feature = "lazy")]
+impl<A: ActedMonoid> LazySegmentTreeBuilder<A> {
+    /// Set how results that do not fit are handled, such as sums that do not fit in `isize`
+    /// overflow_policy: Overflow policy of the results
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> LazySegmentTreeBuilder<A> {
+        self.overflow_policy = Some(overflow_policy);
+        self
+    }
+
+    /// Build the configured lazy segment tree
+    /// input: Vector of input values
+    /// Returns a new `LazySegmentTree` structure or an error
+    pub fn build(self, input: &[A::Item]) -> Result<LazySegmentTree<A>, SegmentTreeError> {
+        let mut monoid = self.monoid;
+        if let Some(overflow_policy) = self.overflow_policy {
+            monoid.set_overflow_policy(overflow_policy);
+        }
+        LazySegmentTree::new(input, monoid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "lazy")]
+    use crate::RangeAddRangeSum;
+    use crate::{CompensatedSum, NumSum, OverflowPolicy, SegmentTreeBuilder, SegmentTreeError};
+
+    #[test]
+    fn test_builder() -> Result<(), SegmentTreeError> {
+        let sums = SegmentTreeBuilder::new().build(&[1, 2, 3])?;
+        assert_eq!(sums.query(0, 2)?, 6);
+
+        let saturating = SegmentTreeBuilder::new()
+            .overflow_policy(OverflowPolicy::Saturating)
+            .build(&[isize::MAX, 1])?;
+        assert_eq!(saturating.query(0, 1)?, isize::MAX);
+
+        let floats = SegmentTreeBuilder::new().monoid(CompensatedSum).build(&[0.5, 0.25])?;
+        assert_eq!(floats.query(0, 1)?, 0.75);
+
+        let mut minimums = SegmentTreeBuilder::new()
+            .operation(i64::MAX, |a: &i64, b: &i64| *a.min(b))
+            .build(&[5, 3, 8, 1])?;
+        assert_eq!(minimums.query(0, 2)?, 3);
+        minimums.update(1, 9)?;
+        assert_eq!(minimums.query(0, 2)?, 5);
+
+        let concatenated = SegmentTreeBuilder::new()
+            .operation(String::new(), |a: &String, b: &String| format!("{a}{b}"))
+            .build(&["a".to_string(), "b".to_string(), "c".to_string()])?;
+        assert_eq!(concatenated.query(1, 2)?, "bc");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_order() -> Result<(), SegmentTreeError> {
+        // The overflow policy is kept whether it is given before or after the monoid
+        let before = SegmentTreeBuilder::new()
+            .overflow_policy(OverflowPolicy::Saturating)
+            .monoid(NumSum::<i32>::default())
+            .build(&[i32::MAX, 1])?;
+        let after = SegmentTreeBuilder::new()
+            .monoid(NumSum::<i32>::default())
+            .overflow_policy(OverflowPolicy::Saturating)
+            .build(&[i32::MAX, 1])?;
+        assert_eq!(before.query(0, 1)?, i32::MAX);
+        assert_eq!(after.query(0, 1)?, i32::MAX);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "lazy")]
+    fn test_lazy_builder() -> Result<(), SegmentTreeError> {
+        let mut lazy = SegmentTreeBuilder::new()
+            .overflow_policy(OverflowPolicy::Saturating)
+            .lazy(RangeAddRangeSum::default())
+            .build(&[1, 2, 3, 4])?;
+        lazy.range_add(1, 2, 10)?;
+        assert_eq!(lazy.query(0, 3)?, 30);
+        lazy.range_add(0, 3, isize::MAX)?;
+        assert_eq!(lazy.query(0, 3)?, isize::MAX);
+
+        let checked = SegmentTreeBuilder::new().lazy(RangeAddRangeSum::default()).overflow_policy(OverflowPolicy::Checked).build(&[isize::MAX, 1])?;
+        assert_eq!(checked.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert!(SegmentTreeBuilder::new().lazy(RangeAddRangeSum::default()).build(&[]).is_err());
+
+        Ok(())
+    }
+}
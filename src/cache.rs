@@ -0,0 +1,126 @@
+//! Query Result Caching
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::collections::HashMap;
+
+/// Cached Segment Tree
+/// Segment tree that memoizes the results of recent queries, for callers that repeat the same ranges
+/// Updates go through the cache, which drops every cached range containing the updated index.
+/// When the cache is full, the least recently used range is evicted.
+/// tree: Segment tree being queried
+/// entries: Result of each cached range, with the tick of its last use
+/// capacity: Largest number of cached ranges
+/// tick: Counter of cache uses, ordering the entries by recency
+pub struct CachedSegmentTree<M: Monoid = Sum> {
+    tree: SegmentTree<M>,
+    entries: HashMap<(usize, usize), (M::Output, u64)>,
+    capacity: usize,
+    tick: u64,
+}
+
+impl<M: Monoid> CachedSegmentTree<M>
+where
+    M::Output: Clone,
+{
+    /// Wrap a segment tree in a query cache
+    /// tree: Segment tree to query
+    /// capacity: Largest number of cached ranges, 0 to disable caching
+    pub fn new(tree: SegmentTree<M>, capacity: usize) -> CachedSegmentTree<M> {
+        CachedSegmentTree { tree, entries: HashMap::with_capacity(capacity), capacity, tick: 0 }
+    }
+
+    /// Get the wrapped segment tree
+    pub fn tree(&self) -> &SegmentTree<M> {
+        &self.tree
+    }
+
+    /// Unwrap the segment tree, dropping the cache
+    pub fn into_inner(self) -> SegmentTree<M> {
+        self.tree
+    }
+
+    /// Get the number of cached ranges
+    pub fn cached(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Query a range, answering from the cache if it was queried since it last changed
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error. Errors are not cached
+    pub fn query(&mut self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
+        self.tick += 1;
+        if let Some((result, last_used)) = self.entries.get_mut(&(start, end)) {
+            *last_used = self.tick;
+            return Ok(result.clone());
+        }
+
+        let result = self.tree.query(start, end)?;
+        if self.capacity > 0 {
+            if self.entries.len() == self.capacity {
+                self.evict();
+            }
+            self.entries.insert((start, end), (result.clone(), self.tick));
+        }
+        Ok(result)
+    }
+
+    /// Update a leaf, dropping the cached ranges that contain it
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.update(index, new_value)?;
+        self.entries.retain(|&(start, end), _| index < start || end < index);
+        Ok(())
+    }
+
+    /// Drop every cached range
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Evict the least recently used range
+    fn evict(&mut self) {
+        let oldest = self.entries.iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(&range, _)| range);
+        if let Some(range) = oldest {
+            self.entries.remove(&range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CachedSegmentTree, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_cached_queries() -> Result<(), SegmentTreeError> {
+        let mut cached = CachedSegmentTree::new(SegmentTree::new(&[1, 2, 3, 4, 5, 6])?, 2);
+
+        assert_eq!(cached.query(0, 2)?, 6);
+        assert_eq!(cached.query(3, 5)?, 15);
+        assert_eq!(cached.query(0, 2)?, 6);
+        assert_eq!(cached.cached(), 2);
+
+        // The least recently used range is evicted
+        assert_eq!(cached.query(1, 4)?, 14);
+        assert_eq!(cached.cached(), 2);
+        assert_eq!(cached.query(0, 2)?, 6);
+        assert!(cached.query(4, 6).is_err());
+        assert_eq!(cached.cached(), 2);
+
+        // Only the ranges containing the updated index are dropped
+        cached.update(4, 50)?;
+        assert_eq!(cached.cached(), 1);
+        assert_eq!(cached.query(1, 4)?, 59);
+        assert_eq!(cached.query(0, 2)?, 6);
+
+        cached.clear();
+        assert_eq!(cached.cached(), 0);
+        assert_eq!(cached.into_inner().query(0, 5)?, 66);
+
+        Ok(())
+    }
+}
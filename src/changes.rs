@@ -0,0 +1,156 @@
+//! Change Tracking
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::ops::Deref;
+
+/// Marker
+/// Point in the update history of a `TrackedSegmentTree`, returned by `TrackedSegmentTree::marker`
+/// Markers count every update since tracking started, so they stay valid when older updates are forgotten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Marker(usize);
+
+/// Tracked Segment Tree
+/// Segment tree that records the index of every update, so callers can find the leaves changed
+/// since a `Marker` and sync derived data incrementally instead of rescanning every leaf.
+/// Queries go straight to the tree, which it dereferences to. Call `forget_before` with the oldest
+/// marker still in use to keep the log from growing forever.
+/// tree: Segment tree being updated
+/// log: Index of every update not forgotten yet, in the order they were made
+/// base: Number of updates forgotten, which is the marker of the first entry of the log
+pub struct TrackedSegmentTree<M: Monoid = Sum> {
+    tree: SegmentTree<M>,
+    log: Vec<usize>,
+    base: usize,
+}
+
+impl<M: Monoid> TrackedSegmentTree<M> {
+    /// Start tracking the updates to a segment tree
+    /// tree: Segment tree to track
+    pub fn new(tree: SegmentTree<M>) -> TrackedSegmentTree<M> {
+        TrackedSegmentTree { tree, log: Vec::new(), base: 0 }
+    }
+
+    /// Unwrap the segment tree, dropping the recorded updates
+    pub fn into_inner(self) -> SegmentTree<M> {
+        self.tree
+    }
+
+    /// Get a marker for the current point in the update history
+    pub fn marker(&self) -> Marker {
+        Marker(self.base + self.log.len())
+    }
+
+    /// Forget the updates made before a marker, in O(k) for the k updates kept
+    /// Markers taken before it can no longer tell which leaves changed, so they report every leaf
+    /// marker: Oldest marker still in use
+    pub fn forget_before(&mut self, marker: Marker) {
+        let forgotten = marker.0.saturating_sub(self.base).min(self.log.len());
+        self.log.drain(..forgotten);
+        self.base += forgotten;
+    }
+
+    /// Get the updates recorded since a marker
+    /// marker: Marker taken earlier from this tree
+    /// Returns the indices updated since the marker, or `None` if some of them were forgotten
+    fn log_since(&self, marker: Marker) -> Option<&[usize]> {
+        let start = marker.0.checked_sub(self.base)?;
+        Some(&self.log[start.min(self.log.len())..])
+    }
+
+    /// Update a leaf and record its index
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.update(index, new_value)?;
+        self.log.push(index);
+        Ok(())
+    }
+
+    /// Get the indices updated since a marker, in O(k log k) for k recorded updates
+    /// marker: Marker taken earlier from this tree
+    /// Returns the sorted indices of the updated leaves, each once, or every index if the marker was forgotten
+    pub fn changes_since(&self, marker: Marker) -> Vec<usize> {
+        let Some(log) = self.log_since(marker) else {
+            return (0..self.tree.len()).collect();
+        };
+
+        let mut indices = log.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// Get the smallest range containing every index updated since a marker, in O(k)
+    /// marker: Marker taken earlier from this tree
+    /// Returns the first and last updated indices, the whole tree if the marker was forgotten, or `None`
+    /// if nothing was updated
+    pub fn changed_range_since(&self, marker: Marker) -> Option<(usize, usize)> {
+        let Some(log) = self.log_since(marker) else {
+            return (!self.tree.is_empty()).then(|| (0, self.tree.len() - 1));
+        };
+
+        Some((*log.iter().min()?, *log.iter().max()?))
+    }
+}
+
+impl<M: Monoid> Deref for TrackedSegmentTree<M> {
+    type Target = SegmentTree<M>;
+
+    fn deref(&self) -> &SegmentTree<M> {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError, TrackedSegmentTree};
+
+    #[test]
+    fn test_changes_since() -> Result<(), SegmentTreeError> {
+        let mut tracked = TrackedSegmentTree::new(SegmentTree::new(&[0; 10])?);
+        let start = tracked.marker();
+
+        tracked.update(7, 1)?;
+        tracked.update(2, 1)?;
+        let middle = tracked.marker();
+        tracked.update(7, 2)?;
+        tracked.update(4, 3)?;
+        assert!(tracked.update(10, 1).is_err());
+
+        assert_eq!(tracked.changes_since(start), vec![2, 4, 7]);
+        assert_eq!(tracked.changes_since(middle), vec![4, 7]);
+        assert_eq!(tracked.changed_range_since(middle), Some((4, 7)));
+        assert_eq!(tracked.changes_since(tracked.marker()), Vec::<usize>::new());
+        assert_eq!(tracked.changed_range_since(tracked.marker()), None);
+        assert_eq!(tracked.query(0, 9)?, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_forget_before() -> Result<(), SegmentTreeError> {
+        let mut tracked = TrackedSegmentTree::new(SegmentTree::new(&[0; 10])?);
+        let start = tracked.marker();
+        for round in 0..1000 {
+            let marker = tracked.marker();
+            tracked.update(round % 10, round as isize)?;
+            tracked.update(3, 1)?;
+            let mut expected = vec![3, round % 10];
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(tracked.changes_since(marker), expected);
+            tracked.forget_before(marker);
+        }
+        assert_eq!(tracked.log.len(), 2);
+
+        // Forgotten markers report every leaf instead of missing changes
+        assert_eq!(tracked.changes_since(start), (0..10).collect::<Vec<usize>>());
+        assert_eq!(tracked.changed_range_since(start), Some((0, 9)));
+        let marker = tracked.marker();
+        tracked.forget_before(marker);
+        assert_eq!(tracked.changes_since(marker), Vec::<usize>::new());
+
+        Ok(())
+    }
+}
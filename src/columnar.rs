@@ -0,0 +1,281 @@
+//! Columnar Summary Tree
+//!
+//! A tree specialized for the sum, minimum and maximum of `isize` leaves, answering each with a
+//! bottom-up fold over its own array. Counts are computed from the ranges.
+//!
+//! It is a separate type, not a backend of `SegmentTree`: it has no monoid, no growth and no
+//! bulk operations. Keeping each aggregate in its own array was measured against the same nodes as an
+//! array of structs, with the same fold, and made no measurable difference for random ranges, so the
+//! layout is not a reason to choose it.
+
+use crate::{OverflowPolicy, RangeSummary, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Columnar Summary Tree
+/// Sum, minimum and maximum of `isize` leaves, each in its own array of a bottom-up tree
+/// sums: Sum of each node, in `i128`, with the leaves at `capacity..2 * capacity` and node 0 unused
+/// mins: Minimum of each node, laid out like `sums`
+/// maxs: Maximum of each node, laid out like `sums`
+/// len: Number of leaves
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnarSummaryTree {
+    sums: Vec<i128>,
+    mins: Vec<isize>,
+    maxs: Vec<isize>,
+    len: usize,
+    overflow_policy: OverflowPolicy,
+}
+
+impl ColumnarSummaryTree {
+    /// Create a columnar summary tree in O(n)
+    /// input: Value of each leaf
+    /// Returns a new `ColumnarSummaryTree` structure or an error
+    pub fn new(input: &[isize]) -> Result<ColumnarSummaryTree, SegmentTreeError> {
+        ColumnarSummaryTree::with_overflow_policy(input, OverflowPolicy::default())
+    }
+
+    /// Create a columnar summary tree with an overflow policy for sums
+    /// input: Value of each leaf
+    /// overflow_policy: How sums that do not fit in `isize` are handled
+    /// Returns a new `ColumnarSummaryTree` structure or an error
+    pub fn with_overflow_policy(input: &[isize], overflow_policy: OverflowPolicy) -> Result<ColumnarSummaryTree, SegmentTreeError> {
+        if input.is_empty() {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+        if input.len() > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: input.len(), max: MAX_INPUT_SIZE });
+        }
+
+        let capacity = input.len().next_power_of_two();
+        let mut tree = ColumnarSummaryTree {
+            sums: vec![0; 2 * capacity],
+            mins: vec![isize::MAX; 2 * capacity],
+            maxs: vec![isize::MIN; 2 * capacity],
+            len: input.len(),
+            overflow_policy,
+        };
+        for (index, &value) in input.iter().enumerate() {
+            tree.sums[capacity + index] = value as i128;
+            tree.mins[capacity + index] = value;
+            tree.maxs[capacity + index] = value;
+        }
+        for node_idx in (1..capacity).rev() {
+            tree.update_node(node_idx);
+        }
+        Ok(tree)
+    }
+
+    /// Get the number of leaves
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree has no leaves, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the value of a leaf
+    /// index: Index of the leaf
+    /// Returns the value, or an error if the index is out of bounds
+    pub fn get(&self, index: usize) -> Result<isize, SegmentTreeError> {
+        self.validate_range(index, index)?;
+
+        Ok(self.mins[self.capacity() + index])
+    }
+
+    /// Update the value of a leaf and recompute its ancestors in every array
+    /// index: Index of the leaf
+    /// value: New value of the leaf
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, value: isize) -> Result<(), SegmentTreeError> {
+        self.validate_range(index, index)?;
+
+        let mut node_idx = self.capacity() + index;
+        self.sums[node_idx] = value as i128;
+        self.mins[node_idx] = value;
+        self.maxs[node_idx] = value;
+        while node_idx > 1 {
+            node_idx /= 2;
+            self.update_node(node_idx);
+        }
+        Ok(())
+    }
+
+    /// Query the sum of a range, reading only the sums
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the sum of the range, or an error
+    pub fn query_sum(&self, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let sum = self.fold(&self.sums, start, end, 0, |left, right| left + right);
+        self.overflow_policy.narrow(sum, start, end)
+    }
+
+    /// Query the minimum of a range, reading only the minimums
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the minimum of the range, or an error
+    pub fn query_min(&self, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        Ok(self.fold(&self.mins, start, end, isize::MAX, isize::min))
+    }
+
+    /// Query the maximum of a range, reading only the maximums
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the maximum of the range, or an error
+    pub fn query_max(&self, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        Ok(self.fold(&self.maxs, start, end, isize::MIN, isize::max))
+    }
+
+    /// Query the sum, minimum, maximum and count of a range at once
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the `RangeSummary` of the range, or an error
+    pub fn query_summary(&self, start: usize, end: usize) -> Result<RangeSummary, SegmentTreeError> {
+        Ok(RangeSummary {
+            sum: self.query_sum(start, end)?,
+            min: self.query_min(start, end)?,
+            max: self.query_max(start, end)?,
+            count: end - start + 1,
+        })
+    }
+
+    /// Get the number of leaf slots, a power of two
+    fn capacity(&self) -> usize {
+        self.sums.len() / 2
+    }
+
+    /// Combine the nodes of one array covering a range, bottom-up
+    /// column: Array of one aggregate
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// identity: Value of an empty range
+    /// combine: Function combining two values, which must be commutative
+    fn fold<T: Copy>(&self, column: &[T], start: usize, end: usize, identity: T, combine: impl Fn(T, T) -> T) -> T {
+        let (mut lo, mut hi) = (start + self.capacity(), end + self.capacity() + 1);
+        let mut value = identity;
+        while lo < hi {
+            if lo % 2 == 1 {
+                value = combine(value, column[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                value = combine(value, column[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        value
+    }
+
+    /// Recompute a node of every array from its children
+    /// node_idx: Index of the internal node
+    fn update_node(&mut self, node_idx: usize) {
+        let (left, right) = (2 * node_idx, 2 * node_idx + 1);
+        self.sums[node_idx] = self.sums[left] + self.sums[right];
+        self.mins[node_idx] = self.mins[left].min(self.mins[right]);
+        self.maxs[node_idx] = self.maxs[left].max(self.maxs[right]);
+    }
+
+    /// Validate a range of leaves
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns `Ok(())` if the range is valid, otherwise an error
+    fn validate_range(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnarSummaryTree, SegmentTree, SegmentTreeError, Summary};
+    use std::time::Instant;
+
+    #[test]
+    fn test_columnar_summary_tree() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..37).map(|i| (i * 31) % 19 - 9).collect();
+        let mut columnar = ColumnarSummaryTree::new(&input)?;
+        let mut expected = SegmentTree::with_monoid(&input, Summary::default())?;
+
+        columnar.update(5, 100)?;
+        expected.update(5, 100)?;
+        for start in 0..37 {
+            for end in start..37 {
+                assert_eq!(columnar.query_summary(start, end)?, expected.query_summary(start, end)?);
+            }
+        }
+        assert_eq!(columnar.get(5)?, 100);
+        assert_eq!(columnar.query_sum(0, 37), Err(SegmentTreeError::IndexOutOfBounds { index: 37, len: 37 }));
+
+        let overflowing = ColumnarSummaryTree::new(&[isize::MAX, 1])?;
+        assert_eq!(overflowing.query_sum(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(overflowing.query_max(0, 1)?, isize::MAX);
+
+        Ok(())
+    }
+
+    /// Sum a range of a bottom-up tree, the same way for either layout
+    /// nodes: Nodes of the tree, with the leaves at `capacity..2 * capacity`
+    /// sum: Sum stored in a node
+    fn fold_sums<T>(nodes: &[T], start: usize, end: usize, sum: impl Fn(&T) -> i128) -> i128 {
+        let capacity = nodes.len() / 2;
+        let (mut lo, mut hi) = (start + capacity, end + capacity + 1);
+        let mut value = 0;
+        while lo < hi {
+            if lo % 2 == 1 {
+                value += sum(&nodes[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                value += sum(&nodes[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        value
+    }
+
+    #[test]
+    fn test_columnar_sum_performance() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..1 << 20).map(|i| i % 1000 - 500).collect();
+        let columnar = ColumnarSummaryTree::new(&input)?;
+        // The same nodes as an array of structs, so only the layout differs between the two folds
+        let interleaved: Vec<(i128, isize, isize)> = (0..columnar.sums.len())
+            .map(|node_idx| (columnar.sums[node_idx], columnar.mins[node_idx], columnar.maxs[node_idx]))
+            .collect();
+        let queries: Vec<(usize, usize)> = (0..1_000_000usize).map(|i| {
+            let (a, b) = (i * 7919 % input.len(), i * 104_729 % input.len());
+            (a.min(b), a.max(b))
+        }).collect();
+
+        let start = Instant::now();
+        let columnar_sums: Vec<i128> = queries.iter().map(|&(l, r)| fold_sums(&columnar.sums, l, r, |&sum| sum)).collect();
+        let columnar_time = start.elapsed();
+
+        let start = Instant::now();
+        let interleaved_sums: Vec<i128> = queries.iter().map(|&(l, r)| fold_sums(&interleaved, l, r, |node| node.0)).collect();
+        let interleaved_time = start.elapsed();
+
+        assert_eq!(columnar_sums, interleaved_sums);
+        assert_eq!(columnar.query_sum(queries[1].0, queries[1].1)? as i128, columnar_sums[1]);
+        // The layout alone makes no measurable difference: each node read is a cache miss either way
+        println!("Columnar sum time: {:?}", columnar_time); // Columnar sum time: 192.082219ms
+        println!("Interleaved sum time: {:?}", interleaved_time); // Interleaved sum time: 196.152659ms
+        Ok(())
+    }
+}
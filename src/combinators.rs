@@ -0,0 +1,150 @@
+//! Tree Combinators
+//!
+//! Build new trees from the leaves of existing ones, rebuilding the nodes in O(n).
+
+use crate::{Monoid, OverflowPolicy, SegmentTree, SegmentTreeError};
+
+/// Zip
+/// Pair of monoids combining pairs of items, so one query gives the results of both
+/// 0: Monoid of the first items
+/// 1: Monoid of the second items
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Zip<A, B>(pub A, pub B);
+
+impl<A: Monoid, B: Monoid> Monoid for Zip<A, B> {
+    type Item = (A::Item, B::Item);
+    type Value = (A::Value, B::Value);
+    type Output = (A::Output, B::Output);
+
+    fn validate(&self, item: &(A::Item, B::Item), index: usize) -> Result<(), SegmentTreeError> {
+        self.0.validate(&item.0, index)?;
+        self.1.validate(&item.1, index)
+    }
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.0.set_overflow_policy(overflow_policy);
+        self.1.set_overflow_policy(overflow_policy);
+    }
+
+    fn identity(&self) -> (A::Value, B::Value) {
+        (self.0.identity(), self.1.identity())
+    }
+
+    fn lift(&self, item: &(A::Item, B::Item)) -> (A::Value, B::Value) {
+        (self.0.lift(&item.0), self.1.lift(&item.1))
+    }
+
+    fn combine(&self, left: &(A::Value, B::Value), right: &(A::Value, B::Value)) -> (A::Value, B::Value) {
+        (self.0.combine(&left.0, &right.0), self.1.combine(&left.1, &right.1))
+    }
+
+    fn combine_into(&self, left: &mut (A::Value, B::Value), right: &(A::Value, B::Value)) {
+        self.0.combine_into(&mut left.0, &right.0);
+        self.1.combine_into(&mut left.1, &right.1);
+    }
+
+    fn finish(&self, value: (A::Value, B::Value), start: usize, end: usize) -> Result<(A::Output, B::Output), SegmentTreeError> {
+        Ok((self.0.finish(value.0, start, end)?, self.1.finish(value.1, start, end)?))
+    }
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Transform every leaf into a new tree, in O(n), such as absolute or squared values
+    /// monoid: Operation used to combine the values of the new tree
+    /// f: Function transforming each leaf
+    /// Returns the new tree, or an error if a transformed leaf is invalid
+    pub fn map<N: Monoid, F: FnMut(&M::Item) -> N::Item>(&self, monoid: N, f: F) -> Result<SegmentTree<N>, SegmentTreeError> {
+        SegmentTree::from_leaves(self.leaves.iter().map(f).collect(), monoid)
+    }
+}
+
+impl<M: Monoid + Clone> SegmentTree<M> {
+    /// Combine two trees of the same length leaf by leaf into a new tree, in O(n)
+    /// other: Tree whose leaves are combined with the leaves of this one
+    /// op: Function combining the leaves at each index, this tree's first
+    /// Returns the new tree with the monoid of this tree, or an error if the lengths differ or a combined leaf is invalid
+    pub fn merge_with<F: FnMut(&M::Item, &M::Item) -> M::Item>(&self, other: &SegmentTree<M>, mut op: F) -> Result<SegmentTree<M>, SegmentTreeError> {
+        if other.leaf_len != self.leaf_len {
+            return Err(SegmentTreeError::LengthMismatch { expected: self.leaf_len, actual: other.leaf_len });
+        }
+
+        let leaves = self.leaves.iter().zip(&other.leaves).map(|(left, right)| op(left, right)).collect();
+        SegmentTree::from_leaves(leaves, self.monoid.clone())
+    }
+
+    /// Pair the leaves of two trees of the same length into a new tree, in O(n)
+    /// Every node of the new tree holds the pair of aggregates, so one query returns both results
+    /// from the same values
+    /// other: Tree providing the second item of each pair
+    /// Returns the tree of pairs, or an error if the lengths differ
+    pub fn zip<N: Monoid + Clone>(&self, other: &SegmentTree<N>) -> Result<SegmentTree<Zip<M, N>>, SegmentTreeError> {
+        if other.leaf_len != self.leaf_len {
+            return Err(SegmentTreeError::LengthMismatch { expected: self.leaf_len, actual: other.leaf_len });
+        }
+
+        let leaves = self.leaves.iter().cloned().zip(other.leaves.iter().cloned()).collect();
+        SegmentTree::from_leaves(leaves, Zip(self.monoid.clone(), other.monoid.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Max, SegmentTree, SegmentTreeError, Sum, Zip};
+
+    #[test]
+    fn test_merge_with() -> Result<(), SegmentTreeError> {
+        let first = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        let second = SegmentTree::new(&[10, 20, 30, 40, 50])?;
+
+        let merged = first.merge_with(&second, |left, right| left + right)?;
+        assert_eq!(merged.leaves(), &[11, 22, 33, 44, 55]);
+        assert_eq!(merged.query(1, 3)?, 99);
+
+        let first = SegmentTree::with_monoid(&[3, 9, 1], Max)?;
+        let second = SegmentTree::with_monoid(&[5, 2, 7], Max)?;
+        let merged = first.merge_with(&second, |left, right| *left.max(right))?;
+        assert_eq!(merged.leaves(), &[5, 9, 7]);
+        assert_eq!(merged.query(0, 2)?, 9);
+
+        let short = SegmentTree::with_monoid(&[1], Max)?;
+        assert_eq!(first.merge_with(&short, |left, _| *left).err(), Some(SegmentTreeError::LengthMismatch { expected: 3, actual: 1 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip() -> Result<(), SegmentTreeError> {
+        let sums = SegmentTree::new(&[4, -2, 7, 1])?;
+        let maxima = SegmentTree::with_monoid(&[4, -2, 7, 1], Max)?;
+
+        let mut zipped = sums.zip(&maxima)?;
+        assert_eq!(zipped.query(0, 3)?, (10, 7));
+        assert_eq!(zipped.query(1, 1)?, (-2, -2));
+        zipped.update(2, (0, 0))?;
+        assert_eq!(zipped.query(0, 3)?, (3, 4));
+
+        let monoid = Zip(Sum::default(), Max);
+        let zipped = SegmentTree::with_monoid(&[(isize::MAX, 1), (1, 2)], monoid)?;
+        assert_eq!(zipped.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(zipped.query(1, 1)?, (1, 2));
+        assert!(sums.zip(&SegmentTree::with_monoid(&[1], Max)?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_map() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[3, -4, 1, -2])?;
+
+        let absolute = segment_tree.map(Sum::default(), |value| value.abs())?;
+        assert_eq!(absolute.query(0, 3)?, 10);
+        let squares = segment_tree.map(Max, |value| value * value)?;
+        assert_eq!(squares.query(0, 3)?, 16);
+        assert_eq!(squares.query(2, 3)?, 4);
+
+        let checked = SegmentTree::new(&[1, isize::MAX])?;
+        assert_eq!(checked.map(Sum::default(), |&value| value)?.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Compensated Floating Point Sums
+
+use crate::{Monoid, SegmentTreeError};
+
+/// Compensated
+/// Floating point sum carrying the rounding error lost while adding it up
+/// sum: Rounded sum
+/// compensation: Accumulated rounding error of `sum`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Compensated {
+    pub sum: f64,
+    pub compensation: f64,
+}
+
+impl Compensated {
+    /// Get the compensated value of the sum
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+/// Compensated Sum
+/// Sum of `f64` leaves using Neumaier summation in every node, so long ranges of small values
+/// do not lose precision to rounding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompensatedSum;
+
+impl Monoid for CompensatedSum {
+    type Item = f64;
+    type Value = Compensated;
+    type Output = f64;
+
+    fn identity(&self) -> Compensated {
+        Compensated::default()
+    }
+
+    fn lift(&self, item: &f64) -> Compensated {
+        Compensated {
+            sum: *item,
+            compensation: 0.0,
+        }
+    }
+
+    fn combine(&self, left: &Compensated, right: &Compensated) -> Compensated {
+        let sum = left.sum + right.sum;
+        if !sum.is_finite() {
+            // The rounding error of an infinite or NaN sum is meaningless
+            return Compensated { sum, compensation: 0.0 };
+        }
+
+        // The low-order bits lost by the addition belong to the operand with the smaller magnitude
+        let error = if left.sum.abs() >= right.sum.abs() {
+            (left.sum - sum) + right.sum
+        } else {
+            (right.sum - sum) + left.sum
+        };
+
+        Compensated {
+            sum,
+            compensation: left.compensation + right.compensation + error,
+        }
+    }
+
+    fn finish(&self, value: Compensated, _start: usize, _end: usize) -> Result<f64, SegmentTreeError> {
+        Ok(value.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompensatedSum, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_compensated_sum() -> Result<(), SegmentTreeError> {
+        // A naive sum loses the 1.0 entirely
+        let input = vec![1e16, 1.0, -1e16, 0.0];
+        let segment_tree = SegmentTree::with_monoid(&input, CompensatedSum)?;
+        assert_eq!(segment_tree.query(0, 3)?, 1.0);
+        assert_eq!(segment_tree.query(1, 1)?, 1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compensated_long_range() -> Result<(), SegmentTreeError> {
+        let input = vec![0.1; 100_000];
+        let mut segment_tree = SegmentTree::with_monoid(&input, CompensatedSum)?;
+
+        let naive: f64 = input.iter().sum();
+        let compensated = segment_tree.query(0, 99_999)?;
+        assert!((compensated - 10_000.0).abs() < (naive - 10_000.0).abs());
+        assert!((compensated - 10_000.0).abs() < 1e-9);
+
+        segment_tree.update(0, 0.2)?;
+        segment_tree.push(0.3)?;
+        assert!((segment_tree.query(0, 100_000)? - 10_000.4).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compensated_non_finite() -> Result<(), SegmentTreeError> {
+        let input = vec![f64::INFINITY, 1.0];
+        let segment_tree = SegmentTree::with_monoid(&input, CompensatedSum)?;
+        assert_eq!(segment_tree.query(0, 1)?, f64::INFINITY);
+
+        Ok(())
+    }
+}
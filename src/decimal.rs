@@ -0,0 +1,147 @@
+//! Fixed Point Decimal Sums
+
+use crate::{Monoid, SegmentTreeError};
+use std::fmt;
+
+/// Decimal
+/// Fixed point decimal number, equal to `mantissa / 10^scale`
+/// mantissa: Value in units of the last decimal place
+/// scale: Number of decimal places
+/// Equality compares the fields, so `1.0` and `1.00` are different decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    pub mantissa: i64,
+    pub scale: u32,
+}
+
+impl Decimal {
+    /// Create a new decimal
+    /// mantissa: Value in units of the last decimal place
+    /// scale: Number of decimal places
+    pub fn new(mantissa: i64, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    /// Get the mantissa of the decimal at another scale
+    /// scale: Number of decimal places, at least the scale of the decimal
+    /// Returns the mantissa, or `None` if it would lose digits or does not fit in `i64`
+    pub fn rescale(&self, scale: u32) -> Option<i64> {
+        let factor = 10i64.checked_pow(scale.checked_sub(self.scale)?)?;
+        self.mantissa.checked_mul(factor)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Decimal Sum
+/// Exact sum of `Decimal` leaves, such as money amounts, at a fixed scale
+/// scale: Number of decimal places of the sums
+/// Leaves with more decimal places than the tree, or that do not fit in `i64` at its scale, are rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecimalSum {
+    pub scale: u32,
+}
+
+impl DecimalSum {
+    /// Create a new decimal sum
+    /// scale: Number of decimal places of the sums
+    pub fn new(scale: u32) -> DecimalSum {
+        DecimalSum { scale }
+    }
+}
+
+impl Monoid for DecimalSum {
+    type Item = Decimal;
+    /// `None` if a leaf does not fit the scale of the sums, which queries covering it report as an overflow
+    type Value = Option<i128>;
+    type Output = Decimal;
+
+    fn validate(&self, item: &Decimal, index: usize) -> Result<(), SegmentTreeError> {
+        match item.rescale(self.scale) {
+            Some(_) => Ok(()),
+            None => Err(SegmentTreeError::InvalidItem { index }),
+        }
+    }
+
+    fn identity(&self) -> Option<i128> {
+        Some(0)
+    }
+
+    fn lift(&self, item: &Decimal) -> Option<i128> {
+        item.rescale(self.scale).map(i128::from)
+    }
+
+    fn combine(&self, left: &Option<i128>, right: &Option<i128>) -> Option<i128> {
+        Some((*left)? + (*right)?)
+    }
+
+    fn finish(&self, value: Option<i128>, start: usize, end: usize) -> Result<Decimal, SegmentTreeError> {
+        let mantissa = value.and_then(|value| i64::try_from(value).ok()).ok_or(SegmentTreeError::Overflow { start, end })?;
+        Ok(Decimal::new(mantissa, self.scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decimal, DecimalSum, Monoid, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_decimal_sum() -> Result<(), SegmentTreeError> {
+        // 0.10 + 0.20 is exactly 0.30, unlike with f64
+        let input = vec![Decimal::new(10, 2), Decimal::new(20, 2), Decimal::new(5, 0), Decimal::new(-125, 1)];
+        let mut segment_tree = SegmentTree::with_monoid(&input, DecimalSum::new(2))?;
+        assert_eq!(segment_tree.query(0, 1)?, Decimal::new(30, 2));
+        assert_eq!(segment_tree.query(0, 3)?, Decimal::new(-720, 2));
+        assert_eq!(segment_tree.query(0, 3)?.to_string(), "-7.20");
+
+        segment_tree.update(3, Decimal::new(1, 2))?;
+        assert_eq!(segment_tree.query(2, 3)?.to_string(), "5.01");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal_validation() -> Result<(), SegmentTreeError> {
+        let input = vec![Decimal::new(1, 2), Decimal::new(1, 3)];
+        assert!(matches!(
+            SegmentTree::with_monoid(&input, DecimalSum::new(2)),
+            Err(SegmentTreeError::InvalidItem { index: 1 })
+        ));
+
+        let mut segment_tree = SegmentTree::with_monoid(&input[..1], DecimalSum::new(2))?;
+        assert_eq!(segment_tree.update(0, Decimal::new(i64::MAX, 0)), Err(SegmentTreeError::InvalidItem { index: 0 }));
+        assert_eq!(segment_tree.push(Decimal::new(1, 3)), Err(SegmentTreeError::InvalidItem { index: 1 }));
+        assert_eq!(segment_tree.resize(1_000, Decimal::new(1, 3)), Err(SegmentTreeError::InvalidItem { index: 1 }));
+        assert_eq!(segment_tree.len(), 1);
+        assert!(segment_tree.update_batch(&[(0, Decimal::new(2, 2)), (0, Decimal::new(1, 5))]).is_err());
+        assert_eq!(segment_tree.query(0, 0)?, Decimal::new(1, 2));
+        assert_eq!(segment_tree.len(), 1);
+
+        // Leaves that bypass validation make queries fail instead of panicking
+        let monoid = DecimalSum::new(2);
+        assert_eq!(monoid.finish(monoid.lift(&Decimal::new(1, 3)), 0, 0), Err(SegmentTreeError::Overflow { start: 0, end: 0 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal_display() {
+        assert_eq!(Decimal::new(5, 3).to_string(), "0.005");
+        assert_eq!(Decimal::new(-5, 3).to_string(), "-0.005");
+        assert_eq!(Decimal::new(12345, 2).to_string(), "123.45");
+        assert_eq!(Decimal::new(42, 0).to_string(), "42");
+    }
+}
@@ -0,0 +1,164 @@
+//! Deferred Updates
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+
+/// Deferred
+/// Write-heavy access to a segment tree, returned by `SegmentTree::defer`
+/// Updates only write their leaf and mark it dirty. The ancestors of dirty leaves are recomputed
+/// by the next query whose range contains them, by `flush`, or when the `Deferred` is dropped,
+/// with every shared ancestor recomputed once.
+/// tree: Segment tree being updated
+/// dirty: Indices of the dirty leaves, in the order they were first written
+/// marked: Whether each leaf is dirty, so repeated writes to a leaf are recorded once
+pub struct Deferred<'a, M: Monoid = Sum> {
+    tree: &'a mut SegmentTree<M>,
+    dirty: Vec<usize>,
+    marked: Vec<bool>,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Start deferring the recomputation of nodes after updates
+    /// Returns a `Deferred` that recomputes all pending nodes when dropped
+    pub fn defer(&mut self) -> Deferred<'_, M> {
+        let marked = vec![false; self.leaf_len];
+        Deferred { tree: self, dirty: Vec::new(), marked }
+    }
+}
+
+impl<M: Monoid> Deferred<'_, M> {
+    /// Update a leaf value without recomputing its ancestors
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.validate_index(index)?;
+        self.tree.monoid.validate(&new_value, index)?;
+
+        let leaf_node = self.tree.leaf_indices[index];
+        self.tree.nodes[leaf_node].value = self.tree.monoid.lift(&new_value);
+        self.tree.leaves[index] = new_value;
+        self.tree.mirror_node(leaf_node);
+
+        if !self.marked[index] {
+            self.marked[index] = true;
+            self.dirty.push(index);
+        }
+        Ok(())
+    }
+
+    /// Query a range, first recomputing the ancestors of the dirty leaves inside it
+    /// Dirty leaves outside the range stay pending, as no node covering the range depends on them
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&mut self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
+        self.tree.validate_public_query(start, end)?;
+
+        let (inside, outside): (Vec<usize>, Vec<usize>) =
+            self.dirty.iter().partition(|&&index| start <= index && index <= end);
+        self.dirty = outside;
+        self.recompute(inside);
+
+        self.tree.query(start, end)
+    }
+
+    /// Recompute the ancestors of every dirty leaf
+    pub fn flush(&mut self) {
+        let dirty = std::mem::take(&mut self.dirty);
+        self.recompute(dirty);
+    }
+
+    /// Get the number of leaves whose ancestors have not been recomputed
+    pub fn pending(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Get the leaf values, including the pending updates
+    pub fn leaves(&self) -> &[M::Item] {
+        self.tree.leaves()
+    }
+
+    /// Recompute the ancestors of some dirty leaves and unmark them
+    /// indices: Indices of the leaves
+    fn recompute(&mut self, indices: Vec<usize>) {
+        if indices.is_empty() {
+            return;
+        }
+
+        let leaf_nodes = indices.iter()
+            .map(|&index| {
+                self.marked[index] = false;
+                self.tree.leaf_indices[index]
+            })
+            .collect();
+        self.tree.update_leaf_paths(leaf_nodes);
+    }
+}
+
+impl Deferred<'_> {
+    /// Add a delta to a leaf value without recomputing its ancestors
+    /// index: Index of the leaf node to update
+    /// delta: Value to add to the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn add(&mut self, index: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.tree.validate_index(index)?;
+
+        let new_value = self.tree.monoid.overflow_policy.add(self.tree.leaves[index], delta, index)?;
+        self.update(index, new_value)
+    }
+}
+
+impl<M: Monoid> Drop for Deferred<'_, M> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_deferred_updates() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5, 6, 7, 8])?;
+
+        let mut deferred = segment_tree.defer();
+        deferred.update(0, 10)?;
+        deferred.add(0, 5)?;
+        deferred.update(6, 70)?;
+        deferred.add(7, -8)?;
+        assert_eq!(deferred.pending(), 3);
+        assert!(deferred.update(8, 1).is_err());
+
+        // Only the dirty leaves inside the range are recomputed
+        assert_eq!(deferred.query(0, 3)?, 24);
+        assert_eq!(deferred.pending(), 2);
+        assert_eq!(deferred.query(4, 7)?, 81);
+        assert_eq!(deferred.pending(), 0);
+
+        deferred.update(3, 40)?;
+        deferred.flush();
+        assert_eq!(deferred.query(0, 7)?, 141);
+        assert_eq!(deferred.leaves(), &[15, 2, 3, 40, 5, 6, 70, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deferred_flushes_on_drop() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&(0..100).collect::<Vec<isize>>())?;
+        {
+            let mut deferred = segment_tree.defer();
+            for index in 0..100 {
+                deferred.add(index, 1)?;
+                deferred.add(index, 1)?;
+            }
+            assert_eq!(deferred.pending(), 100);
+        }
+
+        assert_eq!(segment_tree.verify(), Ok(()));
+        assert_eq!(segment_tree.query(0, 99)?, 5150);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Tree Diffs
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Difference
+/// Leaf that differs between two segment trees, as its index, its value in the first tree and its value in the second
+pub type Difference<T> = (usize, T, T);
+
+// Next unused id of a `DiffableSegmentTree`, taken when one is created or cloned, never on an update
+static NEXT_TREE_ID: AtomicU64 = AtomicU64::new(0);
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Item: PartialEq,
+{
+    /// List the leaves whose values differ between two segment trees of the same length, in O(n)
+    /// Every leaf is compared, since equal aggregates do not mean equal leaves. Wrap replicas in a
+    /// `DiffableSegmentTree` to skip the subtrees neither side wrote to since they were cloned.
+    /// other: Segment tree to compare with
+    /// Returns the index, the value in this tree and the value in `other` of every differing leaf,
+    /// in index order, or an error if the lengths differ
+    pub fn diff(&self, other: &SegmentTree<M>) -> Result<Vec<Difference<M::Item>>, SegmentTreeError> {
+        if self.leaf_len != other.leaf_len {
+            return Err(SegmentTreeError::LengthMismatch { expected: self.leaf_len, actual: other.leaf_len });
+        }
+
+        Ok(self.leaves.iter().zip(&other.leaves).enumerate()
+            .filter(|(_, (value, other_value))| value != other_value)
+            .map(|(index, (value, other_value))| (index, value.clone(), other_value.clone()))
+            .collect())
+    }
+}
+
+/// Diffable Segment Tree
+/// Segment tree that stamps every node an update recomputes, so two replicas cloned from each other
+/// can be diffed in O(k log n) for the k leaves written since the clone. Stamps pair the id of the
+/// tree with a counter of its own updates, so updates stay free of atomics and a clone, which takes a
+/// new id, never hands out a stamp the original already used. Nodes holding the same stamp in two
+/// trees were last recomputed by the same update, so they hold the same leaves below them.
+/// Queries go straight to the tree, which it dereferences to.
+/// tree: Segment tree being updated
+/// stamps: Tree id and update counter of the update that last recomputed each node, by node index
+/// id: Id of this tree, stamped on the nodes it recomputes
+/// writes: Number of updates made by this tree, the counter of its next stamp
+pub struct DiffableSegmentTree<M: Monoid = Sum> {
+    tree: SegmentTree<M>,
+    stamps: Vec<(u64, u64)>,
+    id: u64,
+    writes: u64,
+}
+
+impl<M: Monoid> DiffableSegmentTree<M> {
+    /// Start stamping the updates to a segment tree
+    /// Trees wrapped separately share no stamps, so their first diff compares every leaf
+    /// tree: Segment tree to stamp
+    pub fn new(tree: SegmentTree<M>) -> DiffableSegmentTree<M> {
+        let id = NEXT_TREE_ID.fetch_add(1, Ordering::Relaxed);
+        DiffableSegmentTree { stamps: vec![(id, 0); tree.nodes.len()], tree, id, writes: 0 }
+    }
+
+    /// Unwrap the segment tree, dropping the stamps
+    pub fn into_inner(self) -> SegmentTree<M> {
+        self.tree
+    }
+
+    /// Update a leaf and stamp the nodes from it to the root
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.update(index, new_value)?;
+        self.writes += 1;
+
+        let mut node_idx = self.tree.leaf_indices[index];
+        loop {
+            self.stamps[node_idx] = (self.id, self.writes);
+            if node_idx == 0 {
+                return Ok(());
+            }
+            node_idx = (node_idx - 1) / 2;
+        }
+    }
+}
+
+impl<M: Monoid> DiffableSegmentTree<M>
+where
+    M::Item: PartialEq,
+{
+    /// List the leaves whose values differ between two stamped trees of the same length
+    /// Subtrees holding the same stamp in both trees are skipped, the others are compared down to
+    /// their leaves, so no difference is missed even when the aggregates are equal
+    /// other: Stamped tree to compare with
+    /// Returns the index, the value in this tree and the value in `other` of every differing leaf,
+    /// in index order, or an error if the lengths differ
+    pub fn diff(&self, other: &DiffableSegmentTree<M>) -> Result<Vec<Difference<M::Item>>, SegmentTreeError> {
+        if self.tree.leaf_len != other.tree.leaf_len || self.stamps.len() != other.stamps.len() {
+            return self.tree.diff(&other.tree);
+        }
+
+        let mut differences = Vec::new();
+        if self.tree.leaf_len > 0 {
+            self.diff_recursive(other, 0, &mut differences);
+        }
+
+        Ok(differences)
+    }
+
+    /// Collect the differing leaves below a node of two trees with the same layout (Recursive)
+    /// other: Stamped tree to compare with
+    /// node_idx: Index of the current node in both trees
+    /// differences: Differing leaves found so far
+    fn diff_recursive(&self, other: &DiffableSegmentTree<M>, node_idx: usize, differences: &mut Vec<Difference<M::Item>>) {
+        let node = &self.tree.nodes[node_idx];
+        if node.start >= self.tree.leaf_len || self.stamps[node_idx] == other.stamps[node_idx] {
+            return;
+        }
+
+        match self.tree.children(node_idx) {
+            Some((left, right)) => {
+                self.diff_recursive(other, left, differences);
+                self.diff_recursive(other, right, differences);
+            }
+            None => {
+                let (value, other_value) = (&self.tree.leaves[node.start], &other.tree.leaves[node.start]);
+                if value != other_value {
+                    differences.push((node.start, value.clone(), other_value.clone()));
+                }
+            }
+        }
+    }
+}
+
+impl<M: Monoid + Clone> Clone for DiffableSegmentTree<M> {
+    /// Clone the tree and its stamps under a new id, so updates to either side are told apart
+    fn clone(&self) -> Self {
+        DiffableSegmentTree {
+            tree: self.tree.clone(),
+            stamps: self.stamps.clone(),
+            id: NEXT_TREE_ID.fetch_add(1, Ordering::Relaxed),
+            writes: 0,
+        }
+    }
+}
+
+impl<M: Monoid> Deref for DiffableSegmentTree<M> {
+    type Target = SegmentTree<M>;
+
+    fn deref(&self) -> &SegmentTree<M> {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DiffableSegmentTree, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_diff() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..100).collect();
+        let replica = SegmentTree::new(&input)?;
+        let mut primary = SegmentTree::new(&input)?;
+        assert_eq!(primary.diff(&replica)?, vec![]);
+
+        primary.update(7, -1)?;
+        primary.add(64, 10)?;
+        assert_eq!(primary.diff(&replica)?, vec![(7, -1, 7), (64, 74, 64)]);
+        assert_eq!(replica.diff(&primary)?, vec![(7, 7, -1), (64, 64, 74)]);
+
+        // Changes that keep every aggregate, such as a swapped pair under `Sum`, are still found
+        let original = SegmentTree::new(&[1, 2, 3, 4])?;
+        assert_eq!(original.diff(&SegmentTree::new(&[2, 1, 3, 4])?)?, vec![(0, 1, 2), (1, 2, 1)]);
+        assert_eq!(original.diff(&SegmentTree::new(&[1, 2, 3, 4])?)?, vec![]);
+
+        // Same values in a tree with a different capacity
+        let mut grown = SegmentTree::new(&input)?;
+        grown.extend(0..100);
+        grown.truncate(100);
+        grown.update(99, 0)?;
+        assert_eq!(replica.diff(&grown)?, vec![(99, 99, 0)]);
+
+        assert_eq!(
+            replica.diff(&SegmentTree::new(&[1, 2])?),
+            Err(SegmentTreeError::LengthMismatch { expected: 100, actual: 2 })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diffable() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..100).collect();
+        let mut primary = DiffableSegmentTree::new(SegmentTree::new(&input)?);
+        let mut replica = primary.clone();
+        assert_eq!(primary.diff(&replica)?, vec![]);
+
+        // Writes on either side are found, and the replica's own writes get stamps the primary never used
+        primary.update(7, -1)?;
+        replica.update(64, 10)?;
+        assert_eq!(primary.diff(&replica)?, vec![(7, -1, 7), (64, 64, 10)]);
+
+        // A swapped pair keeps every aggregate but not the stamps
+        let original = DiffableSegmentTree::new(SegmentTree::new(&[1, 2, 3, 4])?);
+        let mut swapped = original.clone();
+        swapped.update(0, 2)?;
+        swapped.update(1, 1)?;
+        assert_eq!(original.diff(&swapped)?, vec![(0, 1, 2), (1, 2, 1)]);
+        assert_eq!(swapped.query(0, 3)?, 10);
+
+        // Trees wrapped separately share no stamps, so every leaf is compared
+        let separate = DiffableSegmentTree::new(SegmentTree::new(&[2, 1, 3, 4])?);
+        assert_eq!(original.diff(&separate)?, vec![(0, 1, 2), (1, 2, 1)]);
+        assert!(original.diff(&DiffableSegmentTree::new(SegmentTree::new(&[1, 2])?)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Tree Printing
+
+use crate::{Monoid, SegmentTree};
+use std::fmt;
+
+/// Print the segment tree one node per line, children indented under their parent
+/// Each line shows the inclusive range of the node and its value, padding past the last leaf is left out
+/// ```text
+/// [0, 2] 6
+///   [0, 1] 3
+///     [0, 0] 1
+///     [1, 1] 2
+///   [2, 2] 3
+/// ```
+impl<M: Monoid> fmt::Display for SegmentTree<M>
+where
+    M::Value: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.leaf_len == 0 {
+            return writeln!(f, "(empty)");
+        }
+
+        self.fmt_node(f, 0, 0)
+    }
+}
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Value: fmt::Display,
+{
+    /// Print a node and its children (Recursive)
+    /// f: Formatter to print to
+    /// node_idx: Index of the node
+    /// depth: Depth of the node, used for the indentation
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>, node_idx: usize, depth: usize) -> fmt::Result {
+        let node = &self.nodes[node_idx];
+        let end = node.end.min(self.leaf_len - 1);
+
+        // A node whose right child is all padding has the same range and value as its left child
+        let children = self.children(node_idx);
+        if let Some((left, right)) = children {
+            if self.nodes[right].start >= self.leaf_len {
+                return self.fmt_node(f, left, depth);
+            }
+        }
+
+        writeln!(f, "{:indent$}[{}, {}] {}", "", node.start, end, node.value, indent = depth * 2)?;
+        for child in children.into_iter().flat_map(|(left, right)| [left, right]) {
+            self.fmt_node(f, child, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_display() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        assert_eq!(
+            segment_tree.to_string(),
+            "[0, 2] 6\n  [0, 1] 3\n    [0, 0] 1\n    [1, 1] 2\n  [2, 2] 3\n"
+        );
+
+        segment_tree.truncate(0);
+        assert_eq!(segment_tree.to_string(), "(empty)\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Duration Aggregates
+
+use crate::{Monoid, SegmentTreeError};
+use std::time::Duration;
+
+/// Duration Sum
+/// Sum of `Duration` leaves, such as per-slot latency totals
+/// Sums are added with `checked_add`, and a range whose sum overflows `Duration` returns an error
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationSum;
+
+impl Monoid for DurationSum {
+    type Item = Duration;
+    /// `None` once the sum has overflowed
+    type Value = Option<Duration>;
+    type Output = Duration;
+
+    fn identity(&self) -> Option<Duration> {
+        Some(Duration::ZERO)
+    }
+
+    fn lift(&self, item: &Duration) -> Option<Duration> {
+        Some(*item)
+    }
+
+    fn combine(&self, left: &Option<Duration>, right: &Option<Duration>) -> Option<Duration> {
+        left.and_then(|left| left.checked_add((*right)?))
+    }
+
+    fn finish(&self, value: Option<Duration>, start: usize, end: usize) -> Result<Duration, SegmentTreeError> {
+        value.ok_or(SegmentTreeError::Overflow { start, end })
+    }
+}
+
+/// Duration Min
+/// Shortest of the `Duration` leaves in a range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationMin;
+
+impl Monoid for DurationMin {
+    type Item = Duration;
+    type Value = Duration;
+    type Output = Duration;
+
+    fn identity(&self) -> Duration {
+        Duration::MAX
+    }
+
+    fn lift(&self, item: &Duration) -> Duration {
+        *item
+    }
+
+    fn combine(&self, left: &Duration, right: &Duration) -> Duration {
+        *left.min(right)
+    }
+
+    fn finish(&self, value: Duration, _start: usize, _end: usize) -> Result<Duration, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+/// Duration Max
+/// Longest of the `Duration` leaves in a range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationMax;
+
+impl Monoid for DurationMax {
+    type Item = Duration;
+    type Value = Duration;
+    type Output = Duration;
+
+    fn identity(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn lift(&self, item: &Duration) -> Duration {
+        *item
+    }
+
+    fn combine(&self, left: &Duration, right: &Duration) -> Duration {
+        *left.max(right)
+    }
+
+    fn finish(&self, value: Duration, _start: usize, _end: usize) -> Result<Duration, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DurationMax, DurationMin, DurationSum, SegmentTree, SegmentTreeError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_duration_aggregates() -> Result<(), SegmentTreeError> {
+        let input: Vec<Duration> = [120, 45, 300, 80, 95].iter().map(|ms| Duration::from_millis(*ms)).collect();
+
+        let mut totals = SegmentTree::with_monoid(&input, DurationSum)?;
+        assert_eq!(totals.query(0, 4)?, Duration::from_millis(640));
+        assert_eq!(totals.query(1, 3)?, Duration::from_millis(425));
+        totals.update(2, Duration::from_micros(1500))?;
+        assert_eq!(totals.query(1, 3)?, Duration::from_micros(126_500));
+
+        let shortest = SegmentTree::with_monoid(&input, DurationMin)?;
+        assert_eq!(shortest.query(0, 4)?, Duration::from_millis(45));
+        assert_eq!(shortest.query(2, 4)?, Duration::from_millis(80));
+
+        let longest = SegmentTree::with_monoid(&input, DurationMax)?;
+        assert_eq!(longest.query(0, 4)?, Duration::from_millis(300));
+        assert_eq!(longest.query(3, 4)?, Duration::from_millis(95));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duration_sum_overflow() -> Result<(), SegmentTreeError> {
+        let input = vec![Duration::from_secs(1), Duration::MAX, Duration::from_secs(1)];
+        let segment_tree = SegmentTree::with_monoid(&input, DurationSum)?;
+        assert_eq!(segment_tree.query(1, 1)?, Duration::MAX);
+        assert_eq!(segment_tree.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(segment_tree.query(1, 2), Err(SegmentTreeError::Overflow { start: 1, end: 2 }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+//! Dynamic Segment Trees
+
+use crate::memory::vec_usage;
+use crate::{MemoryReport, Monoid, OverflowPolicy, SegmentTreeError, Sum};
+
+/// Link to a missing child. The root is never a child, so its index is free to mean none
+const NO_CHILD: usize = 0;
+
+/// Dynamic Node
+/// Node of a dynamic segment tree, stored in the arena of its tree
+/// value: Aggregate of the range covered by the node
+/// left: Arena index of the left child, or `NO_CHILD`
+/// right: Arena index of the right child, or `NO_CHILD`
+#[derive(Debug, Clone)]
+struct DynamicNode<V> {
+    value: V,
+    left: usize,
+    right: usize,
+}
+
+/// Dynamic Segment Tree
+/// Segment tree over a large index domain whose nodes are only created when a value is written,
+/// so memory is O(k log n) for k written indices instead of O(n). Unwritten indices hold the identity.
+/// Nodes are allocated from an arena and linked by index, which avoids one heap allocation per node.
+/// nodes: Arena of nodes, with the root first
+/// len: Number of indices in the domain
+/// monoid: Operation used to combine values
+#[derive(Debug, Clone)]
+pub struct DynamicSegmentTree<M: Monoid = Sum> {
+    nodes: Vec<DynamicNode<M::Value>>,
+    len: usize,
+    monoid: M,
+}
+
+impl DynamicSegmentTree {
+    /// Create a dynamic sum segment tree with checked overflow
+    /// len: Number of indices in the domain, which may be as large as `usize::MAX`
+    /// Returns the tree, or an error if `len` is 0
+    pub fn new(len: usize) -> Result<DynamicSegmentTree, SegmentTreeError> {
+        DynamicSegmentTree::with_monoid(len, Sum { overflow_policy: OverflowPolicy::Checked })
+    }
+}
+
+impl<M: Monoid> DynamicSegmentTree<M> {
+    /// Create a dynamic segment tree with a specific monoid
+    /// len: Number of indices in the domain, which may be as large as `usize::MAX`
+    /// monoid: Operation used to combine values
+    /// Returns the tree, or an error if `len` is 0
+    pub fn with_monoid(len: usize, monoid: M) -> Result<DynamicSegmentTree<M>, SegmentTreeError> {
+        if len == 0 {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+
+        let root = DynamicNode { value: monoid.identity(), left: NO_CHILD, right: NO_CHILD };
+        Ok(DynamicSegmentTree { nodes: vec![root], len, monoid })
+    }
+
+    /// Get the number of indices in the domain
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the domain is empty, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the monoid of the tree
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    /// Get the number of nodes allocated in the arena
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Reset every index to the identity
+    /// The arena keeps its allocation for reuse, and for values without drop glue this is O(1)
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0] = DynamicNode { value: self.monoid.identity(), left: NO_CHILD, right: NO_CHILD };
+    }
+
+    /// Update the value at an index, creating the nodes on its path as needed
+    /// index: Index to update
+    /// new_value: New value for the index
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        if index >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.len });
+        }
+        self.monoid.validate(&new_value, index)?;
+
+        let value = self.monoid.lift(&new_value);
+        self.update_recursive(0, 0, self.len - 1, index, value);
+        Ok(())
+    }
+
+    /// Write a leaf value below a node and recompute the node on the way back up (Recursive)
+    /// node_idx: Arena index of the current node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    /// index: Index to update
+    /// value: Lifted value for the index
+    fn update_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, index: usize, value: M::Value) {
+        if lo == hi {
+            self.nodes[node_idx].value = value;
+            return;
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        if index <= mid {
+            let left = self.child(node_idx, true);
+            self.update_recursive(left, lo, mid, index, value);
+        } else {
+            let right = self.child(node_idx, false);
+            self.update_recursive(right, mid + 1, hi, index, value);
+        }
+
+        let node = &self.nodes[node_idx];
+        self.nodes[node_idx].value = match (node.left, node.right) {
+            (NO_CHILD, right) => self.nodes[right].value.clone(),
+            (left, NO_CHILD) => self.nodes[left].value.clone(),
+            (left, right) => self.monoid.combine(&self.nodes[left].value, &self.nodes[right].value),
+        };
+    }
+
+    /// Get a child of a node, allocating it in the arena if it does not exist
+    /// node_idx: Arena index of the parent
+    /// left: Whether to get the left child rather than the right one
+    /// Returns the arena index of the child
+    fn child(&mut self, node_idx: usize, left: bool) -> usize {
+        let node = &self.nodes[node_idx];
+        let child = if left { node.left } else { node.right };
+        if child != NO_CHILD {
+            return child;
+        }
+
+        let child = self.nodes.len();
+        self.nodes.push(DynamicNode { value: self.monoid.identity(), left: NO_CHILD, right: NO_CHILD });
+        if left {
+            self.nodes[node_idx].left = child;
+        } else {
+            self.nodes[node_idx].right = child;
+        }
+        child
+    }
+
+    /// Query a range of the tree
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        let mut value = None;
+        self.query_recursive(0, 0, self.len - 1, start, end, &mut value);
+        self.monoid.finish(value.unwrap_or_else(|| self.monoid.identity()), start, end)
+    }
+
+    /// Fold the nodes covering a range into an accumulator from left to right (Recursive)
+    /// Missing children hold the identity and are skipped
+    /// node_idx: Arena index of the current node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Accumulated aggregate of the nodes visited so far, `None` before the first one
+    fn query_recursive(&self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize, value: &mut Option<M::Value>) {
+        let node = &self.nodes[node_idx];
+        if start <= lo && hi <= end {
+            match value {
+                Some(value) => self.monoid.combine_into(value, &node.value),
+                None => *value = Some(node.value.clone()),
+            }
+            return;
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        if start <= mid && node.left != NO_CHILD {
+            self.query_recursive(node.left, lo, mid, start, end, value);
+        }
+        if end > mid && node.right != NO_CHILD {
+            self.query_recursive(node.right, mid + 1, hi, start, end, value);
+        }
+    }
+
+    /// Get the result of the whole domain in O(1), from the root node
+    pub fn total(&self) -> Result<M::Output, SegmentTreeError> {
+        self.monoid.finish(self.nodes[0].value.clone(), 0, self.len - 1)
+    }
+
+    /// Get the value at an index
+    /// index: Index to read
+    /// Returns the result for the single index, or an error
+    pub fn get(&self, index: usize) -> Result<M::Output, SegmentTreeError> {
+        self.query(index, index)
+    }
+
+    /// Report the heap memory held by the arena of the dynamic segment tree
+    /// Returns a `MemoryReport` whose `nodes` are the allocated arena nodes
+    pub fn memory_usage(&self) -> MemoryReport {
+        let (nodes, spare) = vec_usage(&self.nodes);
+        MemoryReport { nodes, spare, ..MemoryReport::default() }
+    }
+
+    /// Release the spare capacity of the arena, such as the nodes kept for reuse by `clear`
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DynamicSegmentTree, FnMonoid, SegmentTreeError};
+
+    #[test]
+    fn test_dynamic_tree() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = DynamicSegmentTree::new(usize::MAX)?;
+        assert_eq!(segment_tree.query(0, usize::MAX - 1)?, 0);
+
+        segment_tree.update(5, 10)?;
+        segment_tree.update(1 << 40, -3)?;
+        segment_tree.update(usize::MAX - 1, 7)?;
+        segment_tree.update(5, 4)?;
+        assert_eq!(segment_tree.query(0, usize::MAX - 1)?, 8);
+        assert_eq!(segment_tree.total()?, 8);
+        assert_eq!(segment_tree.query(6, usize::MAX - 2)?, -3);
+        assert_eq!(segment_tree.query(0, 1 << 40)?, 1);
+        assert_eq!(segment_tree.get(5)?, 4);
+        assert_eq!(segment_tree.get(6)?, 0);
+        // Each written index only allocates its own path
+        assert!(segment_tree.node_count() <= 3 * 64 + 1);
+
+        assert_eq!(segment_tree.update(usize::MAX, 1), Err(SegmentTreeError::IndexOutOfBounds { index: usize::MAX, len: usize::MAX }));
+        assert_eq!(segment_tree.query(3, 2), Err(SegmentTreeError::InvalidRange { start: 3, end: 2, len: usize::MAX }));
+        assert_eq!(DynamicSegmentTree::new(0).err(), Some(SegmentTreeError::EmptyInput));
+
+        segment_tree.clear();
+        assert_eq!(segment_tree.node_count(), 1);
+        assert_eq!(segment_tree.query(0, usize::MAX - 1)?, 0);
+        segment_tree.update(0, 2)?;
+        assert_eq!(segment_tree.query(0, 10)?, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dynamic_tree_matches_dense() -> Result<(), SegmentTreeError> {
+        let len = 37;
+        let monoid = FnMonoid::new(String::new(), |left: &String, right: &String| format!("{}{}", left, right));
+        let mut segment_tree = DynamicSegmentTree::with_monoid(len, monoid)?;
+        let mut dense = vec![String::new(); len];
+        for (step, index) in [3, 30, 0, 36, 17, 3, 18].into_iter().enumerate() {
+            let value = char::from(b'a' + step as u8).to_string();
+            segment_tree.update(index, value.clone())?;
+            dense[index] = value;
+        }
+
+        for start in 0..len {
+            for end in start..len {
+                assert_eq!(segment_tree.query(start, end)?, dense[start..=end].concat());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Segment Tree Errors
+
+use std::error::Error;
+use std::fmt;
+
+/// Segment Tree Error
+/// Errors returned by segment tree operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentTreeError {
+    /// The input used to build the segment tree is empty
+    EmptyInput,
+    /// The input is longer than the segment tree can hold
+    /// len: Length of the input
+    /// max: Maximum number of leaves
+    InputTooLarge { len: usize, max: usize },
+    /// An index is past the end of the segment tree
+    /// index: Offending index
+    /// len: Number of leaves in the segment tree
+    IndexOutOfBounds { index: usize, len: usize },
+    /// A range starts after it ends
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// len: Number of leaves in the segment tree
+    InvalidRange { start: usize, end: usize, len: usize },
+    /// The result of an operation over a range does not fit in the value type
+    /// start: Start index of the range
+    /// end: End index of the range
+    Overflow { start: usize, end: usize },
+    /// An input does not have the length of the segment tree
+    /// expected: Number of leaves in the segment tree
+    /// actual: Length of the input
+    LengthMismatch { expected: usize, actual: usize },
+    /// An item was rejected by the monoid of the segment tree
+    /// index: Index the item was written to
+    InvalidItem { index: usize },
+    /// Serialized data does not match its checksum, so it was corrupted
+    /// expected: Checksum stored with the data
+    /// actual: Checksum of the data as read
+    ChecksumMismatch { expected: u64, actual: u64 },
+    /// A version number was never returned by the persistent segment tree
+    /// version: Offending version number
+    /// versions: Number of versions, so the latest version is one less
+    VersionNotFound { version: usize, versions: usize },
+}
+
+impl fmt::Display for SegmentTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentTreeError::EmptyInput => write!(f, "Input is empty"),
+            SegmentTreeError::InputTooLarge { len, max } => {
+                write!(f, "Input size {} exceeded maximum value {}", len, max)
+            }
+            SegmentTreeError::IndexOutOfBounds { index, len } => {
+                write!(f, "Index {} is out of bounds for length {}", index, len)
+            }
+            SegmentTreeError::InvalidRange { start, end, len } => {
+                write!(f, "Start index {} is greater than end index {} for length {}", start, end, len)
+            }
+            SegmentTreeError::Overflow { start, end } => {
+                write!(f, "Result overflowed over range {}..={}", start, end)
+            }
+            SegmentTreeError::LengthMismatch { expected, actual } => {
+                write!(f, "Input length {} does not match the segment tree length {}", actual, expected)
+            }
+            SegmentTreeError::InvalidItem { index } => {
+                write!(f, "Item at index {} is not valid for this segment tree", index)
+            }
+            SegmentTreeError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum {:#018x} does not match the stored checksum {:#018x}", actual, expected)
+            }
+            SegmentTreeError::VersionNotFound { version, versions } => {
+                write!(f, "Version {} does not exist, there are {} versions", version, versions)
+            }
+        }
+    }
+}
+
+impl Error for SegmentTreeError {}
+
+/// Invariant Error
+/// Broken internal invariants found by `SegmentTree::verify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantError {
+    /// The node or leaf storage does not have the size implied by the number of leaves
+    /// expected: Expected number of entries
+    /// actual: Actual number of entries
+    SizeMismatch { expected: usize, actual: usize },
+    /// A node covers the wrong range, or its children do not split its range in two
+    /// node: Index of the node
+    InvalidNodeRange { node: usize },
+    /// A node value is not the combination of its children, or a leaf value is not its lifted item
+    /// node: Index of the node
+    StaleValue { node: usize },
+    /// The leaf index table does not point at the leaf node for a leaf
+    /// index: Index of the leaf
+    LeafIndex { index: usize },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantError::SizeMismatch { expected, actual } => {
+                write!(f, "Storage holds {} entries, expected {}", actual, expected)
+            }
+            InvariantError::InvalidNodeRange { node } => write!(f, "Node {} covers an invalid range", node),
+            InvariantError::StaleValue { node } => write!(f, "Node {} holds a stale value", node),
+            InvariantError::LeafIndex { index } => write!(f, "Leaf {} is mapped to the wrong node", index),
+        }
+    }
+}
+
+impl Error for InvariantError {}
@@ -0,0 +1,151 @@
+//! Query Explanations
+
+use crate::{Monoid, SegmentTree, SegmentTreeError};
+use std::fmt;
+
+/// Decision
+/// What a query did at a node
+/// Covered: The node lies inside the range, so its value was combined into the partial result
+/// Disjoint: The node lies outside the range and was skipped
+/// Split: The node overlaps the range partly, so the query descended into its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Covered,
+    Disjoint,
+    Split,
+}
+
+/// Explain Step
+/// One node visited by a query
+/// node: Index of the node
+/// start: Start index of the node range
+/// end: End index of the node range
+/// depth: Depth of the node, the root is at depth 0
+/// decision: What the query did at the node
+/// value: Value of the node, for covered nodes
+/// partial: Partial result after combining the node, for covered nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainStep<V> {
+    pub node: usize,
+    pub start: usize,
+    pub end: usize,
+    pub depth: usize,
+    pub decision: Decision,
+    pub value: Option<V>,
+    pub partial: Option<V>,
+}
+
+/// Query Explanation
+/// Trace of a single query, returned by `SegmentTree::explain_query`
+/// steps: Nodes visited by the query, in the order it visited them
+/// result: Result of the query, including errors from the monoid such as an overflow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryExplanation<V, O> {
+    pub steps: Vec<ExplainStep<V>>,
+    pub result: Result<O, SegmentTreeError>,
+}
+
+/// Print the trace one node per line, children indented under their parent, then the result
+/// ```text
+/// [0, 3] split
+///   [0, 1] split
+///     [0, 0] disjoint
+///     [1, 1] covered, value 2, partial 2
+///   [2, 3] covered, value 7, partial 9
+/// result Ok(9)
+/// ```
+impl<V: fmt::Debug, O: fmt::Debug> fmt::Display for QueryExplanation<V, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            write!(f, "{:indent$}[{}, {}] ", "", step.start, step.end, indent = step.depth * 2)?;
+            match (step.decision, &step.value, &step.partial) {
+                (Decision::Covered, Some(value), Some(partial)) => writeln!(f, "covered, value {:?}, partial {:?}", value, partial)?,
+                (Decision::Disjoint, _, _) => writeln!(f, "disjoint")?,
+                _ => writeln!(f, "split")?,
+            }
+        }
+        writeln!(f, "result {:?}", self.result)
+    }
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Query a range and record every node visited, what was done there and the partial results
+    /// Meant for debugging wrong answers, such as from a custom monoid that is not associative
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the `QueryExplanation`, or an error if the range is invalid
+    pub fn explain_query(&self, start: usize, end: usize) -> Result<QueryExplanation<M::Value, M::Output>, SegmentTreeError> {
+        self.validate_public_query(start, end)?;
+
+        let mut steps = Vec::new();
+        let mut value = None;
+        self.explain_query_recursive(0, 0, start, end, &mut value, &mut steps);
+        let result = self.monoid.finish(value.unwrap_or_else(|| self.monoid.identity()), start, end);
+
+        Ok(QueryExplanation { steps, result })
+    }
+
+    /// Function to query the segment tree while recording each step (Recursive)
+    /// node_idx: Index of the current node
+    /// depth: Depth of the current node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Accumulated aggregate of the nodes visited so far, `None` before the first one
+    /// steps: Steps recorded so far
+    fn explain_query_recursive(&self, node_idx: usize, depth: usize, start: usize, end: usize, value: &mut Option<M::Value>, steps: &mut Vec<ExplainStep<M::Value>>) {
+        let node = &self.nodes[node_idx];
+        let mut step = ExplainStep { node: node_idx, start: node.start, end: node.end, depth, decision: Decision::Split, value: None, partial: None };
+
+        if start <= node.start && end >= node.end {
+            match value {
+                Some(value) => self.monoid.combine_into(value, &node.value),
+                None => *value = Some(node.value.clone()),
+            }
+            step.decision = Decision::Covered;
+            step.value = Some(node.value.clone());
+            step.partial = value.clone();
+            steps.push(step);
+            return;
+        }
+
+        if end < node.start || start > node.end {
+            step.decision = Decision::Disjoint;
+            steps.push(step);
+            return;
+        }
+
+        steps.push(step);
+        self.explain_query_recursive(2 * node_idx + 1, depth + 1, start, end, value, steps);
+        self.explain_query_recursive(2 * node_idx + 2, depth + 1, start, end, value, steps);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decision, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_explain_query() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3, 4])?;
+
+        let explanation = segment_tree.explain_query(1, 3)?;
+        assert_eq!(explanation.result, Ok(9));
+        let decisions: Vec<_> = explanation.steps.iter().map(|step| (step.start, step.end, step.decision)).collect();
+        assert_eq!(
+            decisions,
+            vec![(0, 3, Decision::Split), (0, 1, Decision::Split), (0, 0, Decision::Disjoint), (1, 1, Decision::Covered), (2, 3, Decision::Covered)]
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "[0, 3] split\n  [0, 1] split\n    [0, 0] disjoint\n    [1, 1] covered, value 2, partial 2\n  [2, 3] covered, value 7, partial 9\nresult Ok(9)\n"
+        );
+
+        let segment_tree = SegmentTree::new(&[isize::MAX, 1])?;
+        let explanation = segment_tree.explain_query(0, 1)?;
+        assert_eq!(explanation.result, Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(explanation.steps[0].partial, Some(isize::MAX as i128 + 1));
+        assert!(segment_tree.explain_query(0, 2).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+//! Fenwick Trees
+
+use crate::{OverflowPolicy, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Lowest set bit of a 1-based Fenwick index
+fn lowbit(index: usize) -> usize {
+    index & index.wrapping_neg()
+}
+
+/// Add a delta to a cell of a 2D Fenwick tree
+/// tree: Partial sums, row-major
+/// rows: Number of rows
+/// cols: Number of columns
+/// row: Row of the cell
+/// col: Column of the cell
+/// delta: Value to add
+fn add_2d(tree: &mut [i128], rows: usize, cols: usize, row: usize, col: usize, delta: i128) {
+    let mut fenwick_row = row + 1;
+    while fenwick_row <= rows {
+        let mut fenwick_col = col + 1;
+        while fenwick_col <= cols {
+            tree[(fenwick_row - 1) * cols + fenwick_col - 1] += delta;
+            fenwick_col += lowbit(fenwick_col);
+        }
+        fenwick_row += lowbit(fenwick_row);
+    }
+}
+
+/// Sum the cells of a 2D Fenwick tree above and to the left of a corner
+/// tree: Partial sums, row-major
+/// cols: Number of columns of the tree
+/// end_row: Number of rows to include
+/// end_col: Number of columns to include
+fn prefix_2d(tree: &[i128], cols: usize, end_row: usize, end_col: usize) -> i128 {
+    let mut sum = 0;
+    let mut fenwick_row = end_row;
+    while fenwick_row > 0 {
+        let mut fenwick_col = end_col;
+        while fenwick_col > 0 {
+            sum += tree[(fenwick_row - 1) * cols + fenwick_col - 1];
+            fenwick_col -= lowbit(fenwick_col);
+        }
+        fenwick_row -= lowbit(fenwick_row);
+    }
+    sum
+}
+
+/// Get the number of cells of a grid
+/// rows: Number of rows
+/// cols: Number of columns
+/// Returns the number of cells, or an error if the grid is empty or too large
+fn grid_len(rows: usize, cols: usize) -> Result<usize, SegmentTreeError> {
+    if rows == 0 || cols == 0 {
+        return Err(SegmentTreeError::EmptyInput);
+    }
+
+    rows.checked_mul(cols).filter(|&len| len <= MAX_INPUT_SIZE)
+        .ok_or(SegmentTreeError::InputTooLarge { len: rows.saturating_mul(cols), max: MAX_INPUT_SIZE })
+}
+
+/// Validate a cell of a grid
+/// rows: Number of rows
+/// cols: Number of columns
+/// row: Row of the cell
+/// col: Column of the cell
+/// Returns `Ok(())` if the cell is in the grid, otherwise an error
+fn validate_cell(rows: usize, cols: usize, row: usize, col: usize) -> Result<(), SegmentTreeError> {
+    if row >= rows {
+        return Err(SegmentTreeError::IndexOutOfBounds { index: row, len: rows });
+    }
+    if col >= cols {
+        return Err(SegmentTreeError::IndexOutOfBounds { index: col, len: cols });
+    }
+
+    Ok(())
+}
+
+/// Validate a rectangle of a grid
+/// rows: Number of rows
+/// cols: Number of columns
+/// row_start: First row of the rectangle
+/// col_start: First column of the rectangle
+/// row_end: Last row of the rectangle
+/// col_end: Last column of the rectangle
+/// Returns `Ok(())` if the rectangle is in the grid, otherwise an error
+fn validate_rect(rows: usize, cols: usize, row_start: usize, col_start: usize, row_end: usize, col_end: usize) -> Result<(), SegmentTreeError> {
+    if row_start > row_end {
+        return Err(SegmentTreeError::InvalidRange { start: row_start, end: row_end, len: rows });
+    }
+    if col_start > col_end {
+        return Err(SegmentTreeError::InvalidRange { start: col_start, end: col_end, len: cols });
+    }
+
+    validate_cell(rows, cols, row_end, col_end)
+}
+
+/// Fenwick Tree 2D
+/// Grid of `isize` values with point updates and rectangle sum queries in O(log rows * log cols).
+/// Uses one `i128` partial sum per cell, so it is much lighter than a 2D segment tree.
+/// Sums are accumulated in `i128`, and rectangles report overflow by the flat indices
+/// `row * cols + col` of their corners.
+/// tree: Partial sums, row-major, for 1-based Fenwick indices shifted down by one
+/// values: Current cell values, row-major
+/// rows: Number of rows
+/// cols: Number of columns
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FenwickTree2D {
+    tree: Vec<i128>,
+    values: Vec<isize>,
+    rows: usize,
+    cols: usize,
+    overflow_policy: OverflowPolicy,
+}
+
+impl FenwickTree2D {
+    /// Create a grid of zeros with checked overflow
+    /// rows: Number of rows
+    /// cols: Number of columns
+    /// Returns a new `FenwickTree2D` structure or an error
+    pub fn new(rows: usize, cols: usize) -> Result<FenwickTree2D, SegmentTreeError> {
+        FenwickTree2D::with_overflow_policy(rows, cols, OverflowPolicy::Checked)
+    }
+
+    /// Create a grid of zeros with a specific overflow policy
+    /// rows: Number of rows
+    /// cols: Number of columns
+    /// overflow_policy: How sums that do not fit in `isize` are handled
+    /// Returns a new `FenwickTree2D` structure or an error if the grid is empty or too large
+    pub fn with_overflow_policy(rows: usize, cols: usize, overflow_policy: OverflowPolicy) -> Result<FenwickTree2D, SegmentTreeError> {
+        let len = grid_len(rows, cols)?;
+        Ok(FenwickTree2D { tree: vec![0; len], values: vec![0; len], rows, cols, overflow_policy })
+    }
+
+    /// Create a grid from its rows with checked overflow, in O(rows * cols)
+    /// input: Rows of the grid, which must all have the same length
+    /// Returns a new `FenwickTree2D` structure or an error
+    pub fn from_rows(input: &[Vec<isize>]) -> Result<FenwickTree2D, SegmentTreeError> {
+        let cols = input.first().map_or(0, Vec::len);
+        let mut fenwick_tree = FenwickTree2D::new(input.len(), cols)?;
+        for row in input {
+            if row.len() != cols {
+                return Err(SegmentTreeError::LengthMismatch { expected: cols, actual: row.len() });
+            }
+        }
+
+        fenwick_tree.values = input.concat();
+        fenwick_tree.tree = fenwick_tree.values.iter().map(|&value| value as i128).collect();
+
+        // Push every partial sum into its Fenwick parent, first along the rows, then along the columns
+        let (rows, cols) = (fenwick_tree.rows, fenwick_tree.cols);
+        for row in 0..rows {
+            for col in 1..=cols {
+                let parent = col + lowbit(col);
+                if parent <= cols {
+                    fenwick_tree.tree[row * cols + parent - 1] += fenwick_tree.tree[row * cols + col - 1];
+                }
+            }
+        }
+        for row in 1..=rows {
+            let parent = row + lowbit(row);
+            if parent <= rows {
+                for col in 0..cols {
+                    fenwick_tree.tree[(parent - 1) * cols + col] += fenwick_tree.tree[(row - 1) * cols + col];
+                }
+            }
+        }
+
+        Ok(fenwick_tree)
+    }
+
+    /// Get the number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Get the number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Get the value of a cell
+    /// row: Row of the cell
+    /// col: Column of the cell
+    /// Returns the value, or an error if the cell is out of bounds
+    pub fn get(&self, row: usize, col: usize) -> Result<isize, SegmentTreeError> {
+        validate_cell(self.rows, self.cols, row, col)?;
+
+        Ok(self.values[row * self.cols + col])
+    }
+
+    /// Add a delta to the value of a cell
+    /// row: Row of the cell
+    /// col: Column of the cell
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn add(&mut self, row: usize, col: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        validate_cell(self.rows, self.cols, row, col)?;
+
+        let index = row * self.cols + col;
+        let new_value = self.overflow_policy.add(self.values[index], delta, index)?;
+        self.update(row, col, new_value)
+    }
+
+    /// Update the value of a cell
+    /// row: Row of the cell
+    /// col: Column of the cell
+    /// new_value: New value for the cell
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, row: usize, col: usize, new_value: isize) -> Result<(), SegmentTreeError> {
+        validate_cell(self.rows, self.cols, row, col)?;
+
+        let index = row * self.cols + col;
+        let delta = new_value as i128 - self.values[index] as i128;
+        self.values[index] = new_value;
+
+        add_2d(&mut self.tree, self.rows, self.cols, row, col, delta);
+        Ok(())
+    }
+
+    /// Query the sum of a rectangle
+    /// row_start: First row of the rectangle
+    /// col_start: First column of the rectangle
+    /// row_end: Last row of the rectangle
+    /// col_end: Last column of the rectangle
+    /// Returns the sum of the rectangle, or an error
+    pub fn query(&self, row_start: usize, col_start: usize, row_end: usize, col_end: usize) -> Result<isize, SegmentTreeError> {
+        validate_rect(self.rows, self.cols, row_start, col_start, row_end, col_end)?;
+
+        let prefix = |end_row, end_col| prefix_2d(&self.tree, self.cols, end_row, end_col);
+        let sum = prefix(row_end + 1, col_end + 1) - prefix(row_start, col_end + 1) - prefix(row_end + 1, col_start)
+            + prefix(row_start, col_start);
+        self.overflow_policy.narrow(sum, row_start * self.cols + col_start, row_end * self.cols + col_end)
+    }
+}
+
+/// Rectangle Add Fenwick Tree 2D
+/// Grid of `isize` values with rectangle additions and point queries in O(log rows * log cols).
+/// Stores the 2D difference of the grid in a Fenwick tree: adding to a rectangle changes the
+/// differences at its four corners, and the value of a cell is the sum of the differences above and to its left.
+/// tree: Partial sums of the differences, row-major
+/// rows: Number of rows
+/// cols: Number of columns
+/// overflow_policy: How cell values that do not fit in `isize` are handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RectAddFenwickTree2D {
+    tree: Vec<i128>,
+    rows: usize,
+    cols: usize,
+    overflow_policy: OverflowPolicy,
+}
+
+impl RectAddFenwickTree2D {
+    /// Create a grid of zeros with checked overflow
+    /// rows: Number of rows
+    /// cols: Number of columns
+    /// Returns a new `RectAddFenwickTree2D` structure or an error
+    pub fn new(rows: usize, cols: usize) -> Result<RectAddFenwickTree2D, SegmentTreeError> {
+        RectAddFenwickTree2D::with_overflow_policy(rows, cols, OverflowPolicy::Checked)
+    }
+
+    /// Create a grid of zeros with a specific overflow policy
+    /// rows: Number of rows
+    /// cols: Number of columns
+    /// overflow_policy: How cell values that do not fit in `isize` are handled
+    /// Returns a new `RectAddFenwickTree2D` structure or an error if the grid is empty or too large
+    pub fn with_overflow_policy(rows: usize, cols: usize, overflow_policy: OverflowPolicy) -> Result<RectAddFenwickTree2D, SegmentTreeError> {
+        let len = grid_len(rows, cols)?;
+        Ok(RectAddFenwickTree2D { tree: vec![0; len], rows, cols, overflow_policy })
+    }
+
+    /// Get the number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Get the number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Add a delta to every cell of a rectangle
+    /// row_start: First row of the rectangle
+    /// col_start: First column of the rectangle
+    /// row_end: Last row of the rectangle
+    /// col_end: Last column of the rectangle
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn rect_add(&mut self, row_start: usize, col_start: usize, row_end: usize, col_end: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        validate_rect(self.rows, self.cols, row_start, col_start, row_end, col_end)?;
+
+        // Corners past the last row or column would only affect cells outside the grid
+        let (rows, cols, delta) = (self.rows, self.cols, delta as i128);
+        add_2d(&mut self.tree, rows, cols, row_start, col_start, delta);
+        add_2d(&mut self.tree, rows, cols, row_start, col_end + 1, -delta);
+        add_2d(&mut self.tree, rows, cols, row_end + 1, col_start, -delta);
+        add_2d(&mut self.tree, rows, cols, row_end + 1, col_end + 1, delta);
+        Ok(())
+    }
+
+    /// Get the value of a cell
+    /// row: Row of the cell
+    /// col: Column of the cell
+    /// Returns the value, or an error
+    pub fn point_query(&self, row: usize, col: usize) -> Result<isize, SegmentTreeError> {
+        validate_cell(self.rows, self.cols, row, col)?;
+
+        let index = row * self.cols + col;
+        self.overflow_policy.narrow(prefix_2d(&self.tree, self.cols, row + 1, col + 1), index, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FenwickTree2D, RectAddFenwickTree2D, SegmentTreeError};
+
+    #[test]
+    fn test_fenwick_tree_2d() -> Result<(), SegmentTreeError> {
+        let mut grid: Vec<Vec<isize>> = (0..5).map(|row| (0..7).map(|col| (row * 7 + col) % 9 - 4).collect()).collect();
+        let mut fenwick_tree = FenwickTree2D::from_rows(&grid)?;
+        fenwick_tree.add(2, 3, 10)?;
+        grid[2][3] += 10;
+        fenwick_tree.update(4, 6, -20)?;
+        grid[4][6] = -20;
+        assert_eq!(fenwick_tree.get(4, 6)?, -20);
+
+        for row_start in 0..5 {
+            for row_end in row_start..5 {
+                for col_start in 0..7 {
+                    for col_end in col_start..7 {
+                        let expected: isize = grid[row_start..=row_end].iter().map(|row| row[col_start..=col_end].iter().sum::<isize>()).sum();
+                        assert_eq!(fenwick_tree.query(row_start, col_start, row_end, col_end)?, expected);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(fenwick_tree.query(0, 0, 5, 0), Err(SegmentTreeError::IndexOutOfBounds { index: 5, len: 5 }));
+        assert_eq!(fenwick_tree.query(0, 3, 0, 2), Err(SegmentTreeError::InvalidRange { start: 3, end: 2, len: 7 }));
+        assert_eq!(FenwickTree2D::from_rows(&[vec![1, 2], vec![3]]), Err(SegmentTreeError::LengthMismatch { expected: 2, actual: 1 }));
+        assert_eq!(FenwickTree2D::new(0, 3), Err(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fenwick_tree_2d_overflow() -> Result<(), SegmentTreeError> {
+        let mut fenwick_tree = FenwickTree2D::new(2, 2)?;
+        fenwick_tree.update(0, 1, isize::MAX)?;
+        fenwick_tree.update(1, 1, isize::MAX)?;
+        assert_eq!(fenwick_tree.query(0, 1, 1, 1), Err(SegmentTreeError::Overflow { start: 1, end: 3 }));
+        assert_eq!(fenwick_tree.add(0, 1, 1), Err(SegmentTreeError::Overflow { start: 1, end: 1 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rect_add_point_query() -> Result<(), SegmentTreeError> {
+        let mut fenwick_tree = RectAddFenwickTree2D::new(6, 4)?;
+        let mut grid = [[0isize; 4]; 6];
+        for (step, (row_start, col_start, row_end, col_end)) in [(0, 0, 5, 3), (1, 1, 3, 2), (2, 0, 5, 0), (5, 3, 5, 3), (0, 2, 4, 3)].into_iter().enumerate() {
+            let delta = step as isize * 3 - 5;
+            fenwick_tree.rect_add(row_start, col_start, row_end, col_end, delta)?;
+            grid[row_start..=row_end].iter_mut().for_each(|row| row[col_start..=col_end].iter_mut().for_each(|cell| *cell += delta));
+        }
+
+        for (row, cells) in grid.iter().enumerate() {
+            for (col, &cell) in cells.iter().enumerate() {
+                assert_eq!(fenwick_tree.point_query(row, col)?, cell);
+            }
+        }
+
+        assert_eq!(fenwick_tree.rect_add(0, 0, 6, 0, 1), Err(SegmentTreeError::IndexOutOfBounds { index: 6, len: 6 }));
+        assert_eq!(fenwick_tree.point_query(0, 4), Err(SegmentTreeError::IndexOutOfBounds { index: 4, len: 4 }));
+
+        Ok(())
+    }
+}
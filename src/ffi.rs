@@ -0,0 +1,164 @@
+//! C FFI
+//!
+//! C-compatible functions over the sum segment tree, declared in `include/segment_tree.h`.
+//!
+//! Ownership: `segtree_new` allocates a tree and hands its pointer to the caller, who owns it until
+//! it is passed to `segtree_free` exactly once. The other functions only borrow the tree, and the
+//! input array of `segtree_new` is copied, so the caller keeps ownership of it.
+//!
+//! Every function except `segtree_free` returns one of the `SEGTREE_*` status codes.
+
+use crate::{SegmentTree, SegmentTreeError};
+use std::os::raw::c_int;
+
+pub const SEGTREE_OK: c_int = 0;
+pub const SEGTREE_NULL_POINTER: c_int = 1;
+pub const SEGTREE_EMPTY_INPUT: c_int = 2;
+pub const SEGTREE_INPUT_TOO_LARGE: c_int = 3;
+pub const SEGTREE_INDEX_OUT_OF_BOUNDS: c_int = 4;
+pub const SEGTREE_INVALID_RANGE: c_int = 5;
+pub const SEGTREE_OVERFLOW: c_int = 6;
+pub const SEGTREE_INVALID_INPUT: c_int = 7;
+
+/// Convert an error into its status code
+/// error: Segment tree error
+fn status(error: SegmentTreeError) -> c_int {
+    match error {
+        SegmentTreeError::EmptyInput => SEGTREE_EMPTY_INPUT,
+        SegmentTreeError::InputTooLarge { .. } => SEGTREE_INPUT_TOO_LARGE,
+        SegmentTreeError::IndexOutOfBounds { .. } => SEGTREE_INDEX_OUT_OF_BOUNDS,
+        SegmentTreeError::InvalidRange { .. } => SEGTREE_INVALID_RANGE,
+        SegmentTreeError::Overflow { .. } => SEGTREE_OVERFLOW,
+        SegmentTreeError::LengthMismatch { .. }
+        | SegmentTreeError::InvalidItem { .. }
+        | SegmentTreeError::VersionNotFound { .. }
+        | SegmentTreeError::ChecksumMismatch { .. } => SEGTREE_INVALID_INPUT,
+    }
+}
+
+/// Create a sum segment tree with checked overflow
+/// values: Array of `len` input values, copied into the tree
+/// len: Number of input values
+/// out: Receives the new tree on success, and is left untouched on failure
+/// Returns a status code
+///
+/// # Safety
+/// `values` must point to `len` readable values, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn segtree_new(values: *const isize, len: usize, out: *mut *mut SegmentTree) -> c_int {
+    if values.is_null() || out.is_null() {
+        return SEGTREE_NULL_POINTER;
+    }
+
+    let values = std::slice::from_raw_parts(values, len);
+    match SegmentTree::new(values) {
+        Ok(tree) => {
+            *out = Box::into_raw(Box::new(tree));
+            SEGTREE_OK
+        }
+        Err(error) => status(error),
+    }
+}
+
+/// Query the sum of a range
+/// tree: Tree created by `segtree_new`
+/// start: Start index of the range
+/// end: End index of the range, inclusive
+/// out: Receives the sum on success
+/// Returns a status code
+///
+/// # Safety
+/// `tree` must be a live tree from `segtree_new`, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn segtree_query(tree: *const SegmentTree, start: usize, end: usize, out: *mut isize) -> c_int {
+    if tree.is_null() || out.is_null() {
+        return SEGTREE_NULL_POINTER;
+    }
+
+    match (*tree).query(start, end) {
+        Ok(sum) => {
+            *out = sum;
+            SEGTREE_OK
+        }
+        Err(error) => status(error),
+    }
+}
+
+/// Update the value at an index
+/// tree: Tree created by `segtree_new`
+/// index: Index of the value
+/// value: New value
+/// Returns a status code
+///
+/// # Safety
+/// `tree` must be a live tree from `segtree_new`, not in use by any other thread.
+#[no_mangle]
+pub unsafe extern "C" fn segtree_update(tree: *mut SegmentTree, index: usize, value: isize) -> c_int {
+    if tree.is_null() {
+        return SEGTREE_NULL_POINTER;
+    }
+
+    match (*tree).update(index, value) {
+        Ok(()) => SEGTREE_OK,
+        Err(error) => status(error),
+    }
+}
+
+/// Free a tree created by `segtree_new`
+/// tree: Tree to free, ignored if null
+///
+/// # Safety
+/// `tree` must be null or a live tree from `segtree_new`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn segtree_free(tree: *mut SegmentTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let values: [isize; 4] = [1, 2, 3, 4];
+        let mut tree = ptr::null_mut();
+        let mut sum = 0;
+        unsafe {
+            assert_eq!(segtree_new(values.as_ptr(), values.len(), &mut tree), SEGTREE_OK);
+            assert_eq!(segtree_query(tree, 1, 3, &mut sum), SEGTREE_OK);
+            assert_eq!(sum, 9);
+
+            assert_eq!(segtree_update(tree, 0, 10), SEGTREE_OK);
+            assert_eq!(segtree_query(tree, 0, 3, &mut sum), SEGTREE_OK);
+            assert_eq!(sum, 19);
+
+            assert_eq!(segtree_update(tree, 4, 1), SEGTREE_INDEX_OUT_OF_BOUNDS);
+            assert_eq!(segtree_query(tree, 3, 1, &mut sum), SEGTREE_INVALID_RANGE);
+            assert_eq!(sum, 19);
+
+            segtree_free(tree);
+            segtree_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        let mut tree = ptr::null_mut();
+        let mut sum = 0;
+        unsafe {
+            assert_eq!(segtree_new(ptr::null(), 0, &mut tree), SEGTREE_NULL_POINTER);
+            assert_eq!(segtree_new([1isize].as_ptr(), 0, &mut tree), SEGTREE_EMPTY_INPUT);
+            assert!(tree.is_null());
+            assert_eq!(segtree_query(ptr::null(), 0, 0, &mut sum), SEGTREE_NULL_POINTER);
+            assert_eq!(segtree_update(ptr::null_mut(), 0, 0), SEGTREE_NULL_POINTER);
+
+            let values = [isize::MAX, 1];
+            assert_eq!(segtree_new(values.as_ptr(), 2, &mut tree), SEGTREE_OK);
+            assert_eq!(segtree_query(tree, 0, 1, &mut sum), SEGTREE_OVERFLOW);
+            segtree_free(tree);
+        }
+    }
+}
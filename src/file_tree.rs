@@ -0,0 +1,287 @@
+//! File-Backed Segment Trees
+//!
+//! A `FileSegmentTree` is a read-only sum segment tree stored in a file in the archive layout of
+//! `SegmentTree::archive_to`. It is built by streaming the values into the file and queried with
+//! positioned reads, so only O(1) memory is used and the tree may be far larger than RAM.
+//! The file can also be memory-mapped and queried with `ArchivedSegmentTree`.
+
+use crate::archive::{Header, HEADER_LEN, NODE_LEN};
+use crate::{OverflowPolicy, SegmentTreeError};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Number of nodes read or written per call while building
+const CHUNK_NODES: usize = 8192;
+
+/// Read exactly `buffer.len()` bytes at an offset of the file, without moving its cursor
+/// file: File to read from
+/// buffer: Destination of the bytes
+/// offset: Offset of the first byte
+#[cfg(unix)]
+fn read_exact_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buffer, offset)
+}
+
+/// Read exactly `buffer.len()` bytes at an offset of the file
+/// file: File to read from
+/// buffer: Destination of the bytes
+/// offset: Offset of the first byte
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buffer: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buffer.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buffer, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buffer = &mut buffer[read..];
+                offset += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write all of `buffer` at an offset of the file, without moving its cursor
+/// file: File to write to
+/// buffer: Bytes to write
+/// offset: Offset of the first byte
+#[cfg(unix)]
+fn write_all_at(file: &File, buffer: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buffer, offset)
+}
+
+/// Write all of `buffer` at an offset of the file
+/// file: File to write to
+/// buffer: Bytes to write
+/// offset: Offset of the first byte
+#[cfg(windows)]
+fn write_all_at(file: &File, mut buffer: &[u8], mut offset: u64) -> io::Result<()> {
+    while !buffer.is_empty() {
+        match std::os::windows::fs::FileExt::seek_write(file, buffer, offset)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            written => {
+                buffer = &buffer[written..];
+                offset += written as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the offset of a node in the file
+/// node_idx: Index of the node in heap order
+fn node_offset(node_idx: usize) -> u64 {
+    HEADER_LEN as u64 + node_idx as u64 * NODE_LEN as u64
+}
+
+/// File Segment Tree
+/// Read-only sum segment tree stored in a file
+/// file: File holding the tree in the archive layout
+/// header: Dimensions and configuration of the tree
+#[derive(Debug)]
+pub struct FileSegmentTree {
+    file: File,
+    header: Header,
+}
+
+impl FileSegmentTree {
+    /// Build a tree into a new file, replacing any existing file
+    /// Values are streamed into the file and the internal nodes are computed one level at a time
+    /// path: Path of the file
+    /// values: Leaf values, whose iterator must report its exact length
+    /// overflow_policy: How sums that do not fit in `isize` are handled
+    /// Returns the tree, or an error of kind `InvalidInput` wrapping a `SegmentTreeError` for empty input
+    pub fn create<P, I>(path: P, values: I, overflow_policy: OverflowPolicy) -> io::Result<FileSegmentTree>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = isize>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let values = values.into_iter();
+        let len = values.len();
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, SegmentTreeError::EmptyInput));
+        }
+
+        let header = Header { len, leaf_capacity: len.next_power_of_two(), overflow_policy };
+        let archive_len = header.archive_len().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, SegmentTreeError::InputTooLarge { len, max: usize::MAX / 2 })
+        })?;
+
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        // Padding leaves and nodes not yet written read as zero, the identity of the sum
+        file.set_len(archive_len)?;
+        write_all_at(&file, &header.encode(), 0)?;
+
+        // Leaf level
+        let first_leaf = header.leaf_capacity - 1;
+        let mut buffer = Vec::with_capacity(CHUNK_NODES * NODE_LEN);
+        let mut written = 0;
+        for value in values {
+            if written == len {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Iterator yielded more values than its length"));
+            }
+            buffer.extend_from_slice(&(value as i128).to_le_bytes());
+            written += 1;
+            if buffer.len() == CHUNK_NODES * NODE_LEN || written == len {
+                let first = first_leaf + written - buffer.len() / NODE_LEN;
+                write_all_at(&file, &buffer, node_offset(first))?;
+                buffer.clear();
+            }
+        }
+        if written != len {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Iterator yielded fewer values than its length"));
+        }
+
+        // Internal levels, bottom-up. The children of a run of parents are a contiguous run twice as long
+        let mut children = vec![0u8; 2 * CHUNK_NODES * NODE_LEN];
+        let mut level_len = header.leaf_capacity / 2;
+        while level_len > 0 {
+            let first_parent = level_len - 1;
+            // Only parents with at least one real leaf below them can be non-zero
+            let leaves_per_parent = header.leaf_capacity / level_len;
+            let used = len.div_ceil(leaves_per_parent);
+
+            let mut parent = first_parent;
+            while parent < first_parent + used {
+                let count = (first_parent + used - parent).min(CHUNK_NODES);
+                let children = &mut children[..2 * count * NODE_LEN];
+                read_exact_at(&file, children, node_offset(2 * parent + 1))?;
+
+                buffer.clear();
+                for pair in children.chunks_exact(2 * NODE_LEN) {
+                    let left = i128::from_le_bytes(pair[..NODE_LEN].try_into().unwrap());
+                    let right = i128::from_le_bytes(pair[NODE_LEN..].try_into().unwrap());
+                    buffer.extend_from_slice(&(left + right).to_le_bytes());
+                }
+                write_all_at(&file, &buffer, node_offset(parent))?;
+                parent += count;
+            }
+
+            level_len /= 2;
+        }
+
+        Ok(FileSegmentTree { file, header })
+    }
+
+    /// Open a tree stored in a file, written by `create` or `SegmentTree::archive_to`
+    /// Only the header and the file length are checked
+    /// path: Path of the file
+    /// Returns the tree, or an error of kind `InvalidData` if the file does not hold a tree
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FileSegmentTree> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() < HEADER_LEN as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "File is too short for a header"));
+        }
+        let mut header = [0u8; HEADER_LEN];
+        read_exact_at(&file, &mut header, 0)?;
+        let header = Header::decode(&header)?;
+
+        if header.archive_len() != Some(file.metadata()?.len()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "File length does not match its header"));
+        }
+
+        Ok(FileSegmentTree { file, header })
+    }
+
+    /// Get the number of leaves
+    pub fn len(&self) -> usize {
+        self.header.len
+    }
+
+    /// Check if the tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// Get the overflow policy of the tree
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.header.overflow_policy
+    }
+
+    /// Query the tree with O(log n) positioned reads
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the sum of the range, or an error. Invalid ranges and overflows are reported as errors
+    /// of kind `InvalidInput` wrapping the `SegmentTreeError`
+    pub fn query(&self, start: usize, end: usize) -> io::Result<isize> {
+        let result = self.header.query(start, end, |node_idx| {
+            let mut bytes = [0u8; NODE_LEN];
+            read_exact_at(&self.file, &mut bytes, node_offset(node_idx))?;
+            Ok::<_, io::Error>(i128::from_le_bytes(bytes))
+        })?;
+
+        result.map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+
+    /// Get a single leaf value
+    /// index: Index of the leaf
+    /// Returns the value, or an error as for `query`
+    pub fn get(&self, index: usize) -> io::Result<isize> {
+        self.query(index, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArchivedSegmentTree, FileSegmentTree, OverflowPolicy, SegmentTree, SegmentTreeError};
+    use std::io;
+
+    /// Get a path in the temporary directory that is unique to a test
+    /// name: Name of the test
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("segment_tree_{}_{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_file_tree() -> Result<(), SegmentTreeError> {
+        let path = temp_path("file_tree");
+        let input: Vec<isize> = (0..20_000).map(|i| (i * 7919) % 1000 - 500).collect();
+
+        let file_tree = FileSegmentTree::create(&path, input.iter().copied(), OverflowPolicy::Checked).unwrap();
+        let segment_tree = SegmentTree::new(&input)?;
+        assert_eq!(file_tree.len(), input.len());
+        for (start, end) in [(0, 19_999), (0, 0), (19_999, 19_999), (123, 17_456), (8191, 8192), (16_383, 16_384)] {
+            assert_eq!(file_tree.query(start, end).unwrap(), segment_tree.query(start, end)?);
+        }
+
+        // The file is a regular archive
+        let reopened = FileSegmentTree::open(&path).unwrap();
+        assert_eq!(reopened.get(4321).unwrap(), input[4321]);
+        let bytes = std::fs::read(&path).unwrap();
+        let mut archived = Vec::new();
+        segment_tree.archive_to(&mut archived).unwrap();
+        assert!(bytes == archived);
+        assert_eq!(ArchivedSegmentTree::from_bytes(&bytes).unwrap().query(5, 9)?, segment_tree.query(5, 9)?);
+
+        let error = file_tree.query(5, 20_000).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            error.get_ref().and_then(|error| error.downcast_ref::<SegmentTreeError>()),
+            Some(&SegmentTreeError::IndexOutOfBounds { index: 20_000, len: 20_000 })
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_tree_invalid() {
+        let path = temp_path("file_tree_invalid");
+        assert_eq!(
+            FileSegmentTree::create(&path, Vec::new(), OverflowPolicy::Checked).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        std::fs::write(&path, b"not a tree").unwrap();
+        assert_eq!(FileSegmentTree::open(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        FileSegmentTree::create(&path, vec![1, 2, 3], OverflowPolicy::Checked).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(FileSegmentTree::open(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
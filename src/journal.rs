@@ -0,0 +1,141 @@
+//! Operation Journal
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::ops::Deref;
+
+/// Journal Entry
+/// One recorded operation of a `JournaledSegmentTree`
+/// Update: A leaf was set to a new item
+/// Push: An item was appended
+/// Pop: The last leaf was removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry<T> {
+    Update { index: usize, item: T },
+    Push(T),
+    Pop,
+}
+
+/// Journaled Segment Tree
+/// Segment tree that appends every successful operation to a journal, for audit trails and for
+/// rebuilding the tree elsewhere from a snapshot with `SegmentTree::replay`.
+/// Queries go straight to the tree, which it dereferences to.
+/// tree: Segment tree being updated
+/// journal: Every operation applied since the tree was wrapped, in order
+pub struct JournaledSegmentTree<M: Monoid = Sum> {
+    tree: SegmentTree<M>,
+    journal: Vec<JournalEntry<M::Item>>,
+}
+
+impl<M: Monoid> JournaledSegmentTree<M> {
+    /// Start journaling the operations on a segment tree
+    /// tree: Segment tree to journal, the snapshot the journal is replayed on
+    pub fn new(tree: SegmentTree<M>) -> JournaledSegmentTree<M> {
+        JournaledSegmentTree { tree, journal: Vec::new() }
+    }
+
+    /// Unwrap the segment tree, dropping the journal
+    pub fn into_inner(self) -> SegmentTree<M> {
+        self.tree
+    }
+
+    /// Get the journal recorded so far
+    pub fn journal(&self) -> &[JournalEntry<M::Item>] {
+        &self.journal
+    }
+
+    /// Copy the journal recorded so far
+    /// Returns the operations in the order they were applied
+    pub fn export_journal(&self) -> Vec<JournalEntry<M::Item>> {
+        self.journal.clone()
+    }
+
+    /// Update a leaf and journal it
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error. Failed updates are not journaled
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.update(index, new_value.clone())?;
+        self.journal.push(JournalEntry::Update { index, item: new_value });
+        Ok(())
+    }
+
+    /// Append an item and journal it
+    /// value: Item to append
+    /// Returns `Ok(())` if the push was successful, otherwise an error. Failed pushes are not journaled
+    pub fn push(&mut self, value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.push(value.clone())?;
+        self.journal.push(JournalEntry::Push(value));
+        Ok(())
+    }
+
+    /// Remove the last leaf and journal it
+    /// Returns the removed item, or `None` if the tree is empty, which is not journaled
+    pub fn pop(&mut self) -> Option<M::Item> {
+        let item = self.tree.pop()?;
+        self.journal.push(JournalEntry::Pop);
+        Some(item)
+    }
+}
+
+impl<M: Monoid> Deref for JournaledSegmentTree<M> {
+    type Target = SegmentTree<M>;
+
+    fn deref(&self) -> &SegmentTree<M> {
+        &self.tree
+    }
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Rebuild a tree by applying a journal to a snapshot of the tree it was recorded on
+    /// initial: Snapshot the journal starts from
+    /// journal: Operations to apply, in order
+    /// Returns the resulting tree, or the error of the first operation that does not apply,
+    /// `EmptyInput` for a pop from an empty tree
+    pub fn replay(initial: SegmentTree<M>, journal: &[JournalEntry<M::Item>]) -> Result<SegmentTree<M>, SegmentTreeError> {
+        let mut tree = initial;
+        for entry in journal {
+            match entry {
+                JournalEntry::Update { index, item } => tree.update(*index, item.clone())?,
+                JournalEntry::Push(item) => tree.push(item.clone())?,
+                JournalEntry::Pop => {
+                    tree.pop().ok_or(SegmentTreeError::EmptyInput)?;
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{JournalEntry, JournaledSegmentTree, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_journal_replay() -> Result<(), SegmentTreeError> {
+        let snapshot = SegmentTree::new(&[1, 2, 3])?;
+        let mut journaled = JournaledSegmentTree::new(snapshot.clone());
+
+        journaled.update(1, 20)?;
+        journaled.push(4)?;
+        journaled.push(5)?;
+        assert_eq!(journaled.pop(), Some(5));
+        assert!(journaled.update(9, 1).is_err());
+
+        let journal = journaled.export_journal();
+        assert_eq!(journal, vec![
+            JournalEntry::Update { index: 1, item: 20 },
+            JournalEntry::Push(4),
+            JournalEntry::Push(5),
+            JournalEntry::Pop,
+        ]);
+
+        let replayed = SegmentTree::replay(snapshot, &journal)?;
+        assert_eq!(replayed.leaves(), journaled.leaves());
+        assert_eq!(replayed.query(0, 3)?, 28);
+
+        let short = SegmentTree::new(&[1])?;
+        assert_eq!(SegmentTree::replay(short, &journal).err(), Some(SegmentTreeError::IndexOutOfBounds { index: 1, len: 1 }));
+
+        Ok(())
+    }
+}
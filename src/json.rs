@@ -0,0 +1,156 @@
+//! JSON Export
+
+use crate::{Monoid, SegmentTree};
+use std::fmt::{Display, Write};
+
+/// Append a string to a JSON document as a quoted and escaped JSON string
+/// json: JSON document
+/// value: String to append
+pub(crate) fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Append a displayable value to a JSON document
+/// Values that display as a JSON number are written as numbers, anything else as a string
+/// json: JSON document
+/// value: Value to append
+pub(crate) fn push_json_value<T: Display>(json: &mut String, value: &T) {
+    let text = value.to_string();
+    if is_json_number(&text) {
+        json.push_str(&text);
+    } else {
+        push_json_string(json, &text);
+    }
+}
+
+/// Check whether text is a number in JSON syntax
+/// text: Text to check
+fn is_json_number(text: &str) -> bool {
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (integer, rest) = text.split_at(text.find(['.', 'e', 'E']).unwrap_or(text.len()));
+    let valid_integer = integer == "0" || (!integer.is_empty() && !integer.starts_with('0'));
+    if !valid_integer || !integer.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+
+    let (fraction, exponent) = rest.split_at(rest.find(['e', 'E']).unwrap_or(rest.len()));
+    let valid_fraction = fraction.is_empty()
+        || (fraction.len() > 1 && fraction[1..].bytes().all(|b| b.is_ascii_digit()));
+    let exponent_digits = exponent.get(1..).map(|e| e.strip_prefix(['+', '-']).unwrap_or(e));
+    let valid_exponent = match exponent_digits {
+        None => true,
+        Some(digits) => !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()),
+    };
+
+    valid_fraction && valid_exponent
+}
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Value: Display,
+{
+    /// Export the structure of the segment tree as JSON
+    /// Every node is an object `{"value": ..., "range": [start, end], "children": [...]}`, starting from the root.
+    /// Ranges are inclusive, and padding past the last leaf is left out.
+    /// Returns a JSON document, `null` for an empty tree
+    pub fn to_json_structure(&self) -> String {
+        let mut json = String::new();
+        if self.leaf_len == 0 {
+            json.push_str("null");
+        } else {
+            self.push_json_node(&mut json, 0);
+        }
+
+        json
+    }
+
+    /// Append a node and its children to a JSON document (Recursive)
+    /// json: JSON document
+    /// node_idx: Index of the node
+    fn push_json_node(&self, json: &mut String, node_idx: usize) {
+        let node = &self.nodes[node_idx];
+
+        // A node whose right child is all padding has the same range and value as its left child
+        let children = self.children(node_idx);
+        if let Some((left, right)) = children {
+            if self.nodes[right].start >= self.leaf_len {
+                return self.push_json_node(json, left);
+            }
+        }
+
+        json.push_str("{\"value\":");
+        push_json_value(json, &node.value);
+        let _ = write!(json, ",\"range\":[{},{}],\"children\":[", node.start, node.end.min(self.leaf_len - 1));
+
+        for (position, child) in children.into_iter().flat_map(|(left, right)| [left, right]).enumerate() {
+            if position > 0 {
+                json.push(',');
+            }
+            self.push_json_node(json, child);
+        }
+
+        json.push_str("]}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_json_number;
+    use crate::{ModInt, ModSum, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_to_json_structure() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        assert_eq!(
+            segment_tree.to_json_structure(),
+            concat!(
+                r#"{"value":6,"range":[0,2],"children":["#,
+                r#"{"value":3,"range":[0,1],"children":["#,
+                r#"{"value":1,"range":[0,0],"children":[]},{"value":2,"range":[1,1],"children":[]}]},"#,
+                r#"{"value":3,"range":[2,2],"children":[]}]}"#
+            )
+        );
+
+        // Nodes that only cover padding on the right collapse into their left child, like `Display`
+        let segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        let json = segment_tree.to_json_structure();
+        assert!(json.starts_with(r#"{"value":15,"range":[0,4],"children":[{"value":10,"range":[0,3],"#));
+        assert!(json.ends_with(r#"{"value":5,"range":[4,4],"children":[]}]}"#));
+        assert_eq!(json.matches(r#""range":[4,4]"#).count(), 1);
+
+        let mut single = SegmentTree::with_monoid(&[ModInt::<7>::new(9)], ModSum)?;
+        assert_eq!(single.to_json_structure(), r#"{"value":2,"range":[0,0],"children":[]}"#);
+        single.pop();
+        assert_eq!(single.to_json_structure(), "null");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_values() {
+        for number in ["0", "-12", "3.25", "1e9", "-0.5E-3"] {
+            assert!(is_json_number(number), "{}", number);
+        }
+        for text in ["", "-", "01", "1.", ".5", "1e", "NaN", "inf", "12ms"] {
+            assert!(!is_json_number(text), "{}", text);
+        }
+
+        let mut json = String::new();
+        super::push_json_value(&mut json, &"a \"b\"\n");
+        assert_eq!(json, r#""a \"b\"\n""#);
+    }
+}
@@ -0,0 +1,345 @@
+//! Lazy Segment Trees
+
+use crate::{ActedMonoid, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Lazy Segment Tree
+/// Segment tree with range updates, applying a map to every value of a range in O(log n).
+/// Maps are applied to the nodes covering the range and kept as pending tags, which are pushed down
+/// to the children only when a later update needs to go below them.
+/// Nodes split their range at the middle, so there are no padding leaves for maps to act on.
+/// values: Aggregate of each node, with the maps of the node and its ancestors' pushed tags applied
+/// maps: Pending map of each node, not yet applied to its children
+/// len: Number of leaves
+/// monoid: Operation used to combine values and apply maps
+#[derive(Debug, Clone)]
+pub struct LazySegmentTree<A: ActedMonoid> {
+    values: Vec<A::Value>,
+    maps: Vec<A::Map>,
+    len: usize,
+    monoid: A,
+}
+
+impl<A: ActedMonoid> LazySegmentTree<A> {
+    /// Create a new lazy segment tree
+    /// input: Vector of input values
+    /// monoid: Operation used to combine values and apply maps
+    /// Returns a new `LazySegmentTree` structure or an error
+    pub fn new(input: &[A::Item], monoid: A) -> Result<LazySegmentTree<A>, SegmentTreeError> {
+        if input.is_empty() {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+        if input.len() > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: input.len(), max: MAX_INPUT_SIZE });
+        }
+        for (index, item) in input.iter().enumerate() {
+            monoid.validate(item, index)?;
+        }
+
+        let node_count = 2 * input.len().next_power_of_two() - 1;
+        let mut segment_tree = LazySegmentTree {
+            values: vec![monoid.identity(); node_count],
+            maps: vec![monoid.identity_map(); node_count],
+            len: input.len(),
+            monoid,
+        };
+        segment_tree.build_recursive(0, 0, input.len() - 1, input);
+        Ok(segment_tree)
+    }
+
+    /// Build the values of the nodes (Recursive)
+    /// node_idx: Index of the current node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// input: Vector of input values
+    fn build_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, input: &[A::Item]) {
+        if lo == hi {
+            self.values[node_idx] = self.monoid.lift(&input[lo]);
+            return;
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        self.build_recursive(2 * node_idx + 1, lo, mid, input);
+        self.build_recursive(2 * node_idx + 2, mid + 1, hi, input);
+        self.update_node(node_idx);
+    }
+
+    /// Number of values in the segment tree
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the segment tree holds no values, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the monoid of the segment tree
+    pub fn monoid(&self) -> &A {
+        &self.monoid
+    }
+
+    /// Validate a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns `Ok(())` if the range is valid, otherwise an error
+    fn validate_range(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        Ok(())
+    }
+
+    /// Recompute a node from its children
+    /// node_idx: Index of the internal node
+    fn update_node(&mut self, node_idx: usize) {
+        self.values[node_idx] = self.monoid.combine(&self.values[2 * node_idx + 1], &self.values[2 * node_idx + 2]);
+    }
+
+    /// Apply a map to a node and record it as pending for the children
+    /// node_idx: Index of the node
+    /// map: Map to apply
+    /// len: Number of leaves covered by the node
+    fn apply_node(&mut self, node_idx: usize, map: &A::Map, len: usize) {
+        self.values[node_idx] = self.monoid.apply(map, &self.values[node_idx], len);
+        self.maps[node_idx] = self.monoid.compose(map, &self.maps[node_idx]);
+    }
+
+    /// Push the pending map of an internal node down to its children
+    /// node_idx: Index of the internal node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    fn push_down(&mut self, node_idx: usize, lo: usize, hi: usize) {
+        let map = std::mem::replace(&mut self.maps[node_idx], self.monoid.identity_map());
+        let mid = lo + (hi - lo) / 2;
+        self.apply_node(2 * node_idx + 1, &map, mid - lo + 1);
+        self.apply_node(2 * node_idx + 2, &map, hi - mid);
+    }
+
+    /// Apply a map to every value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    /// Returns `Ok(())` if the map was applied, otherwise an error
+    pub fn apply_range(&mut self, start: usize, end: usize, map: A::Map) -> Result<(), SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        self.apply_range_recursive(0, 0, self.len - 1, start, end, &map);
+        Ok(())
+    }
+
+    /// Apply a map to the nodes covering a range (Recursive)
+    /// node_idx: Index of the current node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    fn apply_range_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize, map: &A::Map) {
+        if end < lo || hi < start {
+            return;
+        }
+        if start <= lo && hi <= end {
+            self.apply_node(node_idx, map, hi - lo + 1);
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        self.apply_range_recursive(2 * node_idx + 1, lo, mid, start, end, map);
+        self.apply_range_recursive(2 * node_idx + 2, mid + 1, hi, start, end, map);
+        self.update_node(node_idx);
+    }
+
+    /// Update the value at an index
+    /// index: Index of the value
+    /// new_value: New value
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: A::Item) -> Result<(), SegmentTreeError> {
+        if index >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.len });
+        }
+        self.monoid.validate(&new_value, index)?;
+
+        let value = self.monoid.lift(&new_value);
+        self.update_recursive(0, 0, self.len - 1, index, value);
+        Ok(())
+    }
+
+    /// Write a leaf value, pushing pending maps out of its path first (Recursive)
+    /// node_idx: Index of the current node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// index: Index of the value
+    /// value: Lifted new value
+    fn update_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, index: usize, value: A::Value) {
+        if lo == hi {
+            self.values[node_idx] = value;
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        if index <= mid {
+            self.update_recursive(2 * node_idx + 1, lo, mid, index, value);
+        } else {
+            self.update_recursive(2 * node_idx + 2, mid + 1, hi, index, value);
+        }
+        self.update_node(node_idx);
+    }
+
+    /// Query a range of the segment tree
+    /// Pending maps are applied to the partial results on the way up instead of being pushed down,
+    /// so querying does not modify the tree
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&self, start: usize, end: usize) -> Result<A::Output, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let value = self.query_recursive(0, 0, self.len - 1, start, end).unwrap_or_else(|| self.monoid.identity());
+        self.monoid.finish(value, start, end)
+    }
+
+    /// Aggregate the part of a range below a node, from left to right (Recursive)
+    /// node_idx: Index of the current node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the aggregate, or `None` if the node does not overlap the range
+    fn query_recursive(&self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize) -> Option<A::Value> {
+        if end < lo || hi < start {
+            return None;
+        }
+        if start <= lo && hi <= end {
+            return Some(self.values[node_idx].clone());
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        let left = self.query_recursive(2 * node_idx + 1, lo, mid, start, end);
+        let right = self.query_recursive(2 * node_idx + 2, mid + 1, hi, start, end);
+        let value = match (left, right) {
+            (Some(mut left), Some(right)) => {
+                self.monoid.combine_into(&mut left, &right);
+                left
+            }
+            (Some(value), None) | (None, Some(value)) => value,
+            (None, None) => return None,
+        };
+
+        // The pending map of this node has not reached the children yet
+        let covered = end.min(hi) - start.max(lo) + 1;
+        Some(self.monoid.apply(&self.maps[node_idx], &value, covered))
+    }
+
+    /// Get the value at an index
+    /// index: Index of the value
+    /// Returns the result for the single index, or an error
+    pub fn get(&self, index: usize) -> Result<A::Output, SegmentTreeError> {
+        self.query(index, index)
+    }
+
+    /// Get the result of the whole segment tree in O(1), from the root node
+    pub fn total(&self) -> Result<A::Output, SegmentTreeError> {
+        self.monoid.finish(self.values[0].clone(), 0, self.len - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ActedMonoid, LazySegmentTree, Monoid, SegmentTreeError};
+
+    /// Minimum and count of the minimum, with maps adding a constant
+    #[derive(Debug, Clone, Copy)]
+    struct AddMinCount;
+
+    impl Monoid for AddMinCount {
+        type Item = i64;
+        type Value = (i64, usize);
+        type Output = (i64, usize);
+
+        fn identity(&self) -> (i64, usize) {
+            (i64::MAX, 0)
+        }
+
+        fn lift(&self, item: &i64) -> (i64, usize) {
+            (*item, 1)
+        }
+
+        fn combine(&self, left: &(i64, usize), right: &(i64, usize)) -> (i64, usize) {
+            match left.0.cmp(&right.0) {
+                std::cmp::Ordering::Less => *left,
+                std::cmp::Ordering::Greater => *right,
+                std::cmp::Ordering::Equal => (left.0, left.1 + right.1),
+            }
+        }
+
+        fn finish(&self, value: (i64, usize), _start: usize, _end: usize) -> Result<(i64, usize), SegmentTreeError> {
+            Ok(value)
+        }
+    }
+
+    impl ActedMonoid for AddMinCount {
+        type Map = i64;
+
+        fn identity_map(&self) -> i64 {
+            0
+        }
+
+        fn apply(&self, map: &i64, value: &(i64, usize), _len: usize) -> (i64, usize) {
+            (value.0 + map, value.1)
+        }
+
+        fn compose(&self, outer: &i64, inner: &i64) -> i64 {
+            outer + inner
+        }
+    }
+
+    #[test]
+    fn test_lazy_segment_tree() -> Result<(), SegmentTreeError> {
+        let mut items: Vec<i64> = (0..23).map(|i| (i * 37) % 11).collect();
+        let mut segment_tree = LazySegmentTree::new(&items, AddMinCount)?;
+
+        // Deterministic pseudo-random operations, checked against a plain vector
+        let mut seed = 12345u64;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for step in 0..300 {
+            let (a, b) = (next(items.len()), next(items.len()));
+            let (start, end) = (a.min(b), a.max(b));
+            match step % 3 {
+                0 => {
+                    let delta = next(7) as i64 - 3;
+                    segment_tree.apply_range(start, end, delta)?;
+                    items[start..=end].iter_mut().for_each(|item| *item += delta);
+                }
+                1 => {
+                    let value = next(10) as i64;
+                    segment_tree.update(a, value)?;
+                    items[a] = value;
+                }
+                _ => {
+                    let min = *items[start..=end].iter().min().unwrap();
+                    let count = items[start..=end].iter().filter(|&&item| item == min).count();
+                    assert_eq!(segment_tree.query(start, end)?, (min, count));
+                }
+            }
+        }
+
+        let min = *items.iter().min().unwrap();
+        assert_eq!(segment_tree.total()?.0, min);
+        assert_eq!(segment_tree.get(4)?, (items[4], 1));
+
+        assert_eq!(segment_tree.apply_range(3, 23, 1), Err(SegmentTreeError::IndexOutOfBounds { index: 23, len: 23 }));
+        assert_eq!(segment_tree.query(5, 4), Err(SegmentTreeError::InvalidRange { start: 5, end: 4, len: 23 }));
+        assert!(LazySegmentTree::new(&[], AddMinCount).is_err());
+
+        Ok(())
+    }
+}
@@ -1,84 +1,341 @@
-/// Segment Tree
-
+//! Segment Tree
+
+#[cfg(feature = "persist")]
+mod archive;
+mod builder;
+mod cache;
+mod changes;
+mod columnar;
+mod combinators;
+mod compensated;
+mod decimal;
+mod deferred;
+mod diff;
+mod display;
+mod duration;
+#[cfg(feature = "dynamic")]
+mod dynamic;
+mod error;
+mod explain;
+#[cfg(feature = "grid")]
+mod fenwick;
+#[cfg(all(feature = "persist", any(unix, windows)))]
+mod file_tree;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod growth;
+#[cfg(feature = "json")]
+mod json;
+mod journal;
+#[cfg(feature = "lazy")]
+mod lazy;
+mod majority;
+mod memory;
+#[cfg(feature = "grid")]
+mod merge_sort;
+mod model;
+mod modint;
+mod monoid;
+#[cfg(feature = "grid")]
+mod nd;
+mod numeric;
+mod occurrences;
+mod ops;
+mod overflow;
+#[cfg(feature = "persist")]
+mod persist;
+#[cfg(feature = "lazy")]
+mod persistent_lazy;
+#[cfg(feature = "grid")]
+mod point_set;
+mod prefix;
+#[cfg(feature = "lazy")]
+mod presets;
+mod range;
+mod runs;
+mod search;
+#[cfg(feature = "dynamic")]
+mod signed;
+#[cfg(feature = "lazy")]
+mod sparse_lazy;
+mod stats;
+mod summary;
+mod transaction;
+mod traverse;
+mod verify;
+
+#[cfg(feature = "persist")]
+pub use archive::ArchivedSegmentTree;
+pub use builder::SegmentTreeBuilder;
+#[cfg(feature = "lazy")]
+pub use builder::LazySegmentTreeBuilder;
+pub use cache::CachedSegmentTree;
+pub use changes::{Marker, TrackedSegmentTree};
+pub use columnar::ColumnarSummaryTree;
+pub use combinators::Zip;
+pub use compensated::{Compensated, CompensatedSum};
+pub use decimal::{Decimal, DecimalSum};
+pub use deferred::Deferred;
+pub use diff::{DiffableSegmentTree, Difference};
+pub use duration::{DurationMax, DurationMin, DurationSum};
+#[cfg(feature = "dynamic")]
+pub use dynamic::DynamicSegmentTree;
+pub use error::{InvariantError, SegmentTreeError};
+pub use explain::{Decision, ExplainStep, QueryExplanation};
+#[cfg(feature = "grid")]
+pub use fenwick::{FenwickTree2D, RectAddFenwickTree2D};
+#[cfg(all(feature = "persist", any(unix, windows)))]
+pub use file_tree::FileSegmentTree;
+pub use journal::{JournalEntry, JournaledSegmentTree};
+#[cfg(feature = "lazy")]
+pub use lazy::LazySegmentTree;
+pub use majority::Majority;
+pub use memory::MemoryReport;
+#[cfg(feature = "grid")]
+pub use merge_sort::MergeSortTree;
+pub use model::{NaiveRangeModel, RangeModel};
+pub use modint::{ModInt, ModProduct, ModSum};
+#[cfg(feature = "lazy")]
+pub use monoid::ActedMonoid;
+pub use monoid::{FnMonoid, Gcd, Lcm, Max, Min, Monoid, Sum};
+#[cfg(feature = "grid")]
+pub use nd::SegmentTreeND;
+pub use numeric::{NumSum, Number};
+pub use occurrences::{CountingSegmentTree, ValueIndex};
+pub use ops::Op;
+pub use overflow::OverflowPolicy;
+#[cfg(feature = "lazy")]
+pub use persistent_lazy::PersistentLazySegmentTree;
+#[cfg(feature = "grid")]
+pub use point_set::{PointSet2D, WeightedPointSet2D};
+#[cfg(feature = "lazy")]
+pub use presets::{
+    RangeAddRangeMin, RangeAddRangeSum, RangeAffineRangeSum, RangeAssignRangeSum, RangeAssignSegmentCount, RangeChminRangeMax,
+    RangeMulRangeSum,
+};
+pub use runs::{LongestEqualRun, LongestIncreasingRun, RunSummary};
+#[cfg(feature = "dynamic")]
+pub use signed::{SignedSegmentTree, SignedSegmentTreeError};
+#[cfg(feature = "lazy")]
+pub use sparse_lazy::SparseLazySegmentTree;
+pub use stats::QueryStats;
+pub use summary::{RangeSummary, Summary};
+pub use transaction::Transaction;
+pub use traverse::{Decompose, NodeRef, Step};
+
+#[cfg(feature = "rayon")]
 use rayon::prelude::*;
+use growth::Growth;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
 
-// Maximum and minimum values for input elements
-const MAX_VALUE: isize = isize::MAX / 2;
-const MIN_VALUE: isize = isize::MIN / 2;
+// Maximum number of input elements
 const MAX_INPUT_SIZE: usize = usize::MAX / 2 - 1;
 
 /// Node
 /// Structure for each node in the segment tree
-/// value: Sum of the range
+/// value: Aggregate of the range, as combined by the monoid of the tree
 /// start: Start index of the range, in leaves
 /// end: End index of the range, in leaves
-/// left: Index of left child
-/// right: Index of right child
+/// The children of node `i` are always at `2i + 1` and `2i + 2`, so they are computed instead of stored
 #[derive(Debug, Clone)]
-pub struct Node {
-    pub value: isize,
+pub struct Node<V = i128> {
+    pub value: V,
     pub start: usize,
     pub end: usize,
-    pub left: Option<usize>,
-    pub right: Option<usize>,
 }
-unsafe impl Send for Node {}
-unsafe impl Sync for Node {}
+
+impl<V> Node<V> {
+    /// Returns `true` if the node is a leaf, covering a single index
+    pub fn is_leaf(&self) -> bool {
+        self.start == self.end
+    }
+}
+unsafe impl<V: Send> Send for Node<V> {}
+unsafe impl<V: Sync> Sync for Node<V> {}
 
 /// Segment Tree
 /// Structure for the segment tree
 /// nodes: Vector of `Node` structures
+/// leaves: Vector of leaf items, in input order
 /// leaf_len: Number of leaves in the segment tree
+/// leaf_capacity: Number of leaves the nodes are built for (power of two). Leaves past `leaf_len` hold the identity.
 /// leaf_indices: Vector of indices of leaf nodes. This allows changes to the tree without walking the tree twice.
-pub struct SegmentTree {
-    nodes: Vec<Node>,
+/// monoid: Operation used to combine values, `Sum` by default
+/// growth: Node and leaf storage for double the capacity, filled a little on every `push` while the tree is over half full
+pub struct SegmentTree<M: Monoid = Sum> {
+    nodes: Vec<Node<M::Value>>,
+    leaves: Vec<M::Item>,
     leaf_len: usize,
+    leaf_capacity: usize,
     //tree_len: usize,
     leaf_indices: Vec<usize>,
+    monoid: M,
+    growth: Option<Growth<M::Item, M::Value>>,
 }
-unsafe impl Send for SegmentTree {}
-unsafe impl Sync for SegmentTree {}
+unsafe impl<M: Monoid + Send> Send for SegmentTree<M> where M::Item: Send, M::Value: Send {}
+unsafe impl<M: Monoid + Sync> Sync for SegmentTree<M> where M::Item: Sync, M::Value: Sync {}
 
-/// Implementation of the segment tree
+/// Implementation of the sum segment tree
 impl SegmentTree {
-    /// Create a new segment tree
+    /// Create a new segment tree, with checked overflow
+    /// input: Vector of input values
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn new(input: &[isize]) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::with_overflow_policy(input, OverflowPolicy::Checked)
+    }
+
+    /// Create a new segment tree with an overflow policy
+    /// input: Vector of input values
+    /// overflow_policy: How results that do not fit in `isize` are handled
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn with_overflow_policy(input: &[isize], overflow_policy: OverflowPolicy) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::with_monoid(input, Sum { overflow_policy })
+    }
+
+    /// Create an empty segment tree with checked overflow and room for `capacity` values
+    /// Values can then be pushed up to the capacity without rebuilding the nodes
+    /// capacity: Number of values to preallocate for
+    /// Returns a new `SegmentTree` structure or an error if the capacity is too large
+    pub fn with_capacity(capacity: usize) -> Result<SegmentTree, SegmentTreeError> {
+        let mut segment_tree = SegmentTree::empty(Sum::default());
+        segment_tree.reserve(capacity)?;
+        Ok(segment_tree)
+    }
+
+    /// Create a segment tree with checked overflow from the values at a few indices, in O(n + k)
+    /// Every other index holds 0, and a repeated index keeps its last value
+    /// len: Number of values
+    /// values: Pairs of index and value
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn from_sparse(len: usize, values: &[(usize, isize)]) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::from_sparse_iter(len, values.iter().copied())
+    }
+
+    /// Create a segment tree with checked overflow from a map of indices to values, in O(n + k)
+    /// Every other index holds 0
+    /// len: Number of values
+    /// values: Map of indices to values
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn from_sparse_map(len: usize, values: &HashMap<usize, isize>) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::from_sparse_iter(len, values.iter().map(|(&index, &value)| (index, value)))
+    }
+
+    /// Create a segment tree from pairs of index and value, with 0 at every other index
+    /// len: Number of values
+    /// values: Pairs of index and value
+    /// Returns a new `SegmentTree` structure or an error
+    fn from_sparse_iter(len: usize, values: impl Iterator<Item = (usize, isize)>) -> Result<SegmentTree, SegmentTreeError> {
+        if len > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len, max: MAX_INPUT_SIZE });
+        }
+
+        let mut leaves = vec![0; len];
+        for (index, value) in values {
+            *leaves.get_mut(index).ok_or(SegmentTreeError::IndexOutOfBounds { index, len })? = value;
+        }
+        SegmentTree::from_leaves(leaves, Sum::default())
+    }
+
+    /// Get the overflow policy of the segment tree
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.monoid.overflow_policy
+    }
+
+    /// Add a delta to a leaf node in the segment tree
+    /// index: Index of the leaf node to update
+    /// delta: Value to add to the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    /// A leaf value that does not fit in `isize` is handled by the overflow policy
+    pub fn add(&mut self, index: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.validate_index(index)?;
+
+        let new_value = self.monoid.overflow_policy.add(self.leaves[index], delta, index)?;
+        self.set_leaf(index, new_value);
+        Ok(())
+    }
+}
+
+/// Implementation of the segment tree
+impl<M: Monoid> SegmentTree<M> {
+    /// Create a new segment tree combining values with a monoid
     /// input: Vector of input values
-    /// Returns a new `SegmentTree` structure or an error message
-    pub fn new(input: &Vec<isize>) -> Result<SegmentTree, &'static str> {
-        SegmentTree::validate_input(&input)?;
-        let leaf_len = input.len();
-        let tree_len = SegmentTree::get_segment_tree_size(leaf_len);
-        let mut nodes = SegmentTree::reserve_nodes(tree_len);
-        let mut leaf_indices = vec![0; leaf_len];
-        SegmentTree::build_nodes_recursive(&mut nodes, &mut leaf_indices, 0, 0, leaf_len - 1, &input);
-
-        Ok(SegmentTree {
+    /// monoid: Operation used to combine values
+    /// Returns a new `SegmentTree` structure or an error
+    pub fn with_monoid(input: &[M::Item], monoid: M) -> Result<SegmentTree<M>, SegmentTreeError> {
+        SegmentTree::from_leaves(input.to_vec(), monoid)
+    }
+
+    /// Create a new segment tree that takes ownership of its leaf values
+    /// leaves: Vector of input values
+    /// monoid: Operation used to combine values
+    /// Returns a new `SegmentTree` structure or an error
+    fn from_leaves(leaves: Vec<M::Item>, monoid: M) -> Result<SegmentTree<M>, SegmentTreeError> {
+        SegmentTree::validate_input(&leaves, &monoid)?;
+        let leaf_len = leaves.len();
+        let leaf_capacity = leaf_len.next_power_of_two();
+        let (nodes, leaf_indices) = SegmentTree::build_nodes(&leaves, leaf_capacity, &monoid);
+
+        let mut segment_tree = SegmentTree {
             nodes,
+            leaves,
             leaf_len,
+            leaf_capacity,
             //tree_len,
             leaf_indices,
-        })
+            monoid,
+            growth: None,
+        };
+        segment_tree.reserve_leaves();
+        Ok(segment_tree)
+    }
+
+    /// Create a segment tree with no leaves
+    /// monoid: Operation used to combine values
+    fn empty(monoid: M) -> SegmentTree<M> {
+        let (nodes, leaf_indices) = SegmentTree::build_nodes(&[], 1, &monoid);
+
+        SegmentTree {
+            nodes,
+            leaves: Vec::new(),
+            leaf_len: 0,
+            leaf_capacity: 1,
+            leaf_indices,
+            monoid,
+            growth: None,
+        }
+    }
+
+    /// Get the monoid of the segment tree
+    pub fn monoid(&self) -> &M {
+        &self.monoid
     }
 
     /// Validate input values
     /// input: Vector of input values
-    /// Returns `Ok(())` if input is valid, otherwise an error message
-    fn validate_input(input: &Vec<isize>) -> Result<(), &'static str> {
-        if input.len() == 0 {
-            return Err("Input is empty");
+    /// monoid: Monoid validating each value
+    /// Returns `Ok(())` if input is valid, otherwise an error
+    fn validate_input(input: &[M::Item], monoid: &M) -> Result<(), SegmentTreeError> {
+        if input.is_empty() {
+            return Err(SegmentTreeError::EmptyInput);
         }
 
-        if input.len() > MAX_INPUT_SIZE {
-            return Err("Input size exceeded maximum value");
+        SegmentTree::validate_values(input, monoid)
+    }
+
+    /// Validate a slice of leaf values, which may be empty
+    /// values: Vector of leaf values
+    /// monoid: Monoid validating each value
+    /// Returns `Ok(())` if the values are valid, otherwise an error
+    fn validate_values(values: &[M::Item], monoid: &M) -> Result<(), SegmentTreeError> {
+        if values.len() > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: values.len(), max: MAX_INPUT_SIZE });
         }
 
-        for i in 0..input.len() {
-            if input[i] < MIN_VALUE {
-                return Err("Input value exceeded maximum value");
-            }
-            if input[i] > MAX_VALUE {
-                return Err("Input value exceeded minimum value");
-            }
+        for (index, value) in values.iter().enumerate() {
+            monoid.validate(value, index)?;
         }
 
         Ok(())
@@ -100,19 +357,32 @@ impl SegmentTree {
 
     /// Reserve memory for the nodes in the segment tree
     /// tree_size: Size of the segment tree
-    fn reserve_nodes(tree_size: usize) -> Vec<Node> {
+    /// monoid: Operation used to combine values
+    fn reserve_nodes(tree_size: usize, monoid: &M) -> Vec<Node<M::Value>> {
         vec![
             Node {
-                value: 0,
+                value: monoid.identity(),
                 start: 0,
                 end: 0,
-                left: None,
-                right: None,
             }
             ; tree_size
         ]
     }
 
+    /// Build the nodes of the segment tree
+    /// input: Vector of input values
+    /// leaf_capacity: Number of leaves to build, at least `input.len()`
+    /// monoid: Operation used to combine values
+    /// Returns the nodes and the indices of the leaf nodes
+    fn build_nodes(input: &[M::Item], leaf_capacity: usize, monoid: &M) -> (Vec<Node<M::Value>>, Vec<usize>) {
+        let tree_len = SegmentTree::<M>::get_segment_tree_size(leaf_capacity);
+        let mut nodes = SegmentTree::<M>::reserve_nodes(tree_len, monoid);
+        let mut leaf_indices = vec![0; leaf_capacity];
+        SegmentTree::build_nodes_recursive(&mut nodes, &mut leaf_indices, 0, 0, leaf_capacity - 1, input, monoid);
+
+        (nodes, leaf_indices)
+    }
+
     /// Build the nodes of the segment tree (Recursive)
     /// nodes: Vector of `Node` structures
     /// leaf_indices: Vector of indices of leaf nodes
@@ -120,52 +390,48 @@ impl SegmentTree {
     /// start: Start index of the range
     /// end: End index of the range
     /// input: Vector of input values
-    /// Returns the sum of the range
-    fn build_nodes_recursive(nodes: &mut Vec<Node>, leaf_indices: &mut Vec<usize>, node: usize, start: usize, end: usize, input: &[isize]) -> isize {
+    /// monoid: Operation used to combine values
+    fn build_nodes_recursive(nodes: &mut Vec<Node<M::Value>>, leaf_indices: &mut Vec<usize>, node: usize, start: usize, end: usize, input: &[M::Item], monoid: &M) {
         if start == end {
-            // Leaf node
-            nodes[node].value = input[start];
+            // Leaf node, padding leaves past the end of the input keep the identity
+            if let Some(item) = input.get(start) {
+                nodes[node].value = monoid.lift(item);
+            }
             nodes[node].start = start;
             nodes[node].end = end;
             leaf_indices[start] = node;
-            return input[start];
+            return;
         }
 
         let mid = (start + end) / 2;
         let left = 2 * node + 1;
         let right = 2 * node + 2;
 
-        nodes[node].left = Some(left);
-        nodes[node].right = Some(right);
         nodes[node].start = start;
         nodes[node].end = end;
 
-        let left_sum = SegmentTree::build_nodes_recursive(nodes, leaf_indices, left, start, mid, input);
-        let right_sum = SegmentTree::build_nodes_recursive(nodes, leaf_indices, right, mid + 1, end, input);
+        SegmentTree::build_nodes_recursive(nodes, leaf_indices, left, start, mid, input, monoid);
+        SegmentTree::build_nodes_recursive(nodes, leaf_indices, right, mid + 1, end, input, monoid);
 
-        nodes[node].value = left_sum + right_sum;
-        nodes[node].value
+        nodes[node].value = monoid.combine(&nodes[left].value, &nodes[right].value);
     }
 
     /// Validate query parameters
     /// start: Start index of the range
     /// end: End index of the range
-    /// Returns `Ok(())` if parameters are valid, otherwise an error message
-    fn validate_public_query(&self, start: usize, end: usize) -> Result<(), &'static str> {
-        if self.leaf_len == 0 {
-            return Err("Segment tree is empty")
-        }
-
+    /// Returns `Ok(())` if parameters are valid, otherwise an error
+    fn validate_public_query(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
         if start > end {
-            return Err("Start index is greater than end index")
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.leaf_len })
         }
 
-        if start > self.leaf_len - 1 {
-            return Err("Start index is out of bounds")
+        // An empty tree reports the start index as out of bounds for length 0
+        if start >= self.leaf_len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: start, len: self.leaf_len })
         }
 
-        if end > self.leaf_len - 1 {
-            return Err("End index is out of bounds")
+        if end >= self.leaf_len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.leaf_len })
         }
 
         Ok(())
@@ -174,72 +440,185 @@ impl SegmentTree {
     /// Query the segment tree
     /// start: Start index of the range
     /// end: End index of the range
-    /// Returns the sum of the range
-    pub fn query(&self, start: usize, end: usize) -> Result<isize, &'static str> {
+    /// Returns the aggregate of the range, as finished by the monoid
+    pub fn query(&self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
         self.validate_public_query(start, end)?;
-        Ok(self.internal_query_recursive(0, start, end))
+        self.monoid.finish(self.internal_query(start, end), start, end)
+    }
+
+    /// Get the result of the whole segment tree in O(1), from the root node
+    /// Returns the same result as `query(0, len - 1)`, or an error if the tree is empty
+    pub fn total(&self) -> Result<M::Output, SegmentTreeError> {
+        if self.leaf_len == 0 {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+
+        self.monoid.finish(self.nodes[0].value.clone(), 0, self.leaf_len - 1)
+    }
+
+    /// Aggregate a validated range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the aggregate of the range
+    fn internal_query(&self, start: usize, end: usize) -> M::Value {
+        let mut value = None;
+        self.internal_query_recursive(0, start, end, &mut value);
+        value.unwrap_or_else(|| self.monoid.identity())
+    }
+
+    /// Aggregate a validated range bottom-up, climbing from its first and last leaf to their common ancestor
+    /// Uses 1-based heap positions, where the leaf of index `i` is at `leaf_capacity + i`
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the aggregate of the range
+    fn internal_query_bottom_up(&self, start: usize, end: usize) -> M::Value {
+        let mut left = self.monoid.identity();
+        let mut right = self.monoid.identity();
+        let mut low = start + self.leaf_capacity;
+        let mut high = end + self.leaf_capacity + 1;
+        while low < high {
+            if low % 2 == 1 {
+                self.monoid.combine_into(&mut left, &self.nodes[low - 1].value);
+                low += 1;
+            }
+            if high % 2 == 1 {
+                high -= 1;
+                right = self.monoid.combine(&self.nodes[high - 1].value, &right);
+            }
+            low /= 2;
+            high /= 2;
+        }
+
+        self.monoid.combine_into(&mut left, &right);
+        left
     }
 
     /// Function to query the segment tree (Recursive)
+    /// Nodes covering the range are folded into the accumulator from left to right, so only the
+    /// first one is cloned and every later one is combined into it in place
     /// node_idx: Index of the current node
     /// start: Start index of the range
     /// end: End index of the range
-    /// Returns the sum of the range
-    fn internal_query_recursive(&self, node_idx: usize, start: usize, end: usize) -> isize {
+    /// value: Accumulated aggregate of the nodes visited so far, `None` before the first one
+    fn internal_query_recursive(&self, node_idx: usize, start: usize, end: usize, value: &mut Option<M::Value>) {
         if start <= self.nodes[node_idx].start && end >= self.nodes[node_idx].end {
-            return self.nodes[node_idx].value;
+            match value {
+                Some(value) => self.monoid.combine_into(value, &self.nodes[node_idx].value),
+                None => *value = Some(self.nodes[node_idx].value.clone()),
+            }
+            return;
         }
 
         if end < self.nodes[node_idx].start || start > self.nodes[node_idx].end {
-            return 0;
+            return;
         }
 
-        let left_sum = self.internal_query_recursive(self.nodes[node_idx].left.unwrap(), start, end);
-        let right_sum = self.internal_query_recursive(self.nodes[node_idx].right.unwrap(), start, end);
-
-        left_sum + right_sum
+        self.internal_query_recursive(2 * node_idx + 1, start, end, value);
+        self.internal_query_recursive(2 * node_idx + 2, start, end, value);
     }
 
     /// Query the segment tree in parallel
+    /// Requires the `rayon` feature
     /// queries: Vector of query ranges
     /// Returns a vector of query results
-    pub fn parallel_query(&self, queries: &[(usize, usize)]) -> Vec<Result<isize, &'static str>> {
+    #[cfg(feature = "rayon")]
+    pub fn parallel_query(&self, queries: &[(usize, usize)]) -> Vec<Result<M::Output, SegmentTreeError>>
+    where
+        M: Sync,
+        M::Item: Sync,
+        M::Value: Sync,
+        M::Output: Send,
+    {
         queries.par_iter()
             .map(|(start, end)| self.query(*start, *end))
             .collect()
     }
 
-    /// Validate update parameters
-    /// index: Index of the leaf node to update
-    /// new_value: New value for the leaf node
-    /// Returns `Ok(())` if parameters are valid, otherwise an error message
-    fn validate_public_update(&self, index: usize, new_value: isize) -> Result<(), &'static str> {
-        if self.leaf_len == 0 {
-            return Err("Segment tree is empty");
+    /// Query the segment tree with a batch of ranges
+    /// All ranges are validated before any are answered, so an invalid range rejects the whole batch
+    /// without answering the others. Each range is then folded bottom-up from its two leaves, which
+    /// reads only the nodes beside the two paths instead of descending from the root; `bench --batch`
+    /// measures it against calling `query` once per range
+    /// queries: Vector of query ranges
+    /// Returns a vector of query results in the order of `queries`, or the first error
+    pub fn query_batch(&self, queries: &[(usize, usize)]) -> Result<Vec<M::Output>, SegmentTreeError> {
+        for &(start, end) in queries {
+            self.validate_public_query(start, end)?;
         }
 
-        if index >= self.leaf_len {
-            return Err("Update index is out of bounds");
-        }
+        queries.iter()
+            .map(|&(start, end)| self.monoid.finish(self.internal_query_bottom_up(start, end), start, end))
+            .collect()
+    }
 
-        if new_value > MAX_VALUE || new_value < MIN_VALUE {
-            return Err("New value is out of valid range");
+    /// Validate a leaf index
+    /// index: Index of the leaf node
+    /// Returns `Ok(())` if the index is in bounds, otherwise an error
+    fn validate_index(&self, index: usize) -> Result<(), SegmentTreeError> {
+        if index >= self.leaf_len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.leaf_len });
         }
 
         Ok(())
     }
 
+    /// Store a leaf item and recompute the ancestors of its leaf node
+    /// index: Index of the leaf, already validated
+    /// item: New item for the leaf
+    fn set_leaf(&mut self, index: usize, item: M::Item) {
+        self.leaves[index] = item;
+        self.commit_leaf(index);
+    }
+
+    /// Recompute a leaf node from its stored item, then recompute its ancestors
+    /// index: Index of the leaf, already validated
+    fn commit_leaf(&mut self, index: usize) {
+        let leaf_node = self.leaf_indices[index];
+        self.nodes[leaf_node].value = self.monoid.lift(&self.leaves[index]);
+        self.mirror_node(leaf_node);
+        self.update_ancestors(leaf_node);
+    }
+
     /// Update a leaf node in the segment tree
     /// index: Index of the leaf node to update
     /// new_value: New value for the leaf node
-    /// Returns `Ok(())` if the update was successful, otherwise an error message
-    pub fn update(&mut self, index: usize, new_value: isize) -> Result<(), &'static str> {
-        self.validate_public_update(index, new_value)?;
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.validate_index(index)?;
+        self.monoid.validate(&new_value, index)?;
 
-        let leaf_node = self.leaf_indices[index];
-        self.nodes[leaf_node].value = new_value;
+        self.set_leaf(index, new_value);
+        Ok(())
+    }
 
-        self.update_ancestors(leaf_node);
+    /// Update a leaf node in the segment tree from its current value
+    /// index: Index of the leaf node to update
+    /// f: Function mapping the current value to the new value
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update_with<F: FnOnce(M::Item) -> M::Item>(&mut self, index: usize, f: F) -> Result<(), SegmentTreeError> {
+        self.validate_index(index)?;
+
+        let new_value = f(self.leaves[index].clone());
+        self.monoid.validate(&new_value, index)?;
+        self.set_leaf(index, new_value);
+        Ok(())
+    }
+
+    /// Swap two leaf nodes in the segment tree
+    /// first: Index of the first leaf node
+    /// second: Index of the second leaf node
+    /// Returns `Ok(())` if the swap was successful, otherwise an error
+    pub fn swap(&mut self, first: usize, second: usize) -> Result<(), SegmentTreeError> {
+        self.validate_index(first)?;
+        self.validate_index(second)?;
+
+        if first == second {
+            return Ok(());
+        }
+
+        self.leaves.swap(first, second);
+        self.commit_leaf(first);
+        self.commit_leaf(second);
         Ok(())
     }
 
@@ -258,12 +637,8 @@ impl SegmentTree {
             // Calculate parent index using binary heap property
             let parent = (node_idx - 1) / 2;
 
-            // Get indices of both children (we know they exist because this is a parent node)
-            let left_child = self.nodes[parent].left.unwrap();
-            let right_child = self.nodes[parent].right.unwrap();
-
-            // Update parent's value as sum of its children
-            self.nodes[parent].value = self.nodes[left_child].value + self.nodes[right_child].value;
+            // Recompute the parent from both of its children
+            self.update_node(parent);
 
             // Move up to the parent for the next iteration
             // This creates a straight path to the root, making recursion unnecessary
@@ -271,12 +646,531 @@ impl SegmentTree {
         }
     }
 
-    /// Update leaf nodes in the segment tree in a batch
-    /// updates: Vector of tuples with index and new value
-    pub fn batch_update(&mut self, updates: &[(usize, isize)]) -> Vec<Result<(), &'static str>> {
-        updates.iter()
-            .map(|(index, value)| self.update(*index, *value))
-            .collect()
+    /// Get the children of a node, computed from its index in the heap layout
+    /// node_idx: Index of the node
+    /// Returns the indices of the left and right children, or `None` for a leaf node
+    fn children(&self, node_idx: usize) -> Option<(usize, usize)> {
+        (node_idx < self.nodes.len() / 2).then_some((2 * node_idx + 1, 2 * node_idx + 2))
+    }
+
+    /// Recompute an internal node from its children
+    /// node_idx: Index of the internal node
+    fn update_node(&mut self, node_idx: usize) {
+        let left_child = 2 * node_idx + 1;
+        let right_child = 2 * node_idx + 2;
+
+        self.nodes[node_idx].value = self.monoid.combine(&self.nodes[left_child].value, &self.nodes[right_child].value);
+        self.mirror_node(node_idx);
+    }
+
+    /// Recompute all internal nodes from the leaf nodes in one bottom-up pass
+    fn update_internal_nodes(&mut self) {
+        // Children are always stored after their parent, so walking the array
+        // backwards recomputes every child before the parent that reads it
+        for node_idx in (0..self.nodes.len() / 2).rev() {
+            self.update_node(node_idx);
+        }
+        // Copying every node once is cheaper than mirroring them one by one
+        self.refresh_growth();
+    }
+
+    /// Transform every leaf node in the segment tree, then recompute the internal nodes once
+    /// f: Function mapping the index and current value of a leaf to its new value
+    /// Returns `Ok(())` if every new value was accepted by the monoid, otherwise an error and the tree is
+    /// left unchanged
+    pub fn apply_all<F: FnMut(usize, M::Item) -> M::Item>(&mut self, mut f: F) -> Result<(), SegmentTreeError> {
+        let leaves: Vec<M::Item> = self.leaves.iter()
+            .enumerate()
+            .map(|(index, item)| f(index, item.clone()))
+            .collect();
+        SegmentTree::validate_values(&leaves, &self.monoid)?;
+
+        self.leaves = leaves;
+        self.reserve_leaves();
+        self.write_leaves();
+        Ok(())
+    }
+
+    /// Overwrite all leaf nodes from a slice of the same length
+    /// values: Vector of new leaf values
+    /// Returns `Ok(())` if the values were assigned, otherwise an error and the tree is left unchanged
+    pub fn assign_from(&mut self, values: &[M::Item]) -> Result<(), SegmentTreeError> {
+        if values.len() != self.leaf_len {
+            return Err(SegmentTreeError::LengthMismatch { expected: self.leaf_len, actual: values.len() });
+        }
+        SegmentTree::validate_values(values, &self.monoid)?;
+
+        self.leaves.clone_from_slice(values);
+        self.write_leaves();
+        Ok(())
+    }
+
+    /// Replace the contents of the segment tree with a slice of any length
+    /// The existing nodes are reused when they have room for the new values
+    /// values: Vector of new leaf values
+    /// Returns `Ok(())` if the values were assigned, otherwise an error and the tree is left unchanged
+    pub fn rebuild_from(&mut self, values: &[M::Item]) -> Result<(), SegmentTreeError> {
+        SegmentTree::validate_values(values, &self.monoid)?;
+
+        if values.len() > self.leaf_capacity {
+            let leaf_capacity = values.len().next_power_of_two();
+            let (nodes, leaf_indices) = SegmentTree::build_nodes(values, leaf_capacity, &self.monoid);
+            self.nodes = nodes;
+            self.leaf_indices = leaf_indices;
+            self.leaf_capacity = leaf_capacity;
+            self.growth = None;
+            self.leaves = values.to_vec();
+            self.leaf_len = values.len();
+            self.reserve_leaves();
+            return Ok(());
+        }
+
+        // Clear leaves that are past the new length
+        for index in values.len()..self.leaf_len {
+            self.nodes[self.leaf_indices[index]].value = self.monoid.identity();
+        }
+        self.leaves = values.to_vec();
+        self.leaf_len = values.len();
+        self.reserve_leaves();
+
+        self.write_leaves();
+        Ok(())
+    }
+
+    /// Recompute every node from the current leaf values in one O(n) bottom-up pass
+    /// Use after writes that skip recomputation, to bring all aggregates up to date at once
+    pub fn rebuild(&mut self) {
+        self.write_leaves();
+    }
+
+    /// Write the leaf items into the first leaf nodes and recompute the internal nodes
+    fn write_leaves(&mut self) {
+        for (index, item) in self.leaves.iter().enumerate() {
+            self.nodes[self.leaf_indices[index]].value = self.monoid.lift(item);
+        }
+
+        self.update_internal_nodes();
+    }
+
+    /// Update leaf nodes in the segment tree in a batch
+    /// updates: Vector of tuples with index and new value
+    pub fn batch_update(&mut self, updates: &[(usize, M::Item)]) -> Vec<Result<(), SegmentTreeError>> {
+        updates.iter()
+            .map(|(index, value)| self.update(*index, value.clone()))
+            .collect()
+    }
+
+    /// Update leaf nodes in the segment tree, recomputing each affected ancestor only once
+    /// All updates are validated before any are applied; later updates to the same index win
+    /// updates: Vector of tuples with index and new value
+    /// Returns `Ok(())` if all updates were applied, otherwise an error and the tree is left unchanged
+    pub fn update_batch(&mut self, updates: &[(usize, M::Item)]) -> Result<(), SegmentTreeError> {
+        for (index, value) in updates {
+            self.validate_index(*index)?;
+            self.monoid.validate(value, *index)?;
+        }
+
+        let mut dirty: Vec<usize> = Vec::with_capacity(updates.len());
+        for (index, value) in updates {
+            let leaf_node = self.leaf_indices[*index];
+            self.leaves[*index] = value.clone();
+            self.nodes[leaf_node].value = self.monoid.lift(value);
+            self.mirror_node(leaf_node);
+            dirty.push(leaf_node);
+        }
+
+        self.update_leaf_paths(dirty);
+        Ok(())
+    }
+
+    /// Recompute the ancestors of many leaf nodes, recomputing each shared ancestor only once
+    /// dirty: Indices of the leaf nodes that changed, in any order and possibly repeated
+    fn update_leaf_paths(&mut self, mut dirty: Vec<usize>) {
+        // Walking every path costs O(k log n), a full pass costs O(n)
+        let height = self.leaf_capacity.trailing_zeros() as usize;
+        if dirty.len().saturating_mul(height) >= self.nodes.len() {
+            self.update_internal_nodes();
+            return;
+        }
+
+        // All leaves are on the same level, so the dirty nodes move up one level at a time
+        // and paths that meet are merged before their shared ancestors are recomputed
+        while !dirty.is_empty() && dirty[0] > 0 {
+            for node_idx in dirty.iter_mut() {
+                *node_idx = (*node_idx - 1) / 2;
+            }
+            dirty.sort_unstable();
+            dirty.dedup();
+
+            for &parent in &dirty {
+                self.update_node(parent);
+            }
+        }
+    }
+
+    /// Get mutable access to a leaf node in the segment tree
+    /// The ancestors of the leaf are recomputed once, when the returned guard is dropped or committed.
+    /// A value rejected by the monoid is rolled back to the value the leaf had when the guard was created.
+    /// index: Index of the leaf node
+    /// Returns a `LeafGuard` for the leaf node or an error
+    pub fn leaf_mut(&mut self, index: usize) -> Result<LeafGuard<'_, M>, SegmentTreeError> {
+        self.validate_index(index)?;
+
+        Ok(LeafGuard {
+            original: Some(self.leaves[index].clone()),
+            tree: self,
+            index,
+        })
+    }
+
+    /// Get an entry for a leaf node in the segment tree
+    /// The ancestors of the leaf are recomputed when the entry is dropped, only if it was written to
+    /// index: Index of the leaf node
+    /// Returns an `Entry` for the leaf node or an error
+    pub fn entry(&mut self, index: usize) -> Result<Entry<'_, M>, SegmentTreeError> {
+        self.validate_index(index)?;
+
+        Ok(Entry {
+            tree: self,
+            index,
+            modified: false,
+        })
+    }
+
+    /// Get the leaf values of the segment tree
+    /// Together with the monoid, the leaves are all that is needed to rebuild the tree with `with_monoid`
+    /// Returns a slice of leaf values, in input order
+    pub fn leaves(&self) -> &[M::Item] {
+        &self.leaves
+    }
+
+    /// Iterate over the current leaf values of a range, validating the range once
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns an iterator over the leaf values from `start` to `end`, inclusive, or an error
+    pub fn range_values(&self, start: usize, end: usize) -> Result<std::slice::Iter<'_, M::Item>, SegmentTreeError> {
+        self.validate_public_query(start, end)?;
+
+        Ok(self.leaves[start..=end].iter())
+    }
+
+    /// Consume the segment tree and return the leaf values
+    /// Returns a vector of leaf values, in input order
+    pub fn into_vec(self) -> Vec<M::Item> {
+        self.leaves
+    }
+
+    /// Append a value to the end of the segment tree
+    /// Every push is O(log n) worst case: once the tree is over half full, each push also fills a few
+    /// nodes and copies a few leaves into the storage for double the capacity, so no push rebuilds the
+    /// tree or reallocates its leaf vector.
+    /// value: Value of the new leaf node
+    /// Returns `Ok(())` if the value was appended, otherwise an error
+    pub fn push(&mut self, value: M::Item) -> Result<(), SegmentTreeError> {
+        if self.leaf_len >= MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: self.leaf_len + 1, max: MAX_INPUT_SIZE });
+        }
+        self.monoid.validate(&value, self.leaf_len)?;
+
+        if self.leaf_len == self.leaf_capacity {
+            self.finish_growth();
+        }
+
+        self.leaves.push(value);
+        self.leaf_len += 1;
+
+        self.commit_leaf(self.leaf_len - 1);
+        self.step_growth();
+        Ok(())
+    }
+
+    /// Remove the last value from the segment tree
+    /// Returns the removed value, or `None` if the segment tree is empty
+    pub fn pop(&mut self) -> Option<M::Item> {
+        let value = self.leaves.pop()?;
+        self.leaf_len -= 1;
+
+        let leaf_node = self.leaf_indices[self.leaf_len];
+        self.nodes[leaf_node].value = self.monoid.identity();
+        self.mirror_node(leaf_node);
+
+        self.update_ancestors(leaf_node);
+        Some(value)
+    }
+
+    /// Shorten the segment tree, keeping the first `new_len` values
+    /// Has no effect if `new_len` is greater than or equal to the current length
+    /// new_len: New number of leaves
+    pub fn truncate(&mut self, new_len: usize) {
+        while self.leaf_len > new_len {
+            self.pop();
+        }
+    }
+
+    /// Resize the segment tree in place, like `Vec::resize`
+    /// Growing appends copies of `fill` and recomputes the nodes in one O(n) pass, shrinking truncates
+    /// new_len: New number of leaves
+    /// fill: Value of the appended leaves, validated once at the first new index
+    /// Returns `Ok(())` if the tree was resized, otherwise an error and the tree is left unchanged
+    pub fn resize(&mut self, new_len: usize, fill: M::Item) -> Result<(), SegmentTreeError> {
+        if new_len <= self.leaf_len {
+            self.truncate(new_len);
+            return Ok(());
+        }
+        if new_len > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: new_len, max: MAX_INPUT_SIZE });
+        }
+        self.monoid.validate(&fill, self.leaf_len)?;
+
+        self.reserve(new_len - self.leaf_len)?;
+        let value = self.monoid.lift(&fill);
+        for index in self.leaf_len..new_len {
+            self.nodes[self.leaf_indices[index]].value = value.clone();
+        }
+        self.leaves.resize(new_len, fill);
+        self.leaf_len = new_len;
+
+        self.update_internal_nodes();
+        Ok(())
+    }
+
+    /// Number of values in the segment tree
+    pub fn len(&self) -> usize {
+        self.leaf_len
+    }
+
+    /// Returns `true` if the segment tree holds no values
+    pub fn is_empty(&self) -> bool {
+        self.leaf_len == 0
+    }
+
+    /// Number of values the segment tree can hold before `push` has to rebuild the nodes
+    pub fn capacity(&self) -> usize {
+        self.leaf_capacity
+    }
+
+    /// Reserve room for at least `additional` more values, rebuilding the nodes at most once
+    /// additional: Number of values to reserve room for beyond the current length
+    /// Returns `Ok(())` if the room was reserved, otherwise an error if the total is too large
+    pub fn reserve(&mut self, additional: usize) -> Result<(), SegmentTreeError> {
+        let required = self.leaf_len.saturating_add(additional);
+        if required > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: required, max: MAX_INPUT_SIZE });
+        }
+
+        if required > self.leaf_capacity {
+            self.grow(required.next_power_of_two());
+        }
+        self.leaves.reserve(additional);
+        Ok(())
+    }
+
+    /// Rebuild the segment tree with a new capacity in one O(n) pass, dropping any growth in progress
+    /// leaf_capacity: New leaf capacity, a power of two
+    fn grow(&mut self, leaf_capacity: usize) {
+        let (nodes, leaf_indices) = SegmentTree::build_nodes(&self.leaves, leaf_capacity, &self.monoid);
+
+        self.nodes = nodes;
+        self.leaf_indices = leaf_indices;
+        self.leaf_capacity = leaf_capacity;
+        self.growth = None;
+        self.reserve_leaves();
+    }
+}
+
+/// Create a sum segment tree with checked overflow, taking ownership of the values
+impl TryFrom<Vec<isize>> for SegmentTree {
+    type Error = SegmentTreeError;
+
+    fn try_from(input: Vec<isize>) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::from_leaves(input, Sum::default())
+    }
+}
+
+/// Create a sum segment tree with checked overflow
+impl TryFrom<&[isize]> for SegmentTree {
+    type Error = SegmentTreeError;
+
+    fn try_from(input: &[isize]) -> Result<SegmentTree, SegmentTreeError> {
+        SegmentTree::new(input)
+    }
+}
+
+/// Consume the segment tree and return the leaf values, same as `into_vec`
+impl<M: Monoid> From<SegmentTree<M>> for Vec<M::Item> {
+    fn from(segment_tree: SegmentTree<M>) -> Vec<M::Item> {
+        segment_tree.into_vec()
+    }
+}
+
+/// Extend the segment tree with values from an iterator
+/// Panics if the segment tree would exceed the maximum number of leaves, or if the monoid rejects a value,
+/// such as a non-finite value of `CompensatedSum`. Values before the panicking one are kept.
+/// Call `push` for each value to get these as errors instead.
+impl<M: Monoid> Extend<M::Item> for SegmentTree<M> {
+    fn extend<I: IntoIterator<Item = M::Item>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value).expect("Value could not be appended to the segment tree");
+        }
+    }
+}
+
+impl<M: Monoid + Clone> Clone for SegmentTree<M> {
+    fn clone(&self) -> Self {
+        SegmentTree {
+            nodes: self.nodes.clone(),
+            leaves: self.leaves.clone(),
+            leaf_len: self.leaf_len,
+            leaf_capacity: self.leaf_capacity,
+            leaf_indices: self.leaf_indices.clone(),
+            monoid: self.monoid.clone(),
+            growth: self.growth.clone(),
+        }
+    }
+}
+
+/// Summarize the segment tree, without listing its nodes
+impl<M: Monoid + fmt::Debug> fmt::Debug for SegmentTree<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegmentTree")
+            .field("len", &self.leaf_len)
+            .field("capacity", &self.leaf_capacity)
+            .field("nodes", &self.nodes.len())
+            .field("monoid", &self.monoid)
+            .finish()
+    }
+}
+
+/// Create an empty segment tree with the default monoid
+impl<M: Monoid + Default> Default for SegmentTree<M> {
+    fn default() -> Self {
+        SegmentTree::empty(M::default())
+    }
+}
+
+/// Compare segment trees by their leaf values and monoid, regardless of their capacity or internal layout
+impl<M: Monoid + PartialEq> PartialEq for SegmentTree<M>
+where
+    M::Item: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.monoid == other.monoid && self.leaves == other.leaves
+    }
+}
+
+impl<M: Monoid + Eq> Eq for SegmentTree<M> where M::Item: Eq {}
+
+/// Hash segment trees by their leaf values, consistent with `PartialEq`
+impl<M: Monoid> Hash for SegmentTree<M>
+where
+    M::Item: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.leaves.hash(state);
+    }
+}
+
+/// Leaf Guard
+/// Mutable access to a leaf value, returned by `SegmentTree::leaf_mut`
+/// tree: Segment tree holding the leaf
+/// index: Index of the leaf
+/// original: Value of the leaf when the guard was created, restored if the new value is rejected
+/// The ancestors of the leaf are recomputed when the guard is dropped
+pub struct LeafGuard<'a, M: Monoid = Sum> {
+    tree: &'a mut SegmentTree<M>,
+    index: usize,
+    original: Option<M::Item>,
+}
+
+impl<M: Monoid> LeafGuard<'_, M> {
+    /// Validate the new value of the leaf and recompute its ancestors
+    /// Dropping the guard does the same, but cannot report a rejected value
+    /// Returns `Ok(())` if the value was accepted, otherwise an error and the previous value is restored
+    pub fn commit(mut self) -> Result<(), SegmentTreeError> {
+        self.finish()
+    }
+
+    /// Restore the original value if the monoid rejects the new one, then recompute the ancestors
+    fn finish(&mut self) -> Result<(), SegmentTreeError> {
+        let Some(original) = self.original.take() else {
+            return Ok(());
+        };
+
+        let result = self.tree.monoid.validate(&self.tree.leaves[self.index], self.index);
+        if result.is_err() {
+            self.tree.leaves[self.index] = original;
+        }
+        self.tree.commit_leaf(self.index);
+        result
+    }
+}
+
+impl<M: Monoid> Deref for LeafGuard<'_, M> {
+    type Target = M::Item;
+
+    fn deref(&self) -> &M::Item {
+        &self.tree.leaves[self.index]
+    }
+}
+
+impl<M: Monoid> DerefMut for LeafGuard<'_, M> {
+    fn deref_mut(&mut self) -> &mut M::Item {
+        &mut self.tree.leaves[self.index]
+    }
+}
+
+impl<M: Monoid> Drop for LeafGuard<'_, M> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Entry
+/// View of a single leaf value, returned by `SegmentTree::entry`
+/// tree: Segment tree holding the leaf
+/// index: Index of the leaf
+/// modified: Whether the leaf was written to through the entry
+/// When the entry is dropped, the ancestors of a leaf that was written to are recomputed.
+/// A leaf that was only read leaves the tree untouched.
+pub struct Entry<'a, M: Monoid = Sum> {
+    tree: &'a mut SegmentTree<M>,
+    index: usize,
+    modified: bool,
+}
+
+impl<M: Monoid> Entry<'_, M> {
+    /// Get the current value of the leaf
+    pub fn get(&self) -> &M::Item {
+        &self.tree.leaves[self.index]
+    }
+
+    /// Set the value of the leaf
+    /// value: New value for the leaf
+    /// Returns the previous value, or an error if the monoid rejects the new value and the leaf is left unchanged
+    pub fn set(&mut self, value: M::Item) -> Result<M::Item, SegmentTreeError> {
+        self.tree.monoid.validate(&value, self.index)?;
+
+        self.modified = true;
+        Ok(std::mem::replace(&mut self.tree.leaves[self.index], value))
+    }
+
+    /// Modify the value of the leaf
+    /// f: Function applied to a copy of the current value
+    /// Returns the entry, so calls can be chained, or an error if the monoid rejects the modified value
+    /// and the leaf is left unchanged
+    pub fn and_modify<F: FnOnce(&mut M::Item)>(mut self, f: F) -> Result<Self, SegmentTreeError> {
+        let mut value = self.tree.leaves[self.index].clone();
+        f(&mut value);
+        self.set(value)?;
+        Ok(self)
+    }
+}
+
+impl<M: Monoid> Drop for Entry<'_, M> {
+    fn drop(&mut self) {
+        if self.modified {
+            self.tree.commit_leaf(self.index);
+        }
     }
 }
 
@@ -302,17 +1196,20 @@ mod tests {
 
     #[test]
     fn test_input_value_bounds() {
-        let input = vec![MAX_VALUE + 1];
-        let result = SegmentTree::new(&input);
-        assert!(result.is_err());
+        // The full isize range is accepted
+        let input = vec![isize::MAX, isize::MAX, isize::MIN, isize::MIN];
+        let segment_tree = SegmentTree::new(&input).unwrap();
+        assert_eq!(segment_tree.query(0, 0), Ok(isize::MAX));
+        assert_eq!(segment_tree.query(1, 2), Ok(-1));
+        assert_eq!(segment_tree.query(0, 3), Ok(-2));
 
-        let input = vec![MIN_VALUE - 1];
-        let result = SegmentTree::new(&input);
-        assert!(result.is_err());
+        // Intermediate sums may exceed isize, only the result has to fit
+        assert_eq!(segment_tree.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(segment_tree.query(2, 3), Err(SegmentTreeError::Overflow { start: 2, end: 3 }));
     }
 
     #[test]
-    fn test_basic_query() -> Result<(), &'static str> {
+    fn test_basic_query() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let segment_tree = SegmentTree::new(&input)?;
 
@@ -324,6 +1221,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_overflow_policy() -> Result<(), SegmentTreeError> {
+        let input = vec![isize::MAX, 1, isize::MIN, -1];
+
+        let checked = SegmentTree::new(&input)?;
+        assert_eq!(checked.overflow_policy(), OverflowPolicy::Checked);
+        assert_eq!(checked.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(checked.query(0, 3)?, -1);
+
+        let mut saturating = SegmentTree::with_overflow_policy(&input, OverflowPolicy::Saturating)?;
+        assert_eq!(saturating.query(0, 1)?, isize::MAX);
+        assert_eq!(saturating.query(2, 3)?, isize::MIN);
+        saturating.add(0, 10)?;
+        assert_eq!(saturating.query(0, 0)?, isize::MAX);
+
+        let mut wrapping = SegmentTree::with_overflow_policy(&input, OverflowPolicy::Wrapping)?;
+        assert_eq!(wrapping.query(0, 1)?, isize::MIN);
+        assert_eq!(wrapping.query(2, 3)?, isize::MAX);
+        wrapping.add(0, 1)?;
+        assert_eq!(wrapping.query(0, 0)?, isize::MIN);
+
+        let mut checked = checked;
+        assert_eq!(checked.add(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 0 }));
+
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_query_range() {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
@@ -340,7 +1264,7 @@ mod tests {
     }
 
     #[test]
-    fn test_basic_update() -> Result<(), &'static str> {
+    fn test_basic_update() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let mut segment_tree = SegmentTree::new(&input)?;
 
@@ -366,16 +1290,26 @@ mod tests {
 
         // Index out of bounds
         assert!(segment_tree.update(8, 1).is_err());
+    }
 
-        // Value exceeds maximum
-        assert!(segment_tree.update(0, MAX_VALUE + 1).is_err());
+    #[test]
+    fn test_error_variants() {
+        let input = vec![1, 2, 3, 4];
+        let mut segment_tree = SegmentTree::new(&input).unwrap();
 
-        // Value below minimum
-        assert!(segment_tree.update(0, MIN_VALUE - 1).is_err());
+        assert_eq!(SegmentTree::new(&[]).err(), Some(SegmentTreeError::EmptyInput));
+        assert_eq!(segment_tree.query(3, 1), Err(SegmentTreeError::InvalidRange { start: 3, end: 1, len: 4 }));
+        assert_eq!(segment_tree.query(1, 4), Err(SegmentTreeError::IndexOutOfBounds { index: 4, len: 4 }));
+        assert_eq!(segment_tree.update(9, 1), Err(SegmentTreeError::IndexOutOfBounds { index: 9, len: 4 }));
+        assert_eq!(segment_tree.add(1, isize::MAX), Err(SegmentTreeError::Overflow { start: 1, end: 1 }));
+        assert_eq!(segment_tree.assign_from(&[1]), Err(SegmentTreeError::LengthMismatch { expected: 4, actual: 1 }));
+
+        segment_tree.truncate(0);
+        assert_eq!(segment_tree.query(0, 0), Err(SegmentTreeError::IndexOutOfBounds { index: 0, len: 0 }));
     }
 
     #[test]
-    fn test_consecutive_updates() -> Result<(), &'static str> {
+    fn test_consecutive_updates() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let mut segment_tree = SegmentTree::new(&input)?;
 
@@ -393,19 +1327,19 @@ mod tests {
     }
 
     #[test]
-    fn test_query_single_element() -> Result<(), &'static str> {
+    fn test_query_single_element() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let segment_tree = SegmentTree::new(&input)?;
 
-        for i in 0..input.len() {
-            assert_eq!(segment_tree.query(i, i)?, input[i]);
+        for (i, &value) in input.iter().enumerate() {
+            assert_eq!(segment_tree.query(i, i)?, value);
         }
 
         Ok(())
     }
 
     #[test]
-    fn test_update_and_query_boundaries() -> Result<(), &'static str> {
+    fn test_update_and_query_boundaries() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let mut segment_tree = SegmentTree::new(&input)?;
 
@@ -422,7 +1356,446 @@ mod tests {
     }
 
     #[test]
-    fn test_send_sync() -> Result<(), &'static str> {
+    fn test_into_vec() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.update(4, 50)?;
+        assert_eq!(segment_tree.leaves(), &[1, 2, 3, 4, 50, 6, 7, 8, 9]);
+
+        // A checkpoint of the leaves and the monoid rebuilds an identical tree
+        let restored = SegmentTree::with_monoid(segment_tree.leaves(), *segment_tree.monoid())?;
+        assert_eq!(restored.query(0, 8)?, segment_tree.query(0, 8)?);
+
+        assert_eq!(segment_tree.into_vec(), vec![1, 2, 3, 4, 50, 6, 7, 8, 9]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversions() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::try_from(vec![1, 2, 3])?;
+        assert_eq!(segment_tree.query(0, 2)?, 6);
+
+        let segment_tree: SegmentTree = [4, 5].as_slice().try_into()?;
+        assert_eq!(segment_tree.query(0, 1)?, 9);
+
+        assert!(matches!(SegmentTree::try_from(Vec::new()), Err(SegmentTreeError::EmptyInput)));
+
+        let values: Vec<isize> = segment_tree.into();
+        assert_eq!(values, vec![4, 5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        // Fills the padding leaf, then grows past the initial capacity
+        segment_tree.push(4)?;
+        segment_tree.push(5)?;
+        assert_eq!(segment_tree.query(0, 4)?, 15);
+        assert_eq!(segment_tree.query(3, 4)?, 9);
+        assert!(segment_tree.query(0, 5).is_err());
+
+        assert_eq!(segment_tree.into_vec(), vec![1, 2, 3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_capacity_and_reserve() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::with_capacity(100)?;
+        assert!(segment_tree.is_empty());
+        assert_eq!(segment_tree.capacity(), 128);
+
+        let nodes = segment_tree.nodes.as_ptr();
+        segment_tree.extend(0..100);
+        // Pushing up to the capacity reuses the preallocated nodes
+        assert_eq!(segment_tree.nodes.as_ptr(), nodes);
+        assert_eq!(segment_tree.query(0, 99)?, 4950);
+
+        segment_tree.reserve(29)?;
+        assert_eq!(segment_tree.capacity(), 256);
+        assert_eq!(segment_tree.query(10, 19)?, 145);
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        assert_eq!(
+            segment_tree.reserve(usize::MAX),
+            Err(SegmentTreeError::InputTooLarge { len: usize::MAX, max: MAX_INPUT_SIZE })
+        );
+        assert!(SegmentTree::with_capacity(usize::MAX).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1])?;
+        segment_tree.extend(2..=100);
+
+        assert_eq!(segment_tree.query(0, 99)?, 5050);
+        assert_eq!(segment_tree.query(49, 50)?, 101);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pop_and_truncate() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        assert_eq!(segment_tree.pop(), Some(8));
+        assert_eq!(segment_tree.len(), 7);
+        assert_eq!(segment_tree.query(0, 6)?, 28);
+        assert!(segment_tree.query(0, 7).is_err());
+
+        segment_tree.truncate(3);
+        assert_eq!(segment_tree.query(0, 2)?, 6);
+        assert!(segment_tree.query(3, 3).is_err());
+
+        // Truncating to a longer length is a no-op
+        segment_tree.truncate(10);
+        assert_eq!(segment_tree.len(), 3);
+
+        segment_tree.truncate(0);
+        assert!(segment_tree.is_empty());
+        assert_eq!(segment_tree.pop(), None);
+        assert!(segment_tree.query(0, 0).is_err());
+
+        // An emptied tree can grow again
+        segment_tree.push(42)?;
+        assert_eq!(segment_tree.query(0, 0)?, 42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3])?;
+
+        segment_tree.resize(6, 10)?;
+        assert_eq!(segment_tree.leaves(), &[1, 2, 3, 10, 10, 10]);
+        assert_eq!(segment_tree.query(0, 5)?, 36);
+        assert_eq!(segment_tree.capacity(), 8);
+
+        segment_tree.resize(2, 0)?;
+        assert_eq!(segment_tree.leaves(), &[1, 2]);
+        assert_eq!(segment_tree.query(0, 1)?, 3);
+
+        segment_tree.resize(20, -1)?;
+        assert_eq!(segment_tree.query(0, 19)?, -15);
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        assert!(segment_tree.resize(usize::MAX, 0).is_err());
+        assert_eq!(segment_tree.len(), 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_sparse() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::from_sparse(10, &[(2, 5), (9, -1), (2, 7)])?;
+        assert_eq!(segment_tree.leaves(), &[0, 0, 7, 0, 0, 0, 0, 0, 0, -1]);
+        assert_eq!(segment_tree.query(0, 9)?, 6);
+
+        let values = HashMap::from([(0, 3), (4, 4)]);
+        let segment_tree = SegmentTree::from_sparse_map(5, &values)?;
+        assert_eq!(segment_tree.leaves(), &[3, 0, 0, 0, 4]);
+
+        assert_eq!(SegmentTree::from_sparse(3, &[(3, 1)]), Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }));
+        assert_eq!(SegmentTree::from_sparse(0, &[]), Err(SegmentTreeError::EmptyInput));
+        assert!(SegmentTree::from_sparse(usize::MAX, &[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.add(2, 10)?;
+        segment_tree.add(5, -6)?;
+        assert_eq!(segment_tree.query(2, 2)?, 13);
+        assert_eq!(segment_tree.query(5, 5)?, 0);
+        assert_eq!(segment_tree.query(0, 7)?, 40);
+
+        // Index out of bounds
+        assert!(segment_tree.add(8, 1).is_err());
+
+        // Result overflows, the leaf is left unchanged
+        assert!(segment_tree.add(0, isize::MAX).is_err());
+        assert_eq!(segment_tree.query(0, 0)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_with() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.update_with(1, |old| old * 10)?;
+        segment_tree.update_with(6, |old| old.min(3))?;
+        assert_eq!(segment_tree.query(1, 1)?, 20);
+        assert_eq!(segment_tree.query(6, 6)?, 3);
+        assert_eq!(segment_tree.query(0, 7)?, 50);
+
+        // Index out of bounds
+        assert!(segment_tree.update_with(8, |old| old).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_all() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.apply_all(|index, value| value * 2 + index as isize)?;
+        assert_eq!(segment_tree.query(0, 6)?, 77);
+        assert_eq!(segment_tree.query(3, 3)?, 11);
+
+        // A rejected value leaves every leaf unchanged
+        let mut segment_tree = SegmentTree::with_monoid(&[Decimal::new(1, 2), Decimal::new(2, 2)], DecimalSum::new(2))?;
+        assert_eq!(
+            segment_tree.apply_all(|index, _value| if index == 1 { Decimal::new(1, 3) } else { Decimal::new(5, 2) }),
+            Err(SegmentTreeError::InvalidItem { index: 1 })
+        );
+        assert_eq!(segment_tree.leaves(), &[Decimal::new(1, 2), Decimal::new(2, 2)]);
+        assert_eq!(segment_tree.total()?, Decimal::new(3, 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assign_from() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.assign_from(&[8, 7, 6, 5, 4, 3, 2, 1])?;
+        assert_eq!(segment_tree.query(0, 1)?, 15);
+        assert_eq!(segment_tree.query(0, 7)?, 36);
+
+        // Length mismatch leaves the tree unchanged
+        assert!(segment_tree.assign_from(&[1, 2, 3]).is_err());
+        assert_eq!(segment_tree.query(0, 0)?, 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebuild_from() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        // Shrink within the existing nodes
+        segment_tree.rebuild_from(&[10, 20, 30])?;
+        assert_eq!(segment_tree.len(), 3);
+        assert_eq!(segment_tree.query(0, 2)?, 60);
+        assert!(segment_tree.query(0, 3).is_err());
+
+        // Grow past the existing nodes
+        let values: Vec<isize> = (1..=20).collect();
+        segment_tree.rebuild_from(&values)?;
+        assert_eq!(segment_tree.query(0, 19)?, 210);
+
+        // Growing after a shrink does not resurrect old values
+        segment_tree.rebuild_from(&[1])?;
+        segment_tree.push(2)?;
+        assert_eq!(segment_tree.query(0, 1)?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebuild() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+
+        // Stale aggregates, as left behind by a write that skipped recomputation
+        segment_tree.leaves[1] = 20;
+        segment_tree.nodes[0].value = 0;
+        assert!(segment_tree.verify().is_err());
+
+        segment_tree.rebuild();
+        assert_eq!(segment_tree.verify(), Ok(()));
+        assert_eq!(segment_tree.query(0, 4)?, 33);
+        assert_eq!(segment_tree.query(1, 1)?, 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_batch() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (1..=16).collect();
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        // Small batch, ancestors are recomputed along merged paths
+        segment_tree.update_batch(&[(0, 10), (1, 20), (15, 0), (0, 100)])?;
+        assert_eq!(segment_tree.query(0, 1)?, 120);
+        assert_eq!(segment_tree.query(0, 15)?, 237);
+
+        // Large batch, falls back to a full recompute
+        let updates: Vec<(usize, isize)> = (0..16).map(|i| (i, 1)).collect();
+        segment_tree.update_batch(&updates)?;
+        assert_eq!(segment_tree.query(0, 15)?, 16);
+        assert_eq!(segment_tree.query(3, 9)?, 7);
+
+        // One invalid update rejects the whole batch
+        assert!(segment_tree.update_batch(&[(0, 5), (16, 5)]).is_err());
+        assert_eq!(segment_tree.query(0, 0)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_leaf_mut() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        *segment_tree.leaf_mut(3)? += 5;
+        assert_eq!(segment_tree.query(3, 3)?, 9);
+        assert_eq!(segment_tree.query(0, 7)?, 41);
+
+        {
+            let mut leaf = segment_tree.leaf_mut(0)?;
+            *leaf *= 10;
+            *leaf -= 3;
+            assert_eq!(*leaf, 7);
+        }
+        assert_eq!(segment_tree.query(0, 7)?, 47);
+
+        *segment_tree.leaf_mut(7)? = isize::MAX;
+        assert_eq!(segment_tree.query(7, 7)?, isize::MAX);
+
+        assert!(segment_tree.leaf_mut(8).is_err());
+
+        // Values rejected by the monoid are rolled back
+        let mut segment_tree = SegmentTree::with_monoid(&[Decimal::new(1, 2), Decimal::new(2, 2)], DecimalSum::new(2))?;
+        *segment_tree.leaf_mut(0)? = Decimal::new(1, 3);
+        assert_eq!(segment_tree.leaves()[0], Decimal::new(1, 2));
+        assert_eq!(segment_tree.total()?, Decimal::new(3, 2));
+        let mut leaf = segment_tree.leaf_mut(1)?;
+        *leaf = Decimal::new(i64::MAX, 0);
+        assert_eq!(leaf.commit(), Err(SegmentTreeError::InvalidItem { index: 1 }));
+        let mut leaf = segment_tree.leaf_mut(1)?;
+        *leaf = Decimal::new(7, 1);
+        leaf.commit()?;
+        assert_eq!(segment_tree.total()?, Decimal::new(71, 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.entry(2)?.and_modify(|value| *value += 10)?;
+        assert_eq!(segment_tree.query(2, 2)?, 13);
+        assert_eq!(segment_tree.query(0, 7)?, 46);
+
+        // Conditional update composed from get and set
+        let mut entry = segment_tree.entry(5)?;
+        if entry.get() % 2 == 0 {
+            assert_eq!(entry.set(0)?, 6);
+        }
+        drop(entry);
+        assert_eq!(segment_tree.query(0, 7)?, 40);
+
+        // Chained modifications
+        segment_tree.entry(0)?
+            .and_modify(|value| *value *= 3)?
+            .and_modify(|value| *value -= 1)?;
+        assert_eq!(segment_tree.query(0, 0)?, 2);
+
+        assert_eq!(*segment_tree.entry(7)?.get(), 8);
+        assert!(segment_tree.entry(8).is_err());
+
+        // Values rejected by the monoid are not written
+        let mut segment_tree = SegmentTree::with_monoid(&[Decimal::new(1, 2)], DecimalSum::new(2))?;
+        let mut entry = segment_tree.entry(0)?;
+        assert_eq!(entry.set(Decimal::new(1, 3)), Err(SegmentTreeError::InvalidItem { index: 0 }));
+        assert!(entry.and_modify(|value| value.scale = 5).is_err());
+        assert_eq!(segment_tree.total()?, Decimal::new(1, 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut segment_tree = SegmentTree::new(&input)?;
+
+        segment_tree.swap(0, 7)?;
+        assert_eq!(segment_tree.query(0, 0)?, 8);
+        assert_eq!(segment_tree.query(7, 7)?, 1);
+        assert_eq!(segment_tree.query(0, 3)?, 17);
+        assert_eq!(segment_tree.query(0, 7)?, 36);
+
+        // Swapping a leaf with itself is a no-op
+        segment_tree.swap(3, 3)?;
+        assert_eq!(segment_tree.query(3, 3)?, 4);
+
+        // Either index out of bounds
+        assert!(segment_tree.swap(0, 8).is_err());
+        assert!(segment_tree.swap(8, 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eq_and_hash() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+
+        // Same leaves in a tree with a larger capacity
+        let mut grown = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        grown.truncate(3);
+        assert!(segment_tree == grown);
+
+        let mut pushed = SegmentTree::new(&[1])?;
+        pushed.extend([2, 3]);
+        assert!(segment_tree == pushed);
+
+        assert!(segment_tree != SegmentTree::new(&[1, 2, 4])?);
+        assert!(segment_tree != SegmentTree::with_overflow_policy(&[1, 2, 3], OverflowPolicy::Wrapping)?);
+
+        let mut memo = std::collections::HashMap::new();
+        memo.insert(segment_tree, "first");
+        assert_eq!(memo.get(&grown), Some(&"first"));
+        assert_eq!(memo.get(&SegmentTree::new(&[3, 2, 1])?), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_debug_default() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        let mut clone = segment_tree.clone();
+        clone.update(0, 10)?;
+        assert_eq!(segment_tree.query(0, 2)?, 6);
+        assert_eq!(clone.query(0, 2)?, 15);
+
+        assert_eq!(
+            format!("{:?}", segment_tree),
+            "SegmentTree { len: 3, capacity: 4, nodes: 7, monoid: Sum { overflow_policy: Checked } }"
+        );
+
+        let mut empty = SegmentTree::<Sum>::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.query(0, 0), Err(SegmentTreeError::IndexOutOfBounds { index: 0, len: 0 }));
+        empty.extend([4, 5, 6]);
+        assert_eq!(empty.query(0, 2)?, 15);
+        assert_eq!(empty.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_sync() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let segment_tree = Arc::new(SegmentTree::new(&input)?);
 
@@ -447,7 +1820,7 @@ mod tests {
     }
 
     #[test]
-    fn test_send_sync_mutex() -> Result<(), &'static str> {
+    fn test_send_sync_mutex() -> Result<(), SegmentTreeError> {
         use std::sync::Mutex;
 
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
@@ -476,7 +1849,8 @@ mod tests {
     }
 
     #[test]
-    fn test_parallel_queries() -> Result<(), &'static str> {
+    #[cfg(feature = "rayon")]
+    fn test_parallel_queries() -> Result<(), SegmentTreeError> {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let tree = Arc::new(SegmentTree::new(&input)?);
 
@@ -498,6 +1872,119 @@ mod tests {
     }
 
     #[test]
+    fn test_total() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        assert_eq!(segment_tree.total()?, 15);
+        segment_tree.update(4, -5)?;
+        assert_eq!(segment_tree.total()?, segment_tree.query(0, 4)?);
+
+        let max = FnMonoid::new(isize::MIN, |left: &isize, right: &isize| *left.max(right));
+        assert_eq!(SegmentTree::with_monoid(&[3, 9, 2], max)?.total()?, 9);
+
+        let overflow = SegmentTree::new(&[isize::MAX, 1])?;
+        assert_eq!(overflow.total(), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+
+        segment_tree.truncate(0);
+        assert_eq!(segment_tree.total(), Err(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_batch() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let segment_tree = SegmentTree::new(&input)?;
+
+        // Results come back in the order of the ranges
+        let results = segment_tree.query_batch(&[(4, 7), (0, 7), (2, 5), (0, 3)])?;
+        assert_eq!(results, vec![26, 36, 18, 10]);
+
+        assert_eq!(segment_tree.query_batch(&[])?, Vec::<isize>::new());
+
+        // One invalid range rejects the whole batch
+        assert!(segment_tree.query_batch(&[(0, 7), (5, 2)]).is_err());
+        assert!(segment_tree.query_batch(&[(0, 8)]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_batch_bottom_up() -> Result<(), SegmentTreeError> {
+        // Leaves that do not fill the capacity, so some ranges end beside padding
+        let input: Vec<isize> = (0..1000).map(|i| (i * 37 % 101) - 50).collect();
+        let mut segment_tree = SegmentTree::new(&input)?;
+        segment_tree.update(511, 400)?;
+
+        let queries: Vec<(usize, usize)> = (0..500).map(|i| {
+            let (a, b) = (i * 7919 % 1000, i * 104_729 % 1000);
+            (a.min(b), a.max(b))
+        }).collect();
+        let one_by_one = queries.iter().map(|&(start, end)| segment_tree.query(start, end)).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(segment_tree.query_batch(&queries)?, one_by_one);
+        assert_eq!(segment_tree.query_batch(&[(999, 999), (0, 999)])?, vec![input[999], segment_tree.total()?]);
+
+        // The bottom-up fold keeps the order of non-commutative values
+        let concat = FnMonoid::new(String::new(), |left: &String, right: &String| format!("{}{}", left, right));
+        let letters: Vec<String> = "abcdefghijk".chars().map(String::from).collect();
+        let tree = SegmentTree::with_monoid(&letters, concat)?;
+        assert_eq!(tree.query_batch(&[(1, 9), (0, 10), (3, 3)])?, vec!["bcdefghij", "abcdefghijk", "d"]);
+
+        Ok(())
+    }
+
+    /// Concatenation of the leaves, which is not commutative and counts its in-place combines
+    struct Concat {
+        combined_in_place: std::cell::Cell<usize>,
+    }
+
+    impl Monoid for Concat {
+        type Item = char;
+        type Value = String;
+        type Output = String;
+
+        fn identity(&self) -> String {
+            String::new()
+        }
+
+        fn lift(&self, item: &char) -> String {
+            item.to_string()
+        }
+
+        fn combine(&self, left: &String, right: &String) -> String {
+            format!("{left}{right}")
+        }
+
+        fn combine_into(&self, left: &mut String, right: &String) {
+            self.combined_in_place.set(self.combined_in_place.get() + 1);
+            left.push_str(right);
+        }
+
+        fn finish(&self, value: String, _start: usize, _end: usize) -> Result<String, SegmentTreeError> {
+            Ok(value)
+        }
+    }
+
+    #[test]
+    fn test_query_combines_in_place() -> Result<(), SegmentTreeError> {
+        let input: Vec<char> = "abcdefg".chars().collect();
+        let concat = Concat {
+            combined_in_place: std::cell::Cell::new(0),
+        };
+        let segment_tree = SegmentTree::with_monoid(&input, concat)?;
+
+        // [1, 5] is covered by the nodes for [1, 1], [2, 3] and [4, 5]
+        assert_eq!(segment_tree.query(1, 5)?, "bcdef");
+        assert_eq!(segment_tree.monoid().combined_in_place.get(), 2);
+
+        assert_eq!(segment_tree.query(0, 6)?, "abcdefg");
+        assert_eq!(segment_tree.query(3, 3)?, "d");
+        assert_eq!(segment_tree.query_batch(&[(2, 4), (0, 1)])?, vec!["cde", "ab"]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
     fn test_thread_safety() {
         let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let tree = Arc::new(SegmentTree::new(&input).unwrap());
@@ -523,6 +2010,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rayon")]
     fn test_performance_comparison() {
         let input = vec![1; 1_000_000];
         let tree = Arc::new(SegmentTree::new(&input).unwrap());
@@ -548,4 +2036,32 @@ mod tests {
         println!("Sequential time: {:?}", sequential_time); // Sequential time: 1.463208ms
         println!("Parallel time: {:?}", parallel_time); // Parallel time: 378.75µs
     }
+
+    #[test]
+    fn test_node_size() -> Result<(), SegmentTreeError> {
+        // Children are computed from the node index, so a node only holds its value and range
+        assert_eq!(std::mem::size_of::<Node<i128>>(), 32);
+
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        assert!(segment_tree.nodes[3].is_leaf());
+        assert!(!segment_tree.nodes[2].is_leaf());
+        assert_eq!(segment_tree.children(2), Some((5, 6)));
+        assert_eq!(segment_tree.children(5), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_values() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[5, 1, 4, 2, 3])?;
+        segment_tree.update(2, 40)?;
+
+        assert_eq!(segment_tree.range_values(1, 3)?.copied().collect::<Vec<_>>(), vec![1, 40, 2]);
+        assert_eq!(segment_tree.range_values(4, 4)?.len(), 1);
+        assert_eq!(segment_tree.range_values(0, 4)?.sum::<isize>(), segment_tree.query(0, 4)?);
+        assert!(segment_tree.range_values(3, 2).is_err());
+        assert!(segment_tree.range_values(0, 5).is_err());
+
+        Ok(())
+    }
 }
@@ -1,37 +1,1008 @@
+//! Segment Tree CLI
+//!
+//! Reads one command per line, from a script file or stdin, and writes one response per command to stdout.
+//! Commands are either plain words (`query 2 5`) or JSON arrays (`["query", 2, 5]`).
+//! Run `help` for the list of commands.
+//!
+//! `bench` runs a seeded random workload instead and reports throughput and latency percentiles.
+//! With `--batch N` it instead answers `N` random ranges one `query` at a time and with one `query_batch`,
+//! and reports the time of each.
+//! `verify` runs seeded random operations on the tree and on `NaiveRangeModel`, and reports the first
+//! divergence with a minimized script that reproduces it.
+//! `serve` reads JSON array commands and writes JSON responses, one per line, on stdin and stdout or
+//! on every connection to a unix socket, so other processes can use the tree as a sidecar.
+//!
+//! Usage: `segment_tree [--format plain|json] [FILE]`
+//! or `segment_tree bench [--size N] [--ops N] [--queries PERCENT] [--seed S] [--backend tree|naive] [--batch N]`
+//! or `segment_tree verify [--size N] [--ops N] [--runs N] [--seed S]`
+//! or `segment_tree serve [--socket PATH]`
 
-use segment_tree::SegmentTree;
+use segment_tree::{NaiveRangeModel, Op, RangeModel, SegmentTree, SegmentTreeError, Sum};
+use std::fmt;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-fn main() -> Result<(), &'static str> {
-    let input: Vec<isize> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
-    let mut segmemt_tree = SegmentTree::new(&input)?;
-    // Input values are now the leaves in the segment tree
-    drop(input);
+const HELP: &str = "\
+build <values...>   Build a new tree from the values
+query <start> <end> Sum of the values from start to end, inclusive
+update <index> <v>  Set the value at index
+add <index> <delta> Add a delta to the value at index
+push <value>        Append a value
+pop                 Remove the last value
+len                 Number of values
+print               Print the tree
+help                Show this help
+quit                Exit";
 
-    // Original input values / 36
-    let result = segmemt_tree.query(0, 7)?;
-    println!("Query result: {}", result);
+const USAGE: &str = "\
+Usage: segment_tree [--format plain|json] [FILE]
+       segment_tree bench [--size N] [--ops N] [--queries PERCENT] [--seed S] [--backend tree|naive] [--batch N]
+       segment_tree verify [--size N] [--ops N] [--runs N] [--seed S]
+       segment_tree serve [--socket PATH]";
 
-    // Original input values / 14
-    let result = segmemt_tree.query(1, 4)?;
-    println!("Query result: {}", result);
+/// Output Format
+/// How responses are written
+/// Plain: The bare response, or `error: <message>`
+/// Json: One JSON object per line, `{"result": ...}` or `{"error": "<message>"}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Format {
+    #[default]
+    Plain,
+    Json,
+}
+
+/// Response
+/// Successful result of a command
+/// Ok: The command was applied
+/// Built: A tree was built, with its number of values
+/// Number: A numeric result
+/// Text: A text result
+/// Nothing: There was no result, such as popping an empty tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Response {
+    Ok,
+    Built(usize),
+    Number(i128),
+    Text(String),
+    Nothing,
+}
+
+/// Command
+/// Parsed REPL command
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Build(Vec<isize>),
+    Query(usize, usize),
+    Update(usize, isize),
+    Add(usize, isize),
+    Push(isize),
+    Pop,
+    Len,
+    Print,
+    Help,
+    Quit,
+}
+
+/// CLI Error
+/// Errors reported for a command, after which the REPL keeps going
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CliError {
+    /// The command could not be parsed
+    Parse(String),
+    /// No tree has been built yet
+    NoTree,
+    /// The tree rejected the command
+    Tree(SegmentTreeError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Parse(message) => write!(f, "{}", message),
+            CliError::NoTree => write!(f, "No tree, use `build <values...>` first"),
+            CliError::Tree(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<SegmentTreeError> for CliError {
+    fn from(error: SegmentTreeError) -> CliError {
+        CliError::Tree(error)
+    }
+}
+
+/// Parse a single argument
+/// name: Name of the argument, for error messages
+/// value: Text of the argument, if present
+fn parse_arg<T: FromStr>(name: &str, value: Option<&str>) -> Result<T, CliError> {
+    let value = value.ok_or_else(|| CliError::Parse(format!("Missing argument <{}>", name)))?;
+    value.parse().map_err(|_| CliError::Parse(format!("Invalid <{}>: {}", name, value)))
+}
+
+/// Split a JSON array of strings and integers into command words
+/// line: Line of input holding a JSON array, such as `["query", 2, 5]`
+/// Returns the words, or an error
+fn json_words(line: &str) -> Result<Vec<String>, CliError> {
+    let invalid = || CliError::Parse(format!("Invalid JSON command: {}", line));
+    let inner = line.trim()
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(invalid)?;
 
-    // [1, 2, 3, 10, 5, 6, 7, 8, 9] / 20
-    // Change idx 3 to 10
-    segmemt_tree.update(3, 10)?;
-    let result = segmemt_tree.query(1, 4)?;
-    println!("Query result: {}", result);
+    let mut words = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut word = String::new();
+        match chars.next() {
+            None if words.is_empty() => break,
+            Some('"') => loop {
+                match chars.next().ok_or_else(invalid)? {
+                    '"' => break,
+                    '\\' => word.push(chars.next().ok_or_else(invalid)?),
+                    c => word.push(c),
+                }
+            },
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                word.push(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                    word.push(c);
+                }
+            }
+            _ => return Err(invalid()),
+        }
+        if word.is_empty() || word.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        words.push(word);
 
-    // [1, 2, 3, 10, 5, 6, 7, 8, 1] / 43
-    // Change idx 8 to 1
-    segmemt_tree.update(8, 1)?;
-    let result = segmemt_tree.query(0, 8)?;
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some(',') => {}
+            Some(_) => return Err(invalid()),
+        }
+    }
+
+    Ok(words)
+}
 
-    println!("Query result: {}", result);
+/// Parse a line of input
+/// line: Line of input, plain words or a JSON array
+/// Returns the command, `None` for blank lines and `#` comments, or an error
+fn parse_command(line: &str) -> Result<Option<Command>, CliError> {
+    if line.trim_start().starts_with('[') {
+        return parse_words(json_words(line)?.iter().map(String::as_str));
+    }
+
+    parse_words(line.split_whitespace())
+}
+
+/// Parse the words of a command
+/// words: Command name followed by its arguments
+/// Returns the command, `None` for no words and `#` comments, or an error
+fn parse_words<'a, I: Iterator<Item = &'a str>>(mut words: I) -> Result<Option<Command>, CliError> {
+    let Some(name) = words.next() else {
+        return Ok(None);
+    };
+    if name.starts_with('#') {
+        return Ok(None);
+    }
+
+    let command = match name {
+        "build" => Command::Build(
+            words.by_ref()
+                .map(|word| parse_arg("values", Some(word)))
+                .collect::<Result<_, _>>()?,
+        ),
+        "query" => Command::Query(parse_arg("start", words.next())?, parse_arg("end", words.next())?),
+        "update" => Command::Update(parse_arg("index", words.next())?, parse_arg("value", words.next())?),
+        "add" => Command::Add(parse_arg("index", words.next())?, parse_arg("delta", words.next())?),
+        "push" => Command::Push(parse_arg("value", words.next())?),
+        "pop" => Command::Pop,
+        "len" => Command::Len,
+        "print" => Command::Print,
+        "help" => Command::Help,
+        "quit" | "exit" => Command::Quit,
+        _ => return Err(CliError::Parse(format!("Unknown command `{}`, try `help`", name))),
+    };
+
+    if let Some(extra) = words.next() {
+        return Err(CliError::Parse(format!("Unexpected argument: {}", extra)));
+    }
+
+    Ok(Some(command))
+}
+
+/// Session
+/// State of the REPL between commands
+/// tree: Segment tree built by the last `build` command
+#[derive(Debug, Default)]
+struct Session {
+    tree: Option<SegmentTree>,
+}
+
+impl Session {
+    /// Get the current tree
+    /// Returns the tree, or an error if none has been built
+    fn tree(&mut self) -> Result<&mut SegmentTree, CliError> {
+        self.tree.as_mut().ok_or(CliError::NoTree)
+    }
+
+    /// Run a command
+    /// command: Command to run
+    /// Returns the response, or an error
+    fn execute(&mut self, command: Command) -> Result<Response, CliError> {
+        let response = match command {
+            Command::Build(values) => {
+                let tree = SegmentTree::new(&values)?;
+                let len = tree.len();
+                self.tree = Some(tree);
+                Response::Built(len)
+            }
+            Command::Query(start, end) => Response::Number(self.tree()?.query(start, end)? as i128),
+            Command::Update(index, value) => {
+                self.tree()?.update(index, value)?;
+                Response::Ok
+            }
+            Command::Add(index, delta) => {
+                self.tree()?.add(index, delta)?;
+                Response::Ok
+            }
+            Command::Push(value) => {
+                self.tree()?.push(value)?;
+                Response::Ok
+            }
+            Command::Pop => match self.tree()?.pop() {
+                Some(value) => Response::Number(value as i128),
+                None => Response::Nothing,
+            },
+            Command::Len => Response::Number(self.tree()?.len() as i128),
+            Command::Print => Response::Text(self.tree()?.to_string().trim_end().to_string()),
+            Command::Help => Response::Text(HELP.to_string()),
+            Command::Quit => Response::Nothing,
+        };
+
+        Ok(response)
+    }
+}
+
+/// Quote and escape a string as a JSON string
+/// text: String to quote
+fn json_string(text: &str) -> String {
+    let mut json = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
 
-    // [1, 2, 3, 10, 5, 6, 7, 8, 1] / 1
-    let result = segmemt_tree.query(8, 8)?;
+/// Format the outcome of a command
+/// result: Response or error of the command
+/// format: Output format
+fn format_result(result: &Result<Response, CliError>, format: Format) -> String {
+    match (format, result) {
+        (Format::Plain, Ok(Response::Ok)) => "ok".to_string(),
+        (Format::Plain, Ok(Response::Built(len))) => format!("ok {}", len),
+        (Format::Plain, Ok(Response::Number(number))) => number.to_string(),
+        (Format::Plain, Ok(Response::Text(text))) => text.clone(),
+        (Format::Plain, Ok(Response::Nothing)) => "empty".to_string(),
+        (Format::Plain, Err(error)) => format!("error: {}", error),
+        (Format::Json, Ok(Response::Ok | Response::Nothing)) => r#"{"result":null}"#.to_string(),
+        (Format::Json, Ok(Response::Built(len))) => format!(r#"{{"result":{}}}"#, len),
+        (Format::Json, Ok(Response::Number(number))) => format!(r#"{{"result":{}}}"#, number),
+        (Format::Json, Ok(Response::Text(text))) => format!(r#"{{"result":{}}}"#, json_string(text)),
+        (Format::Json, Err(error)) => format!(r#"{{"error":{}}}"#, json_string(&error.to_string())),
+    }
+}
+
+/// Run commands until the input ends or a `quit` command
+/// input: Source of command lines
+/// output: Destination of the responses
+/// format: Output format
+/// prompt: Whether to print a prompt before each command
+fn run<R: BufRead, W: Write>(input: R, mut output: W, format: Format, prompt: bool) -> io::Result<()> {
+    let mut session = Session::default();
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let result = match parse_command(&line?) {
+            Ok(None) => continue,
+            Ok(Some(Command::Quit)) => break,
+            Ok(Some(command)) => session.execute(command),
+            Err(error) => Err(error),
+        };
+
+        writeln!(output, "{}", format_result(&result, format))?;
+    }
+
+    output.flush()
+}
+
+/// Parse the command line arguments
+/// args: Arguments after the program name
+/// Returns the output format and the script path, if any, or a usage error
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<(Format, Option<String>), String> {
+    let mut format = Format::default();
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--format") {
+            Some("") => args.next(),
+            Some(value) => value.strip_prefix('=').map(str::to_string),
+            None => None,
+        };
+        match (arg.starts_with("--format"), value.as_deref()) {
+            (true, Some("plain")) => format = Format::Plain,
+            (true, Some("json")) => format = Format::Json,
+            (true, _) => return Err("--format must be `plain` or `json`".to_string()),
+            (false, _) if arg.starts_with("--") || path.is_some() => return Err(format!("Unexpected argument: {}", arg)),
+            (false, _) => path = Some(arg),
+        }
+    }
+
+    Ok((format, path))
+}
+
+/// Backend
+/// Range structure measured by `bench`
+/// Tree: `SegmentTree`
+/// Naive: `NaiveRangeModel`, with O(n) queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Backend {
+    #[default]
+    Tree,
+    Naive,
+}
+
+/// Bench Config
+/// Workload run by `bench`
+/// size: Number of leaves
+/// ops: Number of operations
+/// query_percent: Share of the operations that are queries, the rest are updates
+/// seed: Seed of the random generator, so runs are repeatable
+/// backend: Range structure to measure
+/// batch: Number of ranges answered by `query` and by `query_batch` instead of running `ops`, 0 to run `ops`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BenchConfig {
+    size: usize,
+    ops: usize,
+    query_percent: usize,
+    seed: u64,
+    backend: Backend,
+    batch: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        BenchConfig { size: 1 << 20, ops: 1_000_000, query_percent: 50, seed: 1, backend: Backend::Tree, batch: 0 }
+    }
+}
+
+/// Random number generator (SplitMix64), seeded so workloads are repeatable
+/// state: State advanced on every draw
+#[derive(Debug, Clone)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Draw the next number
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Draw a number below a bound
+    /// bound: Number of possible values, at least 1
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Draw a leaf value
+    fn value(&mut self) -> isize {
+        self.below(2001) as isize - 1000
+    }
+}
+
+/// Split subcommand arguments into options and their values
+/// args: Arguments such as `--size 100` or `--size=100`
+/// Returns the name and value of each option, or a usage error
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Vec<(String, String)>, String> {
+    let mut options = Vec::new();
+    while let Some(arg) = args.next() {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg, args.next()),
+        };
+        let value = value.ok_or_else(|| format!("Missing value for {}", name))?;
+        options.push((name, value));
+    }
+
+    Ok(options)
+}
+
+/// Parse the arguments of the `bench` subcommand
+/// args: Arguments after `bench`
+/// Returns the workload, or a usage error
+fn parse_bench_args<I: Iterator<Item = String>>(args: I) -> Result<BenchConfig, String> {
+    let mut config = BenchConfig::default();
+    for (name, value) in parse_options(args)? {
+        let invalid = || format!("Invalid value for {}: {}", name, value);
+        match name.as_str() {
+            "--size" => config.size = value.parse().map_err(|_| invalid())?,
+            "--ops" => config.ops = value.parse().map_err(|_| invalid())?,
+            "--queries" => config.query_percent = value.parse().ok().filter(|&percent| percent <= 100).ok_or_else(invalid)?,
+            "--seed" => config.seed = value.parse().map_err(|_| invalid())?,
+            "--backend" => config.backend = match value.as_str() {
+                "tree" => Backend::Tree,
+                "naive" => Backend::Naive,
+                _ => return Err(invalid()),
+            },
+            "--batch" => config.batch = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("Unexpected argument: {}", name)),
+        }
+    }
+    if config.size == 0 {
+        return Err("--size must be at least 1".to_string());
+    }
+    if config.batch > 0 && config.backend != Backend::Tree {
+        return Err("--batch needs --backend tree".to_string());
+    }
+
+    Ok(config)
+}
+
+/// Format the latency percentiles of one kind of operation
+/// name: Kind of operation
+/// latencies: Latency of every operation of that kind
+fn format_latencies(name: &str, latencies: &mut [Duration]) -> String {
+    if latencies.is_empty() {
+        return format!("{}: none", name);
+    }
+
+    latencies.sort_unstable();
+    let total: Duration = latencies.iter().sum();
+    let percentile = |percent: usize| latencies[(latencies.len() - 1) * percent / 100];
+    format!(
+        "{}: {} in {:?}, {:.0} ops/s, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        name,
+        latencies.len(),
+        total,
+        latencies.len() as f64 / total.as_secs_f64().max(f64::MIN_POSITIVE),
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        percentile(100),
+    )
+}
 
-    println!("Query result: {}", result);
+/// Run a random workload on a range structure and report the results
+/// model: Range structure holding the leaves
+/// config: Workload to run
+/// rng: Random generator, already used to draw the leaves
+/// output: Destination of the report
+fn run_bench<R: RangeModel<Sum>, W: Write>(model: &mut R, config: &BenchConfig, rng: &mut Rng, mut output: W) -> io::Result<()> {
+    let mut queries = Vec::new();
+    let mut updates = Vec::new();
+    let start = Instant::now();
+    for _ in 0..config.ops {
+        if rng.below(100) < config.query_percent {
+            let (a, b) = (rng.below(config.size), rng.below(config.size));
+            let op_start = Instant::now();
+            let _ = black_box(model.query(a.min(b), a.max(b)));
+            queries.push(op_start.elapsed());
+        } else {
+            let (index, value) = (rng.below(config.size), rng.value());
+            let op_start = Instant::now();
+            let _ = black_box(model.update(index, value));
+            updates.push(op_start.elapsed());
+        }
+    }
+    let elapsed = start.elapsed();
+
+    writeln!(output, "{}", format_latencies("queries", &mut queries))?;
+    writeln!(output, "{}", format_latencies("updates", &mut updates))?;
+    writeln!(output, "total: {} ops in {:?}, {:.0} ops/s", config.ops, elapsed, config.ops as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE))
+}
+
+/// Answer one batch of random ranges one query at a time and with `query_batch`, and report both
+/// tree: Tree holding the leaves
+/// config: Workload to run
+/// rng: Random generator, already used to draw the leaves
+/// output: Destination of the report
+fn run_batch_bench<W: Write>(tree: &SegmentTree, config: &BenchConfig, rng: &mut Rng, mut output: W) -> io::Result<()> {
+    let queries: Vec<(usize, usize)> = (0..config.batch)
+        .map(|_| {
+            let (a, b) = (rng.below(config.size), rng.below(config.size));
+            (a.min(b), a.max(b))
+        })
+        .collect();
+    let invalid = |error: SegmentTreeError| io::Error::new(io::ErrorKind::InvalidInput, error);
+
+    let start = Instant::now();
+    let one_by_one = black_box(queries.iter().map(|&(a, b)| tree.query(a, b)).collect::<Result<Vec<_>, _>>().map_err(invalid)?);
+    let query_time = start.elapsed();
+    let start = Instant::now();
+    let batch = black_box(tree.query_batch(&queries).map_err(invalid)?);
+    let batch_time = start.elapsed();
+    if one_by_one != batch {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "query_batch disagrees with query"));
+    }
+
+    let rate = |elapsed: Duration| config.batch as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+    writeln!(output, "query: {} in {:?}, {:.0} ops/s", config.batch, query_time, rate(query_time))?;
+    writeln!(output, "query_batch: {} in {:?}, {:.0} ops/s", config.batch, batch_time, rate(batch_time))?;
+    writeln!(output, "speedup: {:.2}x", query_time.as_secs_f64() / batch_time.as_secs_f64().max(f64::MIN_POSITIVE))
+}
+
+/// Build the backend of a workload, then run it
+/// config: Workload to run
+/// output: Destination of the report
+fn bench<W: Write>(config: &BenchConfig, mut output: W) -> io::Result<()> {
+    let mut rng = Rng { state: config.seed };
+    let values: Vec<isize> = (0..config.size).map(|_| rng.value()).collect();
+    if config.batch > 0 {
+        writeln!(output, "backend {:?}, size {}, batch {}, seed {}", config.backend, config.size, config.batch, config.seed)?;
+    } else {
+        writeln!(output, "backend {:?}, size {}, ops {}, queries {}%, seed {}", config.backend, config.size, config.ops, config.query_percent, config.seed)?;
+    }
+
+    let start = Instant::now();
+    let invalid = |error: SegmentTreeError| io::Error::new(io::ErrorKind::InvalidInput, error);
+    match config.backend {
+        Backend::Tree => {
+            let mut tree = SegmentTree::new(&values).map_err(invalid)?;
+            writeln!(output, "build: {:?}", start.elapsed())?;
+            if config.batch > 0 {
+                run_batch_bench(&tree, config, &mut rng, &mut output)?;
+            } else {
+                run_bench(&mut tree, config, &mut rng, &mut output)?;
+            }
+        }
+        Backend::Naive => {
+            let mut model = NaiveRangeModel::new(&values, Sum::default()).map_err(invalid)?;
+            writeln!(output, "build: {:?}", start.elapsed())?;
+            run_bench(&mut model, config, &mut rng, &mut output)?;
+        }
+    }
+
+    output.flush()
+}
+
+/// Verify Config
+/// Differential test run by `verify`
+/// size: Number of leaves each run starts with
+/// ops: Number of operations per run
+/// runs: Number of runs, each with new random leaves and operations
+/// seed: Seed of the random generator, so runs are repeatable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VerifyConfig {
+    size: usize,
+    ops: usize,
+    runs: usize,
+    seed: u64,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> VerifyConfig {
+        VerifyConfig { size: 64, ops: 1000, runs: 100, seed: 1 }
+    }
+}
+
+/// Parse the arguments of the `verify` subcommand
+/// args: Arguments after `verify`
+/// Returns the test to run, or a usage error
+fn parse_verify_args<I: Iterator<Item = String>>(args: I) -> Result<VerifyConfig, String> {
+    let mut config = VerifyConfig::default();
+    for (name, value) in parse_options(args)? {
+        let invalid = || format!("Invalid value for {}: {}", name, value);
+        match name.as_str() {
+            "--size" => config.size = value.parse().map_err(|_| invalid())?,
+            "--ops" => config.ops = value.parse().map_err(|_| invalid())?,
+            "--runs" => config.runs = value.parse().map_err(|_| invalid())?,
+            "--seed" => config.seed = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("Unexpected argument: {}", name)),
+        }
+    }
+    if config.size == 0 {
+        return Err("--size must be at least 1".to_string());
+    }
+
+    Ok(config)
+}
+
+/// Run operations on a tree and on the naive model side by side
+/// values: Leaves both start with
+/// ops: Operations to run
+/// Returns the index of the first operation after which they differ and how, or `None` if they agree
+fn find_divergence(values: &[isize], ops: &[Op<isize>]) -> Option<(usize, String)> {
+    let mut tree = SegmentTree::new(values).ok()?;
+    let mut model = NaiveRangeModel::new(values, Sum::default()).ok()?;
+    for (index, op) in ops.iter().enumerate() {
+        let (tree_result, model_result) = (op.apply(&mut tree), op.apply(&mut model));
+        if tree_result != model_result {
+            return Some((index, format!("tree returned {:?}, model returned {:?}", tree_result, model_result)));
+        }
+        if tree.leaves() != model.leaves() {
+            return Some((index, "leaves differ".to_string()));
+        }
+    }
+
+    None
+}
+
+/// Shrink a diverging operation sequence by removing every operation it still diverges without
+/// ops: Diverging operations
+/// diverges: Whether a sequence still diverges
+/// Returns the remaining operations
+fn minimize<F: Fn(&[Op<isize>]) -> bool>(mut ops: Vec<Op<isize>>, diverges: F) -> Vec<Op<isize>> {
+    for index in (0..ops.len()).rev() {
+        let removed = ops.remove(index);
+        if !diverges(&ops) {
+            ops.insert(index, removed);
+        }
+    }
+    ops
+}
+
+/// Format an operation as a REPL command
+/// op: Operation
+fn op_command(op: &Op<isize>) -> String {
+    match op {
+        Op::Query { start, end } => format!("query {} {}", start, end),
+        Op::Update { index, item } => format!("update {} {}", index, item),
+        Op::Push(item) => format!("push {}", item),
+        Op::Pop => "pop".to_string(),
+    }
+}
+
+/// Run random operation sequences on the tree and the naive model and report the first divergence
+/// config: Test to run
+/// output: Destination of the report
+/// Returns `true` if a divergence was found, or an error writing the report
+fn verify<W: Write>(config: &VerifyConfig, mut output: W) -> io::Result<bool> {
+    let mut rng = Rng { state: config.seed };
+    for run in 0..config.runs {
+        let values: Vec<isize> = (0..config.size).map(|_| rng.value()).collect();
+        let bytes: Vec<u8> = (0..6 * config.ops).map(|_| rng.next() as u8).collect();
+        let mut ops = Op::sequence_from_bytes(&bytes, config.size);
+        ops.truncate(config.ops);
+
+        let Some((index, _)) = find_divergence(&values, &ops) else {
+            continue;
+        };
+        ops.truncate(index + 1);
+        let ops = minimize(ops, |ops| find_divergence(&values, ops).is_some());
+        let (index, message) = find_divergence(&values, &ops).unwrap_or((ops.len(), String::new()));
+
+        writeln!(output, "divergence in run {} at operation {}: {}", run, index, message)?;
+        writeln!(output, "reproducer:")?;
+        writeln!(output, "build {}", values.iter().map(isize::to_string).collect::<Vec<_>>().join(" "))?;
+        for op in &ops {
+            writeln!(output, "{}", op_command(op))?;
+        }
+        output.flush()?;
+        return Ok(true);
+    }
+
+    writeln!(output, "ok: {} runs of {} operations on {} leaves, no divergence", config.runs, config.ops, config.size)?;
+    output.flush()?;
+    Ok(false)
+}
+
+/// Parse the arguments of the `serve` subcommand
+/// args: Arguments after `serve`
+/// Returns the socket path, `None` to serve stdin, or a usage error
+fn parse_serve_args<I: Iterator<Item = String>>(args: I) -> Result<Option<String>, String> {
+    let mut socket = None;
+    for (name, value) in parse_options(args)? {
+        match name.as_str() {
+            "--socket" => socket = Some(value),
+            _ => return Err(format!("Unexpected argument: {}", name)),
+        }
+    }
+
+    Ok(socket)
+}
+
+/// Serve JSON commands on a unix socket until the process is stopped
+/// Every connection runs on its own thread with its own session, and responses are JSON lines
+/// path: Path of the socket to create
+#[cfg(unix)]
+fn serve_socket(path: &str) -> io::Result<()> {
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            let reader = BufReader::new(&stream);
+            let _ = run(reader, &stream, Format::Json, false);
+        });
+    }
 
     Ok(())
 }
+
+/// Serving on a unix socket needs a unix platform
+/// path: Path of the socket
+#[cfg(not(unix))]
+fn serve_socket(_path: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--socket needs a unix platform"))
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("serve").is_some() {
+        let socket = match parse_serve_args(args) {
+            Ok(socket) => socket,
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                return ExitCode::from(2);
+            }
+        };
+        let result = match socket {
+            Some(path) => serve_socket(&path),
+            None => run(io::stdin().lock(), io::stdout().lock(), Format::Json, false),
+        };
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if args.next_if_eq("verify").is_some() {
+        let config = match parse_verify_args(args) {
+            Ok(config) => config,
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                return ExitCode::from(2);
+            }
+        };
+        return match verify(&config, io::stdout().lock()) {
+            Ok(false) => ExitCode::SUCCESS,
+            Ok(true) => ExitCode::FAILURE,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if args.next_if_eq("bench").is_some() {
+        let config = match parse_bench_args(args) {
+            Ok(config) => config,
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                return ExitCode::from(2);
+            }
+        };
+        return match bench(&config, io::stdout().lock()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let (format, path) = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let stdout = io::stdout().lock();
+    let result = match path.as_deref() {
+        None | Some("-") => {
+            let stdin = io::stdin();
+            let prompt = stdin.is_terminal() && format == Format::Plain;
+            run(stdin.lock(), stdout, format, prompt)
+        }
+        Some(path) => File::open(path).and_then(|file| run(BufReader::new(file), stdout, format, false)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run lines of input through a new session
+    /// lines: Lines of input
+    /// format: Output format
+    /// Returns the output, one line per response
+    fn run_lines(lines: &[&str], format: Format) -> Vec<String> {
+        let mut output = Vec::new();
+        run(lines.join("\n").as_bytes(), &mut output, format, false).unwrap();
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("build 1 -2 3"), Ok(Some(Command::Build(vec![1, -2, 3]))));
+        assert_eq!(parse_command("  query 2 5  "), Ok(Some(Command::Query(2, 5))));
+        assert_eq!(parse_command(""), Ok(None));
+        assert_eq!(parse_command("# comment"), Ok(None));
+        assert!(parse_command("query 2").is_err());
+        assert!(parse_command("update x 1").is_err());
+        assert!(parse_command("len 1").is_err());
+        assert!(parse_command("frobnicate").is_err());
+
+        assert_eq!(parse_command(r#"["query", 2, 5]"#), Ok(Some(Command::Query(2, 5))));
+        assert_eq!(parse_command(r#" ["build",1,-2] "#), Ok(Some(Command::Build(vec![1, -2]))));
+        assert_eq!(parse_command("[]"), Ok(None));
+        assert!(parse_command(r#"["query", 2 5]"#).is_err());
+        assert!(parse_command(r#"["query", "2 5"]"#).is_err());
+        assert!(parse_command(r#"["query", 2, 5"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]), Ok((Format::Plain, None)));
+        assert_eq!(args(&["--format", "json", "cmds.txt"]), Ok((Format::Json, Some("cmds.txt".to_string()))));
+        assert_eq!(args(&["-", "--format=plain"]), Ok((Format::Plain, Some("-".to_string()))));
+        assert!(args(&["--format", "xml"]).is_err());
+        assert!(args(&["--format"]).is_err());
+        assert!(args(&["a", "b"]).is_err());
+        assert!(args(&["--verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        assert_eq!(
+            run_lines(
+                &["query 0 0", "build 1 2 3 4", "query 1 2", "update 3 10", "add 0 5", "query 0 3", "push 7", "len", "pop", "query 0 4"],
+                Format::Plain
+            ),
+            vec![
+                "error: No tree, use `build <values...>` first",
+                "ok 4",
+                "5",
+                "ok",
+                "ok",
+                "21",
+                "ok",
+                "5",
+                "7",
+                "error: Index 4 is out of bounds for length 4",
+            ]
+        );
+        assert_eq!(run_lines(&["build 1 2", "", "print", "quit", "len"], Format::Plain), vec!["ok 2", "[0, 1] 3", "  [0, 0] 1", "  [1, 1] 2"]);
+    }
+
+    #[test]
+    fn test_json_format() {
+        assert_eq!(
+            run_lines(&[r#"["build", 1, 2, 3]"#, "query 0 2", "update 0 5", "pop", "print", "query 3 3"], Format::Json),
+            vec![
+                r#"{"result":3}"#,
+                r#"{"result":6}"#,
+                r#"{"result":null}"#,
+                r#"{"result":3}"#,
+                r#"{"result":"[0, 1] 7\n  [0, 0] 5\n  [1, 1] 2"}"#,
+                r#"{"error":"Index 3 is out of bounds for length 2"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bench() {
+        let args = |args: &[&str]| parse_bench_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]), Ok(BenchConfig::default()));
+        let config = BenchConfig { size: 100, ops: 500, query_percent: 30, seed: 7, backend: Backend::Naive, batch: 0 };
+        assert_eq!(args(&["--size", "100", "--ops=500", "--queries", "30", "--seed", "7", "--backend=naive"]), Ok(config));
+        assert!(args(&["--queries", "101"]).is_err());
+        assert!(args(&["--size", "0"]).is_err());
+        assert!(args(&["--ops"]).is_err());
+        assert!(args(&["--backend", "fenwick"]).is_err());
+        assert!(args(&["--batch", "10", "--backend", "naive"]).is_err());
+
+        let mut output = Vec::new();
+        bench(&config, &mut output).unwrap();
+        let report = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "backend Naive, size 100, ops 500, queries 30%, seed 7");
+        assert!(lines[1].starts_with("build: "));
+        assert!(lines[2].starts_with("queries: ") && lines[2].contains("p99"));
+        assert!(lines[3].starts_with("updates: "));
+        assert!(lines[4].starts_with("total: 500 ops in "));
+
+        // The same seed draws the same workload
+        let counts = |report: &str| report.lines().skip(2).take(2).map(|line| line.split(" in ").next().unwrap().to_string()).collect::<Vec<_>>();
+        let mut again = Vec::new();
+        bench(&BenchConfig { backend: Backend::Tree, ..config }, &mut again).unwrap();
+        assert_eq!(counts(&report), counts(&String::from_utf8(again).unwrap()));
+
+        // A batch answers the same random ranges both ways
+        let mut batch = Vec::new();
+        bench(&BenchConfig { backend: Backend::Tree, batch: 200, ..config }, &mut batch).unwrap();
+        let report = String::from_utf8(batch).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "backend Tree, size 100, batch 200, seed 7");
+        assert!(lines[2].starts_with("query: 200 in "));
+        assert!(lines[3].starts_with("query_batch: 200 in "));
+        assert!(lines[4].starts_with("speedup: "));
+    }
+
+    #[test]
+    fn test_verify() {
+        let args = |args: &[&str]| parse_verify_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]), Ok(VerifyConfig::default()));
+        let config = VerifyConfig { size: 8, ops: 200, runs: 20, seed: 3 };
+        assert_eq!(args(&["--size=8", "--ops", "200", "--runs", "20", "--seed", "3"]), Ok(config));
+        assert!(args(&["--size", "0"]).is_err());
+        assert!(args(&["--queries", "50"]).is_err());
+
+        let mut output = Vec::new();
+        assert!(!verify(&config, &mut output).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "ok: 20 runs of 200 operations on 8 leaves, no divergence\n");
+
+        // A sequence that "diverges" whenever it pushes 5 and later pops shrinks to those two operations
+        let ops = vec![Op::Query { start: 0, end: 1 }, Op::Push(5), Op::Update { index: 0, item: 2 }, Op::Push(1), Op::Pop, Op::Query { start: 1, end: 1 }];
+        let diverges = |ops: &[Op<isize>]| {
+            ops.iter().position(|op| *op == Op::Push(5)).is_some_and(|push| ops[push..].contains(&Op::Pop))
+        };
+        assert_eq!(minimize(ops, diverges), vec![Op::Push(5), Op::Pop]);
+        assert_eq!(op_command(&Op::Update { index: 3, item: -4 }), "update 3 -4");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_serve_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let args = |args: &[&str]| parse_serve_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]), Ok(None));
+        assert_eq!(args(&["--socket", "/tmp/tree.sock"]), Ok(Some("/tmp/tree.sock".to_string())));
+        assert!(args(&["--port", "80"]).is_err());
+
+        let path = std::env::temp_dir().join(format!("segment_tree_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server_path = path.to_str().unwrap().to_string();
+        std::thread::spawn(move || serve_socket(&server_path));
+
+        let stream = (0..100)
+            .find_map(|_| UnixStream::connect(&path).ok().or_else(|| {
+                std::thread::sleep(Duration::from_millis(10));
+                None
+            }))
+            .unwrap();
+        (&stream).write_all(b"[\"build\", 1, 2, 3]\n[\"query\", 0, 2]\n[\"query\", 0, 3]\n").unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let lines: Vec<String> = BufReader::new(&stream).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, vec![r#"{"result":3}"#, r#"{"result":6}"#, r#"{"error":"Index 3 is out of bounds for length 3"}"#]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
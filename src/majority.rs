@@ -0,0 +1,134 @@
+//! Majority Candidates
+
+use crate::{CountingSegmentTree, Monoid, SegmentTree, SegmentTreeError};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// Majority
+/// Boyer-Moore majority vote over the leaves of a range: if a value fills more than half of the
+/// range, it is the candidate. Any other range still gets a candidate, which has to be verified.
+/// Merging vote counts is only associative up to the candidate of ranges without a majority, which
+/// may then depend on how the range splits into nodes.
+/// Values are `Some((candidate, votes))`, or `None` for an empty range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Majority<T> {
+    item: PhantomData<T>,
+}
+
+impl<T> Majority<T> {
+    /// Create a majority monoid
+    pub fn new() -> Majority<T> {
+        Majority { item: PhantomData }
+    }
+}
+
+impl<T> Default for Majority<T> {
+    fn default() -> Majority<T> {
+        Majority::new()
+    }
+}
+
+impl<T: Clone + Eq> Monoid for Majority<T> {
+    type Item = T;
+    type Value = Option<(T, usize)>;
+    type Output = Option<T>;
+
+    fn identity(&self) -> Option<(T, usize)> {
+        None
+    }
+
+    fn lift(&self, item: &T) -> Option<(T, usize)> {
+        Some((item.clone(), 1))
+    }
+
+    fn combine(&self, left: &Option<(T, usize)>, right: &Option<(T, usize)>) -> Option<(T, usize)> {
+        match (left, right) {
+            (None, value) | (value, None) => value.clone(),
+            (Some((left, left_votes)), Some((right, right_votes))) if left == right => Some((left.clone(), left_votes + right_votes)),
+            // Different candidates cancel out their votes pairwise
+            (Some((left, left_votes)), Some((_, right_votes))) if left_votes >= right_votes => Some((left.clone(), left_votes - right_votes)),
+            (Some((_, left_votes)), Some((right, right_votes))) => Some((right.clone(), right_votes - left_votes)),
+        }
+    }
+
+    fn finish(&self, value: Option<(T, usize)>, _start: usize, _end: usize) -> Result<Option<T>, SegmentTreeError> {
+        Ok(value.map(|(candidate, _)| candidate))
+    }
+}
+
+impl<T: Clone + Eq + Hash> SegmentTree<Majority<T>> {
+    /// Get the majority candidate of a range in O(log n), without verifying it
+    /// The candidate is the majority element whenever the range has one
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the candidate, or an error
+    pub fn majority_candidate(&self, start: usize, end: usize) -> Result<Option<T>, SegmentTreeError> {
+        self.query(start, end)
+    }
+
+    /// Get the value filling more than half of a range, verifying the candidate by scanning the
+    /// current leaves of the range, in O(log n + k) for a range of k leaves
+    /// Wrap the tree in a `CountingSegmentTree` to verify in O(log n) instead
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the majority value, `None` if no value fills more than half of the range, or an error
+    pub fn majority(&self, start: usize, end: usize) -> Result<Option<T>, SegmentTreeError> {
+        let Some(candidate) = self.majority_candidate(start, end)? else {
+            return Ok(None);
+        };
+
+        let count = self.count_eq(start, end, &candidate)?;
+        Ok((2 * count > end - start + 1).then_some(candidate))
+    }
+}
+
+impl<T: Clone + Eq + Hash> CountingSegmentTree<Majority<T>> {
+    /// Get the value filling more than half of a range, verifying the candidate with the value index
+    /// kept by the tree, in O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the majority value, `None` if no value fills more than half of the range, or an error
+    pub fn majority(&self, start: usize, end: usize) -> Result<Option<T>, SegmentTreeError> {
+        let Some(candidate) = self.majority_candidate(start, end)? else {
+            return Ok(None);
+        };
+
+        let count = self.count_eq(start, end, &candidate)?;
+        Ok((2 * count > end - start + 1).then_some(candidate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CountingSegmentTree, Majority, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_majority() -> Result<(), SegmentTreeError> {
+        let input = ["a", "b", "a", "a", "c", "a", "b", "b", "b", "b", "a", "c"];
+        let mut segment_tree = SegmentTree::with_monoid(&input, Majority::new())?;
+        let mut counting = CountingSegmentTree::new(segment_tree.clone());
+        let mut items = input.to_vec();
+
+        // Candidates are verified against the current leaves, so updates are always seen
+        for (index, value) in [(None, ""), (Some(3), "b"), (Some(11), "b"), (Some(0), "c")] {
+            if let Some(index) = index {
+                segment_tree.update(index, value)?;
+                counting.update(index, value)?;
+                items[index] = value;
+            }
+            for start in 0..items.len() {
+                for end in start..items.len() {
+                    let range = &items[start..=end];
+                    let expected = range.iter().find(|&&value| 2 * range.iter().filter(|&&other| other == value).count() > range.len());
+                    assert_eq!(segment_tree.majority(start, end)?, expected.copied());
+                    assert_eq!(counting.majority(start, end)?, expected.copied());
+                    if let Some(&expected) = expected {
+                        assert_eq!(segment_tree.majority_candidate(start, end)?, Some(expected));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
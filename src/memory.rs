@@ -0,0 +1,123 @@
+//! Memory Usage
+
+use crate::{Monoid, Node, SegmentTree};
+use std::mem::size_of;
+
+/// Memory Report
+/// Heap memory held by a segment tree, in bytes. Memory owned by the items or values themselves,
+/// such as the buffers of `String` values, is not included.
+/// nodes: Bytes of the nodes in use, including padding leaves and, for a dynamic tree, every arena node
+/// leaves: Bytes of the leaf items
+/// leaf_indices: Bytes of the table mapping leaves to their nodes
+/// spare: Bytes allocated beyond the entries in use, including storage retired by growth, which `shrink_to_fit` releases
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub leaf_indices: usize,
+    pub spare: usize,
+}
+
+impl MemoryReport {
+    /// Get the total number of bytes allocated
+    pub fn total(&self) -> usize {
+        self.nodes + self.leaves + self.leaf_indices + self.spare
+    }
+}
+
+/// Get the bytes in use and the spare bytes of a vector
+/// vec: Vector to measure
+pub(crate) fn vec_usage<T>(vec: &Vec<T>) -> (usize, usize) {
+    (vec.len() * size_of::<T>(), (vec.capacity() - vec.len()) * size_of::<T>())
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Report the heap memory held by the segment tree
+    /// Returns a `MemoryReport` of the node, leaf and leaf index storage, including storage being
+    /// filled for a larger capacity
+    pub fn memory_usage(&self) -> MemoryReport {
+        let (nodes, spare_nodes) = vec_usage(&self.nodes);
+        let (leaves, spare_leaves) = vec_usage(&self.leaves);
+        let (leaf_indices, spare_indices) = vec_usage(&self.leaf_indices);
+        let mut report = MemoryReport { nodes, leaves, leaf_indices, spare: spare_nodes + spare_leaves + spare_indices };
+
+        // Storage being filled for a larger capacity
+        if let Some(growth) = &self.growth {
+            let (nodes, spare_nodes) = vec_usage(&growth.nodes);
+            let (leaf_indices, spare_indices) = vec_usage(&growth.leaf_indices);
+            let (leaves, spare_leaves) = vec_usage(&growth.leaves);
+            report.nodes += nodes;
+            report.leaf_indices += leaf_indices;
+            report.leaves += leaves;
+            report.spare += spare_nodes + spare_indices + spare_leaves;
+            report.spare += growth.retired_nodes.capacity() * size_of::<Node<M::Value>>();
+            report.spare += growth.retired_indices.capacity() * size_of::<usize>();
+            report.spare += growth.retired_leaves.capacity() * size_of::<M::Item>();
+        }
+        report
+    }
+
+    /// Release memory the segment tree does not need for its current values
+    /// After `pop` or `truncate`, the nodes are rebuilt for the smallest power of two capacity that
+    /// holds the values, in O(n). Spare vector capacity and storage filled for growth are released in every case.
+    pub fn shrink_to_fit(&mut self) {
+        let leaf_capacity = self.leaf_len.max(1).next_power_of_two();
+        if leaf_capacity < self.leaf_capacity {
+            self.grow(leaf_capacity);
+        }
+        self.growth = None;
+
+        self.nodes.shrink_to_fit();
+        self.leaves.shrink_to_fit();
+        self.leaf_indices.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "dynamic")]
+    use crate::DynamicSegmentTree;
+    use crate::{SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_memory_usage() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        let report = segment_tree.memory_usage();
+        assert_eq!(report.leaves, 5 * size_of::<isize>());
+        assert_eq!(report.leaf_indices, 5usize.next_power_of_two() * size_of::<usize>());
+        assert!(report.nodes >= 15 * size_of::<i128>());
+        assert_eq!(report.total(), report.nodes + report.leaves + report.leaf_indices + report.spare);
+
+        // Truncating keeps the capacity until the tree is shrunk
+        segment_tree.truncate(2);
+        assert_eq!(segment_tree.memory_usage().nodes, report.nodes);
+        segment_tree.shrink_to_fit();
+        let shrunk = segment_tree.memory_usage();
+        assert_eq!(shrunk.nodes, report.nodes / 5);
+        assert_eq!(shrunk.spare, 0);
+        assert!(shrunk.total() < report.total());
+        assert_eq!(segment_tree.query(0, 1)?, 3);
+        segment_tree.push(10)?;
+        assert_eq!(segment_tree.query(0, 2)?, 13);
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "dynamic")]
+    fn test_dynamic_memory_usage() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = DynamicSegmentTree::new(1 << 20)?;
+        let empty = segment_tree.memory_usage();
+        segment_tree.update(12345, 1)?;
+        let report = segment_tree.memory_usage();
+        assert!(report.nodes > empty.nodes);
+
+        segment_tree.clear();
+        assert_eq!(segment_tree.memory_usage().total(), report.total());
+        segment_tree.shrink_to_fit();
+        assert_eq!(segment_tree.memory_usage(), empty);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+//! Merge Sort Trees
+//!
+//! Static trees over positions where every node holds the values of its range, sorted, so a
+//! range of positions splits into O(log n) nodes that are each binary searched by value.
+
+use crate::point_set::{build_lists, for_each_node, validate_len};
+use crate::SegmentTreeError;
+
+/// Merge Sort Tree
+/// Static list of `isize` values answering queries by value within a range of positions in O(log² n),
+/// using O(n log n) memory. Every node also keeps prefix sums of its sorted values, in `i128`.
+/// values: Sorted values below each node of a bottom-up tree, with the leaves at `len..2 * len`
+/// prefix_sums: Sums of the first k sorted values of each node, for k from 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeSortTree {
+    values: Vec<Vec<isize>>,
+    prefix_sums: Vec<Vec<i128>>,
+}
+
+impl MergeSortTree {
+    /// Create a merge sort tree in O(n log n)
+    /// input: Value at each position
+    /// Returns a new `MergeSortTree` structure or an error
+    pub fn new(input: &[isize]) -> Result<MergeSortTree, SegmentTreeError> {
+        validate_len(input.len())?;
+
+        let values = build_lists(input.iter().copied());
+        let prefix_sums = values.iter()
+            .map(|list| {
+                std::iter::once(0)
+                    .chain(list.iter().scan(0, |sum, &value| {
+                        *sum += value as i128;
+                        Some(*sum)
+                    }))
+                    .collect()
+            })
+            .collect();
+        Ok(MergeSortTree { values, prefix_sums })
+    }
+
+    /// Get the number of positions
+    pub fn len(&self) -> usize {
+        self.values.len() / 2
+    }
+
+    /// Returns `true` if the tree holds no values, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Sum the values at most `x` among the positions of a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// x: Largest value included in the sum
+    /// Returns the sum, or an error if the range is invalid
+    pub fn sum_le(&self, start: usize, end: usize, x: isize) -> Result<i128, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let mut sum = 0;
+        for_each_node(self.len(), start, end + 1, |node_idx| {
+            sum += self.prefix_sums[node_idx][self.values[node_idx].partition_point(|&value| value <= x)];
+        });
+        Ok(sum)
+    }
+
+    /// Find the largest value at most `x` among the positions of a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// x: Upper bound on the value
+    /// Returns the value, `None` if every value in the range is greater than `x`, or an error if the range is invalid
+    pub fn max_le(&self, start: usize, end: usize, x: isize) -> Result<Option<isize>, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let mut best = None;
+        for_each_node(self.len(), start, end + 1, |node_idx| {
+            let values = &self.values[node_idx];
+            if let Some(count) = values.partition_point(|&value| value <= x).checked_sub(1) {
+                best = best.max(Some(values[count]));
+            }
+        });
+        Ok(best)
+    }
+
+    /// Find the smallest value at least `x` among the positions of a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// x: Lower bound on the value
+    /// Returns the value, `None` if every value in the range is less than `x`, or an error if the range is invalid
+    pub fn min_ge(&self, start: usize, end: usize, x: isize) -> Result<Option<isize>, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let mut best: Option<isize> = None;
+        for_each_node(self.len(), start, end + 1, |node_idx| {
+            let values = &self.values[node_idx];
+            if let Some(&value) = values.get(values.partition_point(|&value| value < x)) {
+                best = Some(best.map_or(value, |best| best.min(value)));
+            }
+        });
+        Ok(best)
+    }
+
+    /// Validate a range of positions
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns `Ok(())` if the range is valid, otherwise an error
+    fn validate_range(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len() });
+        }
+        if end >= self.len() {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len() });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MergeSortTree, SegmentTreeError};
+
+    #[test]
+    fn test_sum_le() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..30).map(|i| (i * 37) % 23 - 11).collect();
+        let tree = MergeSortTree::new(&input)?;
+        assert_eq!(tree.len(), 30);
+
+        for start in 0..30 {
+            for end in start..30 {
+                for x in [-12, -5, 0, 3, 11, isize::MIN, isize::MAX] {
+                    let expected: isize = input[start..=end].iter().filter(|&&value| value <= x).sum();
+                    assert_eq!(tree.sum_le(start, end, x)?, expected as i128);
+                }
+            }
+        }
+
+        let tree = MergeSortTree::new(&[isize::MAX, isize::MAX, 1])?;
+        assert_eq!(tree.sum_le(0, 2, isize::MAX)?, 2 * isize::MAX as i128 + 1);
+        assert_eq!(tree.sum_le(2, 1, 0), Err(SegmentTreeError::InvalidRange { start: 2, end: 1, len: 3 }));
+        assert_eq!(tree.sum_le(0, 3, 0), Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }));
+        assert_eq!(MergeSortTree::new(&[]), Err(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_le_and_min_ge() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..25).map(|i| (i * 29) % 17 - 8).collect();
+        let tree = MergeSortTree::new(&input)?;
+
+        for start in 0..25 {
+            for end in start..25 {
+                for x in -10..10 {
+                    let range = &input[start..=end];
+                    assert_eq!(tree.max_le(start, end, x)?, range.iter().copied().filter(|&value| value <= x).max());
+                    assert_eq!(tree.min_ge(start, end, x)?, range.iter().copied().filter(|&value| value >= x).min());
+                }
+            }
+        }
+
+        assert_eq!(tree.max_le(0, 24, isize::MIN)?, None);
+        assert_eq!(tree.min_ge(0, 24, isize::MAX)?, None);
+        assert!(tree.min_ge(0, 25, 0).is_err());
+
+        Ok(())
+    }
+}
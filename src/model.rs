@@ -0,0 +1,179 @@
+//! Naive Reference Model
+//!
+//! `RangeModel` is the point update and range query interface shared by `SegmentTree` and
+//! `NaiveRangeModel`, a plain vector answering every query with an O(n) fold. Running the same
+//! operations on both is a differential test of the tree, or of a custom monoid.
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Range Model
+/// Sequence of items answering range queries under a monoid
+pub trait RangeModel<M: Monoid> {
+    /// Get the number of items
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no items
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the items, in order
+    fn leaves(&self) -> &[M::Item];
+
+    /// Query the range from `start` to `end`, inclusive
+    /// Returns the result of the range, or an error
+    fn query(&self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError>;
+
+    /// Set the item at an index
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    fn update(&mut self, index: usize, item: M::Item) -> Result<(), SegmentTreeError>;
+
+    /// Append an item
+    /// Returns `Ok(())` if the item was appended, otherwise an error
+    fn push(&mut self, item: M::Item) -> Result<(), SegmentTreeError>;
+
+    /// Remove the last item
+    /// Returns the removed item, or `None` if there are no items
+    fn pop(&mut self) -> Option<M::Item>;
+}
+
+impl<M: Monoid> RangeModel<M> for SegmentTree<M> {
+    fn len(&self) -> usize {
+        SegmentTree::len(self)
+    }
+
+    fn leaves(&self) -> &[M::Item] {
+        SegmentTree::leaves(self)
+    }
+
+    fn query(&self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
+        SegmentTree::query(self, start, end)
+    }
+
+    fn update(&mut self, index: usize, item: M::Item) -> Result<(), SegmentTreeError> {
+        SegmentTree::update(self, index, item)
+    }
+
+    fn push(&mut self, item: M::Item) -> Result<(), SegmentTreeError> {
+        SegmentTree::push(self, item)
+    }
+
+    fn pop(&mut self) -> Option<M::Item> {
+        SegmentTree::pop(self)
+    }
+}
+
+/// Naive Range Model
+/// Reference implementation of `RangeModel` over a plain vector, with O(n) queries and O(1) updates.
+/// It validates and reports errors exactly like `SegmentTree`.
+/// items: Item of each index
+/// monoid: Operation used to combine values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaiveRangeModel<M: Monoid> {
+    items: Vec<M::Item>,
+    monoid: M,
+}
+
+impl<M: Monoid> NaiveRangeModel<M> {
+    /// Create a naive model
+    /// input: Items of the model
+    /// monoid: Operation used to combine values
+    /// Returns a new `NaiveRangeModel` structure, or the error `SegmentTree::with_monoid` returns for
+    /// the same input: empty, too large, or holding an invalid item
+    pub fn new(input: &[M::Item], monoid: M) -> Result<NaiveRangeModel<M>, SegmentTreeError> {
+        SegmentTree::validate_input(input, &monoid)?;
+
+        Ok(NaiveRangeModel { items: input.to_vec(), monoid })
+    }
+
+    /// Validate an index
+    /// index: Index of an item
+    /// Returns `Ok(())` if the index is in bounds, otherwise an error
+    fn validate_index(&self, index: usize) -> Result<(), SegmentTreeError> {
+        if index >= self.items.len() {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.items.len() });
+        }
+
+        Ok(())
+    }
+}
+
+impl<M: Monoid> RangeModel<M> for NaiveRangeModel<M> {
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn leaves(&self) -> &[M::Item] {
+        &self.items
+    }
+
+    fn query(&self, start: usize, end: usize) -> Result<M::Output, SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.items.len() });
+        }
+        self.validate_index(start)?;
+        self.validate_index(end)?;
+
+        let value = self.items[start..=end].iter()
+            .fold(self.monoid.identity(), |value, item| self.monoid.combine(&value, &self.monoid.lift(item)));
+        self.monoid.finish(value, start, end)
+    }
+
+    fn update(&mut self, index: usize, item: M::Item) -> Result<(), SegmentTreeError> {
+        self.validate_index(index)?;
+        self.monoid.validate(&item, index)?;
+
+        self.items[index] = item;
+        Ok(())
+    }
+
+    fn push(&mut self, item: M::Item) -> Result<(), SegmentTreeError> {
+        if self.items.len() >= MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: self.items.len() + 1, max: MAX_INPUT_SIZE });
+        }
+        self.monoid.validate(&item, self.items.len())?;
+
+        self.items.push(item);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<M::Item> {
+        self.items.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FnMonoid, NaiveRangeModel, Op, RangeModel, SegmentTree, SegmentTreeError, Sum};
+
+    #[test]
+    fn test_differential_against_naive_model() -> Result<(), SegmentTreeError> {
+        let data: Vec<u8> = (0..3000_u32).map(|i| (i.wrapping_mul(2_246_822_519) >> 11) as u8).collect();
+        let ops = Op::sequence_from_bytes(&data, 4);
+
+        let input = [3, -1, 4, -1];
+        let mut segment_tree = SegmentTree::new(&input)?;
+        let mut model = NaiveRangeModel::new(&input, Sum::default())?;
+        for op in &ops {
+            assert_eq!(op.apply(&mut segment_tree), op.apply(&mut model));
+        }
+        assert_eq!(segment_tree.leaves(), model.leaves());
+        assert_eq!(RangeModel::query(&model, 1, 0), SegmentTree::query(&segment_tree, 1, 0));
+
+        // A custom non-commutative monoid, checked the same way
+        let monoid = FnMonoid::new(String::new(), |left: &String, right: &String| format!("{}{}", left, right));
+        let input: Vec<String> = "segment".chars().map(String::from).collect();
+        let mut segment_tree = SegmentTree::with_monoid(&input, monoid.clone())?;
+        let mut model = NaiveRangeModel::new(&input, monoid)?;
+        for op in [Op::Update { index: 2, item: "G".to_string() }, Op::Push("s".to_string()), Op::Pop, Op::Query { start: 1, end: 5 }] {
+            assert_eq!(op.apply(&mut segment_tree), op.apply(&mut model));
+        }
+        assert_eq!(RangeModel::query(&model, 0, 6)?, "seGment");
+
+        // Construction is validated the same way
+        assert_eq!(NaiveRangeModel::new(&[], Sum::default()).err(), SegmentTree::new(&[]).err());
+        assert_eq!(NaiveRangeModel::new(&[], Sum::default()).err(), Some(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Modular Integers
+
+use crate::{Monoid, SegmentTreeError};
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+/// Modular Integer
+/// Integer modulo `M`, always stored reduced into `0..M`
+/// M: Modulus, which must be greater than zero
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModInt<const M: u64>(u64);
+
+impl<const M: u64> ModInt<M> {
+    /// Create a new modular integer
+    /// value: Integer to reduce modulo `M`
+    pub fn new(value: u64) -> ModInt<M> {
+        const { assert!(M > 0, "The modulus must be greater than zero") };
+        ModInt(value % M)
+    }
+
+    /// Get the reduced value, in `0..M`
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+impl<const M: u64> From<u64> for ModInt<M> {
+    fn from(value: u64) -> ModInt<M> {
+        ModInt::new(value)
+    }
+}
+
+impl<const M: u64> From<i64> for ModInt<M> {
+    fn from(value: i64) -> ModInt<M> {
+        ModInt::new((value as i128).rem_euclid(M as i128) as u64)
+    }
+}
+
+impl<const M: u64> Add for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn add(self, other: ModInt<M>) -> ModInt<M> {
+        // Both values are below M, so the sum cannot overflow u128
+        ModInt(((self.0 as u128 + other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Sub for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn sub(self, other: ModInt<M>) -> ModInt<M> {
+        ModInt(((self.0 as u128 + M as u128 - other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Mul for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn mul(self, other: ModInt<M>) -> ModInt<M> {
+        ModInt(((self.0 as u128 * other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> fmt::Display for ModInt<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Modular Sum
+/// Sum of `ModInt<M>` leaves modulo `M`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModSum<const M: u64>;
+
+impl<const M: u64> Monoid for ModSum<M> {
+    type Item = ModInt<M>;
+    type Value = ModInt<M>;
+    type Output = ModInt<M>;
+
+    fn identity(&self) -> ModInt<M> {
+        ModInt::new(0)
+    }
+
+    fn lift(&self, item: &ModInt<M>) -> ModInt<M> {
+        *item
+    }
+
+    fn combine(&self, left: &ModInt<M>, right: &ModInt<M>) -> ModInt<M> {
+        *left + *right
+    }
+
+    fn finish(&self, value: ModInt<M>, _start: usize, _end: usize) -> Result<ModInt<M>, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+/// Modular Product
+/// Product of `ModInt<M>` leaves modulo `M`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModProduct<const M: u64>;
+
+impl<const M: u64> Monoid for ModProduct<M> {
+    type Item = ModInt<M>;
+    type Value = ModInt<M>;
+    type Output = ModInt<M>;
+
+    fn identity(&self) -> ModInt<M> {
+        ModInt::new(1)
+    }
+
+    fn lift(&self, item: &ModInt<M>) -> ModInt<M> {
+        *item
+    }
+
+    fn combine(&self, left: &ModInt<M>, right: &ModInt<M>) -> ModInt<M> {
+        *left * *right
+    }
+
+    fn finish(&self, value: ModInt<M>, _start: usize, _end: usize) -> Result<ModInt<M>, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ModInt, ModProduct, ModSum, SegmentTree, SegmentTreeError};
+
+    const P: u64 = 1_000_000_007;
+
+    #[test]
+    fn test_modint_arithmetic() {
+        let a = ModInt::<P>::new(P - 1);
+        let b = ModInt::<P>::new(2);
+        assert_eq!((a + b).value(), 1);
+        assert_eq!((b - a).value(), 3);
+        assert_eq!((a * a).value(), 1);
+        assert_eq!(ModInt::<P>::from(-1i64), a);
+        assert_eq!(ModInt::<7>::new(100).to_string(), "2");
+
+        // Products near u64::MAX must not overflow
+        let big = ModInt::<{ u64::MAX }>::new(u64::MAX - 1);
+        assert_eq!((big * big).value(), 1);
+    }
+
+    #[test]
+    fn test_modint_segment_tree() -> Result<(), SegmentTreeError> {
+        let input: Vec<ModInt<P>> = [P - 1, 2, 500_000_004, 10].into_iter().map(ModInt::new).collect();
+
+        let mut sums = SegmentTree::with_monoid(&input, ModSum)?;
+        assert_eq!(sums.query(0, 1)?.value(), 1);
+        assert_eq!(sums.query(0, 3)?.value(), 500_000_015);
+
+        // 2 * 500_000_004 is 1 modulo P
+        let mut products = SegmentTree::with_monoid(&input, ModProduct)?;
+        assert_eq!(products.query(1, 2)?.value(), 1);
+        assert_eq!(products.query(0, 3)?.value(), P - 10);
+
+        sums.update(0, ModInt::new(1))?;
+        products.update(0, ModInt::new(1))?;
+        assert_eq!(sums.query(0, 1)?.value(), 3);
+        assert_eq!(products.query(0, 3)?.value(), 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+//! Monoids
+
+use crate::{OverflowPolicy, SegmentTreeError};
+
+/// Monoid
+/// Operation used to combine the values of a segment tree
+/// Item: Type of the values stored in the leaves
+/// Value: Type of the aggregates stored in the nodes
+/// Output: Type of query results
+/// `combine` must be associative, and combining any value with `identity` must leave it unchanged
+/// `combine` does not have to be commutative. Every query combines the values of its range strictly in
+/// index order, always passing the range on the left as `left`, so operations like string concatenation,
+/// function composition and matrix products give the same result as a left-to-right fold of the items.
+pub trait Monoid {
+    type Item: Clone;
+    type Value: Clone;
+    type Output;
+
+    /// Check that an item can be stored in a leaf
+    /// Items are validated before they are written, so `lift` only ever sees valid items
+    /// item: Leaf item
+    /// index: Index the item is written to
+    /// Returns `Ok(())` if the item is valid, otherwise an error
+    fn validate(&self, _item: &Self::Item, _index: usize) -> Result<(), SegmentTreeError> {
+        Ok(())
+    }
+
+    /// Set how results that do not fit are handled, for monoids with an `OverflowPolicy`
+    /// `SegmentTreeBuilder` sets it at `build`, so the policy can be chosen before or after the monoid.
+    /// Monoids whose results cannot overflow ignore it
+    /// overflow_policy: Overflow policy of the results
+    fn set_overflow_policy(&mut self, _overflow_policy: OverflowPolicy) {}
+
+    /// Value of an empty range
+    fn identity(&self) -> Self::Value;
+
+    /// Convert a leaf item into a value
+    /// item: Leaf item
+    fn lift(&self, item: &Self::Item) -> Self::Value;
+
+    /// Combine the values of two adjacent ranges
+    /// left: Value of the range on the left
+    /// right: Value of the range on the right
+    fn combine(&self, left: &Self::Value, right: &Self::Value) -> Self::Value;
+
+    /// Combine the value of the range on the right into the value of the range on the left
+    /// Queries accumulate through this method, so values that are expensive to create
+    /// (such as big integers) can override it to update `left` without allocating
+    /// left: Value of the range on the left, replaced by the combined value
+    /// right: Value of the range on the right
+    fn combine_into(&self, left: &mut Self::Value, right: &Self::Value) {
+        *left = self.combine(left, right);
+    }
+
+    /// Convert the value of a queried range into a query result
+    /// value: Value of the range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the query result or an error
+    fn finish(&self, value: Self::Value, start: usize, end: usize) -> Result<Self::Output, SegmentTreeError>;
+}
+
+/// Acted Monoid
+/// Monoid whose values can be transformed by maps, used by `LazySegmentTree` for range updates
+/// Map: Type of the transformations applied to ranges
+/// `compose` must be associative with `identity_map` as its identity, and every map must distribute
+/// over `combine`: applying a map to a combined range equals combining the mapped parts.
+/// Requires the `lazy` feature
+#[cfg(feature = "lazy")]
+pub trait ActedMonoid: Monoid {
+    type Map: Clone;
+
+    /// Map that leaves every value unchanged
+    fn identity_map(&self) -> Self::Map;
+
+    /// Apply a map to the value of a range
+    /// map: Map to apply
+    /// value: Value of the range
+    /// len: Number of leaves in the range, for maps whose effect depends on it (such as adding to a sum)
+    fn apply(&self, map: &Self::Map, value: &Self::Value, len: usize) -> Self::Value;
+
+    /// Compose two maps into one
+    /// outer: Map applied second
+    /// inner: Map applied first
+    /// Returns the map equivalent to applying `inner` and then `outer`
+    fn compose(&self, outer: &Self::Map, inner: &Self::Map) -> Self::Map;
+}
+
+/// Sum
+/// Sum of `isize` leaves. Sums are accumulated in `i128`, so leaves may use the full `isize` range
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sum {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for Sum {
+    type Item = isize;
+    type Value = i128;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> i128 {
+        0
+    }
+
+    fn lift(&self, item: &isize) -> i128 {
+        *item as i128
+    }
+
+    fn combine(&self, left: &i128, right: &i128) -> i128 {
+        left + right
+    }
+
+    fn finish(&self, value: i128, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        self.overflow_policy.narrow(value, start, end)
+    }
+}
+
+/// Max
+/// Maximum of `isize` leaves. The maximum of an empty range is `isize::MIN`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Max;
+
+impl Monoid for Max {
+    type Item = isize;
+    type Value = isize;
+    type Output = isize;
+
+    fn identity(&self) -> isize {
+        isize::MIN
+    }
+
+    fn lift(&self, item: &isize) -> isize {
+        *item
+    }
+
+    fn combine(&self, left: &isize, right: &isize) -> isize {
+        *left.max(right)
+    }
+
+    fn finish(&self, value: isize, _start: usize, _end: usize) -> Result<isize, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+/// Min
+/// Minimum of `isize` leaves. The minimum of an empty range is `isize::MAX`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Min;
+
+impl Monoid for Min {
+    type Item = isize;
+    type Value = isize;
+    type Output = isize;
+
+    fn identity(&self) -> isize {
+        isize::MAX
+    }
+
+    fn lift(&self, item: &isize) -> isize {
+        *item
+    }
+
+    fn combine(&self, left: &isize, right: &isize) -> isize {
+        *left.min(right)
+    }
+
+    fn finish(&self, value: isize, _start: usize, _end: usize) -> Result<isize, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+/// Greatest common divisor of two values
+/// Returns the GCD, which is 0 only if both values are 0
+fn gcd(mut left: u64, mut right: u64) -> u64 {
+    while right != 0 {
+        (left, right) = (right, left % right);
+    }
+    left
+}
+
+/// Gcd
+/// Greatest common divisor of `u64` leaves. The GCD of an empty range is 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gcd;
+
+impl Monoid for Gcd {
+    type Item = u64;
+    type Value = u64;
+    type Output = u64;
+
+    fn identity(&self) -> u64 {
+        0
+    }
+
+    fn lift(&self, item: &u64) -> u64 {
+        *item
+    }
+
+    fn combine(&self, left: &u64, right: &u64) -> u64 {
+        gcd(*left, *right)
+    }
+
+    fn finish(&self, value: u64, _start: usize, _end: usize) -> Result<u64, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+/// Lcm
+/// Least common multiple of `u64` leaves. The LCM of an empty range is 1, and of any range holding 0 is 0.
+/// Values are `None` once the LCM no longer fits in `u64`
+/// overflow_policy: How LCMs that do not fit in `u64` are handled. `Checked` returns an error, while
+/// `Saturating` and `Wrapping` both return `u64::MAX`, as a wrapped LCM means nothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lcm {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for Lcm {
+    type Item = u64;
+    type Value = Option<u64>;
+    type Output = u64;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> Option<u64> {
+        Some(1)
+    }
+
+    fn lift(&self, item: &u64) -> Option<u64> {
+        Some(*item)
+    }
+
+    fn combine(&self, left: &Option<u64>, right: &Option<u64>) -> Option<u64> {
+        match (*left, *right) {
+            (Some(0), _) | (_, Some(0)) => Some(0),
+            (Some(left), Some(right)) => (left / gcd(left, right)).checked_mul(right),
+            _ => None,
+        }
+    }
+
+    fn finish(&self, value: Option<u64>, start: usize, end: usize) -> Result<u64, SegmentTreeError> {
+        match (value, self.overflow_policy) {
+            (Some(value), _) => Ok(value),
+            (None, OverflowPolicy::Checked) => Err(SegmentTreeError::Overflow { start, end }),
+            (None, _) => Ok(u64::MAX),
+        }
+    }
+}
+
+/// Function Monoid
+/// Monoid over any cloneable type, built from an identity value and a combining function
+/// identity: Value of an empty range
+/// combine: Function combining the values of two adjacent ranges, which must be associative
+#[derive(Debug, Clone, Copy)]
+pub struct FnMonoid<T, F> {
+    pub identity: T,
+    pub combine: F,
+}
+
+impl<T, F> FnMonoid<T, F>
+where
+    T: Clone,
+    F: Fn(&T, &T) -> T,
+{
+    /// Create a new function monoid
+    /// identity: Value of an empty range
+    /// combine: Function combining the values of two adjacent ranges
+    pub fn new(identity: T, combine: F) -> FnMonoid<T, F> {
+        FnMonoid { identity, combine }
+    }
+}
+
+impl<T, F> Monoid for FnMonoid<T, F>
+where
+    T: Clone,
+    F: Fn(&T, &T) -> T,
+{
+    type Item = T;
+    type Value = T;
+    type Output = T;
+
+    fn identity(&self) -> T {
+        self.identity.clone()
+    }
+
+    fn lift(&self, item: &T) -> T {
+        item.clone()
+    }
+
+    fn combine(&self, left: &T, right: &T) -> T {
+        (self.combine)(left, right)
+    }
+
+    fn finish(&self, value: T, _start: usize, _end: usize) -> Result<T, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FnMonoid, Gcd, Lcm, OverflowPolicy, SegmentTree, SegmentTreeError};
+
+    /// Affine map `x -> a * x + b`, as `(a, b)`
+    type Affine = (i64, i64);
+
+    /// Compose two affine maps, applying `first` and then `second`
+    /// first: Map applied first, on the left of the range
+    /// second: Map applied second
+    fn compose(first: &Affine, second: &Affine) -> Affine {
+        (first.0 * second.0, first.1 * second.0 + second.1)
+    }
+
+    #[test]
+    fn test_non_commutative_order() -> Result<(), SegmentTreeError> {
+        let input: Vec<Affine> = (0..13).map(|i| (i % 3 + 1, i - 6)).collect();
+        let monoid = FnMonoid::new((1, 0), compose);
+        let mut segment_tree = SegmentTree::with_monoid(&input, monoid)?;
+        segment_tree.update(5, (2, 7))?;
+        let mut items = input.clone();
+        items[5] = (2, 7);
+
+        let fold = |start: usize, end: usize| items[start..=end].iter().fold((1, 0), |left, right| compose(&left, right));
+        let mut ranges = Vec::new();
+        for start in 0..items.len() {
+            for end in start..items.len() {
+                assert_eq!(segment_tree.query(start, end)?, fold(start, end));
+                assert_eq!(segment_tree.query_with_stats(start, end)?.0, fold(start, end));
+                ranges.push((start, end));
+            }
+            assert_eq!(segment_tree.prefix(start)?, fold(0, start));
+            assert_eq!(segment_tree.suffix(start)?, fold(start, items.len() - 1));
+        }
+        assert_eq!(segment_tree.total()?, fold(0, items.len() - 1));
+
+        let expected: Vec<Affine> = ranges.iter().map(|&(start, end)| fold(start, end)).collect();
+        assert_eq!(segment_tree.query_batch(&ranges)?, expected);
+        #[cfg(feature = "rayon")]
+        assert_eq!(segment_tree.parallel_query(&ranges), expected.iter().map(|value| Ok(*value)).collect::<Vec<_>>());
+
+        #[cfg(feature = "dynamic")]
+        {
+            let mut dynamic = crate::DynamicSegmentTree::with_monoid(items.len(), monoid)?;
+            for (index, item) in items.iter().enumerate() {
+                dynamic.update(index, *item)?;
+            }
+            for &(start, end) in &ranges {
+                assert_eq!(dynamic.query(start, end)?, fold(start, end));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gcd_and_lcm() -> Result<(), SegmentTreeError> {
+        let input = [12, 18, 30, 7, 0, 5];
+        let mut gcds = SegmentTree::with_monoid(&input, Gcd)?;
+        let lcms = SegmentTree::with_monoid(&input, Lcm::default())?;
+
+        assert_eq!(gcds.query(0, 2)?, 6);
+        assert_eq!(gcds.query(0, 3)?, 1);
+        assert_eq!(gcds.query(4, 4)?, 0);
+        assert_eq!(gcds.query(4, 5)?, 5);
+        gcds.update(3, 42)?;
+        assert_eq!(gcds.query(0, 3)?, 6);
+
+        assert_eq!(lcms.query(0, 2)?, 180);
+        assert_eq!(lcms.query(0, 3)?, 1260);
+        assert_eq!(lcms.query(3, 5)?, 0);
+        assert_eq!(lcms.query_range(2..2)?, 1);
+
+        let large = [1 << 40, 3 << 30, (1 << 31) - 1, 7];
+        let checked = SegmentTree::with_monoid(&large, Lcm::default())?;
+        assert_eq!(checked.query(0, 1)?, 3 << 40);
+        assert_eq!(checked.query(0, 3), Err(SegmentTreeError::Overflow { start: 0, end: 3 }));
+        let saturating = SegmentTree::with_monoid(&large, Lcm { overflow_policy: OverflowPolicy::Saturating })?;
+        assert_eq!(saturating.query(0, 3)?, u64::MAX);
+
+        Ok(())
+    }
+}
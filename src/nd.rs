@@ -0,0 +1,215 @@
+//! N-Dimensional Segment Trees
+
+use crate::point_set::for_each_node;
+use crate::{OverflowPolicy, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Call a function on every node index of a cartesian product of per-dimension node lists
+/// lists: Node indices to combine in each dimension
+/// strides: Distance between consecutive node indices in each dimension
+/// offset: Flat index of the dimensions already chosen
+/// f: Function to call with the flat index of each node
+fn for_each_product(lists: &[Vec<usize>], strides: &[usize], offset: usize, f: &mut impl FnMut(usize)) {
+    match lists.split_first() {
+        None => f(offset),
+        Some((list, rest)) => {
+            for &node_idx in list {
+                for_each_product(rest, &strides[1..], offset + node_idx * strides[0], f);
+            }
+        }
+    }
+}
+
+/// Segment Tree ND
+/// Sum tree over a `D`-dimensional grid of `isize` values with point updates and hyper-rectangle
+/// sum queries in O(log^D n), meant for small `D` such as 2 to 4.
+/// Every dimension is a bottom-up tree with its leaves at `len..2 * len`, and the trees are nested,
+/// so a node is a tuple of one node per dimension and the grid takes 2^D times its cells.
+/// Sums are accumulated in `i128`, and hyper-rectangles report overflow by the row-major flat
+/// indices of their corners.
+/// dims: Number of cells in each dimension
+/// nodes: Sum of every node tuple, row-major over `2 * dims[d]` node indices per dimension
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentTreeND<const D: usize> {
+    dims: [usize; D],
+    nodes: Vec<i128>,
+    overflow_policy: OverflowPolicy,
+}
+
+impl<const D: usize> SegmentTreeND<D> {
+    /// Create a grid of zeros with checked overflow
+    /// dims: Number of cells in each dimension
+    /// Returns a new `SegmentTreeND` structure or an error
+    pub fn new(dims: [usize; D]) -> Result<SegmentTreeND<D>, SegmentTreeError> {
+        SegmentTreeND::with_overflow_policy(dims, OverflowPolicy::Checked)
+    }
+
+    /// Create a grid of zeros with a specific overflow policy
+    /// dims: Number of cells in each dimension
+    /// overflow_policy: How sums that do not fit in `isize` are handled
+    /// Returns a new `SegmentTreeND` structure or an error if a dimension is empty or the grid is too large
+    pub fn with_overflow_policy(dims: [usize; D], overflow_policy: OverflowPolicy) -> Result<SegmentTreeND<D>, SegmentTreeError> {
+        if D == 0 || dims.contains(&0) {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+        let node_count = dims.iter().try_fold(1usize, |count, &len| count.checked_mul(2 * len))
+            .filter(|&count| count <= MAX_INPUT_SIZE)
+            .ok_or(SegmentTreeError::InputTooLarge { len: dims.iter().fold(1usize, |count, &len| count.saturating_mul(len)), max: MAX_INPUT_SIZE })?;
+
+        Ok(SegmentTreeND { dims, nodes: vec![0; node_count], overflow_policy })
+    }
+
+    /// Get the number of cells in each dimension
+    pub fn dims(&self) -> [usize; D] {
+        self.dims
+    }
+
+    /// Get the distance between consecutive node indices in each dimension
+    fn strides(&self) -> [usize; D] {
+        let mut strides = [1; D];
+        for d in (0..D.saturating_sub(1)).rev() {
+            strides[d] = strides[d + 1] * 2 * self.dims[d + 1];
+        }
+        strides
+    }
+
+    /// Get the row-major index of a cell among all cells
+    /// point: Coordinates of the cell
+    fn flat_index(&self, point: &[usize; D]) -> usize {
+        point.iter().zip(&self.dims).fold(0, |index, (&coordinate, &len)| index * len + coordinate)
+    }
+
+    /// Validate a cell
+    /// point: Coordinates of the cell
+    /// Returns `Ok(())` if the cell is in the grid, otherwise an error
+    fn validate_point(&self, point: &[usize; D]) -> Result<(), SegmentTreeError> {
+        for (&coordinate, &len) in point.iter().zip(&self.dims) {
+            if coordinate >= len {
+                return Err(SegmentTreeError::IndexOutOfBounds { index: coordinate, len });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the value of a cell
+    /// point: Coordinates of the cell
+    /// Returns the value, or an error if the cell is out of bounds
+    pub fn get(&self, point: [usize; D]) -> Result<isize, SegmentTreeError> {
+        self.validate_point(&point)?;
+
+        let strides = self.strides();
+        let leaf = (0..D).map(|d| (self.dims[d] + point[d]) * strides[d]).sum::<usize>();
+        Ok(self.nodes[leaf] as isize)
+    }
+
+    /// Update the value of a cell
+    /// Adds the change to every node tuple whose nodes all contain the cell, O(log^D n) of them
+    /// point: Coordinates of the cell
+    /// new_value: New value for the cell
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, point: [usize; D], new_value: isize) -> Result<(), SegmentTreeError> {
+        let delta = new_value as i128 - self.get(point)? as i128;
+
+        // Every dimension contributes the leaf of the cell and each of its ancestors
+        let lists: Vec<Vec<usize>> = (0..D)
+            .map(|d| std::iter::successors(Some(self.dims[d] + point[d]), |&node_idx| Some(node_idx / 2))
+                .take_while(|&node_idx| node_idx > 0)
+                .collect())
+            .collect();
+        let mut touched = Vec::new();
+        for_each_product(&lists, &self.strides(), 0, &mut |node_idx| touched.push(node_idx));
+        for node_idx in touched {
+            self.nodes[node_idx] += delta;
+        }
+        Ok(())
+    }
+
+    /// Add a delta to the value of a cell
+    /// point: Coordinates of the cell
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn add(&mut self, point: [usize; D], delta: isize) -> Result<(), SegmentTreeError> {
+        let value = self.get(point)?;
+        let new_value = self.overflow_policy.add(value, delta, self.flat_index(&point))?;
+        self.update(point, new_value)
+    }
+
+    /// Query the sum of a hyper-rectangle
+    /// start: First cell of the hyper-rectangle in each dimension
+    /// end: Last cell of the hyper-rectangle in each dimension
+    /// Returns the sum of the hyper-rectangle, or an error
+    pub fn query(&self, start: [usize; D], end: [usize; D]) -> Result<isize, SegmentTreeError> {
+        for d in 0..D {
+            if start[d] > end[d] {
+                return Err(SegmentTreeError::InvalidRange { start: start[d], end: end[d], len: self.dims[d] });
+            }
+        }
+        self.validate_point(&end)?;
+
+        // Every dimension contributes the nodes covering its range, from its bottom-up tree
+        let lists: Vec<Vec<usize>> = (0..D)
+            .map(|d| {
+                let mut nodes = Vec::new();
+                for_each_node(self.dims[d], start[d], end[d] + 1, |node_idx| nodes.push(node_idx));
+                nodes
+            })
+            .collect();
+        let mut sum = 0;
+        for_each_product(&lists, &self.strides(), 0, &mut |node_idx| sum += self.nodes[node_idx]);
+        self.overflow_policy.narrow(sum, self.flat_index(&start), self.flat_index(&end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTreeError, SegmentTreeND};
+
+    #[test]
+    fn test_segment_tree_3d() -> Result<(), SegmentTreeError> {
+        let dims = [4, 5, 3];
+        let mut segment_tree = SegmentTreeND::new(dims)?;
+        let mut grid = vec![vec![vec![0isize; 3]; 5]; 4];
+        for step in 0..30 {
+            let point = [step * 7 % 4, step * 3 % 5, step % 3];
+            let value = step as isize * 5 - 40;
+            segment_tree.update(point, value)?;
+            grid[point[0]][point[1]][point[2]] = value;
+        }
+        segment_tree.add([1, 1, 1], 3)?;
+        grid[1][1][1] += 3;
+        assert_eq!(segment_tree.get([1, 1, 1])?, grid[1][1][1]);
+
+        for start in [[0, 0, 0], [1, 2, 0], [3, 4, 2], [0, 1, 1]] {
+            for end in [[3, 4, 2], [3, 2, 1], [1, 4, 2]] {
+                if (0..3).any(|d| start[d] > end[d]) {
+                    continue;
+                }
+                let expected: isize = (start[0]..=end[0])
+                    .flat_map(|x| (start[1]..=end[1]).flat_map(move |y| (start[2]..=end[2]).map(move |z| (x, y, z))))
+                    .map(|(x, y, z)| grid[x][y][z])
+                    .sum();
+                assert_eq!(segment_tree.query(start, end)?, expected);
+            }
+        }
+
+        assert_eq!(segment_tree.query([0, 3, 0], [1, 2, 0]), Err(SegmentTreeError::InvalidRange { start: 3, end: 2, len: 5 }));
+        assert_eq!(segment_tree.update([0, 0, 3], 1), Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }));
+        // Coordinates are validated before they are combined into a flat index, which could overflow
+        assert_eq!(segment_tree.add([usize::MAX, 0, 0], 1), Err(SegmentTreeError::IndexOutOfBounds { index: usize::MAX, len: 4 }));
+        assert_eq!(SegmentTreeND::new([2, 0]), Err(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_segment_tree_2d_overflow() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTreeND::new([2, 3])?;
+        segment_tree.update([1, 1], isize::MAX)?;
+        segment_tree.update([1, 2], 1)?;
+        assert_eq!(segment_tree.query([1, 0], [1, 1])?, isize::MAX);
+        assert_eq!(segment_tree.query([0, 1], [1, 2]), Err(SegmentTreeError::Overflow { start: 1, end: 5 }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+//! Generic Numeric Sums
+//!
+//! `Sum` is fixed to `isize` leaves. `NumSum` adds up any type implementing `Number`, which
+//! mirrors the `Zero`, `CheckedAdd`, `SaturatingAdd` and `WrappingAdd` traits of `num-traits`
+//! and is implemented for every primitive integer, so user numeric types can implement it too.
+
+use crate::{Monoid, OverflowPolicy, SegmentTree, SegmentTreeError};
+use std::marker::PhantomData;
+
+/// Number
+/// Numeric type that can be summed by `NumSum`
+pub trait Number: Copy {
+    /// Get the additive identity
+    fn zero() -> Self;
+
+    /// Add two numbers
+    /// Returns the sum, or `None` if it overflows
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// Add two numbers, clamping the sum to the bounds of the type
+    fn saturating_add(self, other: Self) -> Self;
+
+    /// Add two numbers, wrapping the sum around the bounds of the type
+    fn wrapping_add(self, other: Self) -> Self;
+
+    /// Check if the number is below zero, which tells the direction of an overflow
+    /// Defaults to `false`, which is right for unsigned types. Signed types that keep the default
+    /// report some sums that overflow and come back as overflowing, but never miss an overflow.
+    fn is_negative(self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_number {
+    (signed: $($number:ty),*) => {
+        $(
+            impl_number!(@impl $number, fn is_negative(self) -> bool {
+                self < 0
+            });
+        )*
+    };
+    (unsigned: $($number:ty),*) => {
+        $(
+            impl_number!(@impl $number,);
+        )*
+    };
+    (@impl $number:ty, $($is_negative:tt)*) => {
+        impl Number for $number {
+            fn zero() -> $number {
+                0
+            }
+
+            fn checked_add(self, other: $number) -> Option<$number> {
+                <$number>::checked_add(self, other)
+            }
+
+            fn saturating_add(self, other: $number) -> $number {
+                <$number>::saturating_add(self, other)
+            }
+
+            fn wrapping_add(self, other: $number) -> $number {
+                <$number>::wrapping_add(self, other)
+            }
+
+            $($is_negative)*
+        }
+    };
+}
+
+impl_number!(signed: i8, i16, i32, i64, i128, isize);
+impl_number!(unsigned: u8, u16, u32, u64, u128, usize);
+
+/// Number Sum
+/// Sum of `Number` leaves, added with the operation of the overflow policy
+/// Leaves of type `T` are accumulated in a wider `A`, such as `NumSum<u8, u64>`, so huge arrays of
+/// small counters keep their leaf items compact while the nodes hold sums that do not overflow.
+/// Under `Checked`, nodes keep their wrapped sum and the net number of overflows, so a range returns
+/// an error only when its own sum overflows, even if a node inside it overflowed and the rest of the
+/// range brings the sum back. Under `Saturating`, every node saturates, so a range that overflows and
+/// comes back may stay clamped.
+/// overflow_policy: How sums that do not fit in the number type are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumSum<T, A = T> {
+    pub overflow_policy: OverflowPolicy,
+    number: PhantomData<(T, A)>,
+}
+
+impl<T> NumSum<T> {
+    /// Create a number sum accumulating in the leaf type
+    /// overflow_policy: How sums that do not fit in the number type are handled
+    pub fn new(overflow_policy: OverflowPolicy) -> NumSum<T> {
+        NumSum::widening(overflow_policy)
+    }
+}
+
+impl<T, A> NumSum<T, A> {
+    /// Create a number sum accumulating in a wider type, such as `NumSum::<u8, u64>::widening`
+    /// overflow_policy: How sums that do not fit in the accumulator type are handled
+    pub fn widening(overflow_policy: OverflowPolicy) -> NumSum<T, A> {
+        NumSum { overflow_policy, number: PhantomData }
+    }
+}
+
+impl<T> Default for NumSum<T> {
+    fn default() -> NumSum<T> {
+        NumSum::new(OverflowPolicy::default())
+    }
+}
+
+impl<T: Copy, A: Number + From<T>> Monoid for NumSum<T, A> {
+    type Item = T;
+    /// Sum and net number of overflows past the upper bound, counted under the checked policy only.
+    /// The sum wraps under the checked policy, so the exact sum is the sum plus the overflows times 2^bits.
+    type Value = (A, isize);
+    type Output = A;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> (A, isize) {
+        (A::zero(), 0)
+    }
+
+    fn lift(&self, item: &T) -> (A, isize) {
+        (A::from(*item), 0)
+    }
+
+    fn combine(&self, left: &(A, isize), right: &(A, isize)) -> (A, isize) {
+        let ((left, left_overflows), (right, right_overflows)) = (*left, *right);
+        match self.overflow_policy {
+            OverflowPolicy::Checked => {
+                let overflows = match left.checked_add(right) {
+                    Some(_) => 0,
+                    None if right.is_negative() => -1,
+                    None => 1,
+                };
+                (left.wrapping_add(right), left_overflows + right_overflows + overflows)
+            }
+            OverflowPolicy::Saturating => (left.saturating_add(right), 0),
+            OverflowPolicy::Wrapping => (left.wrapping_add(right), 0),
+        }
+    }
+
+    fn finish(&self, value: (A, isize), start: usize, end: usize) -> Result<A, SegmentTreeError> {
+        match value {
+            (sum, 0) => Ok(sum),
+            _ => Err(SegmentTreeError::Overflow { start, end }),
+        }
+    }
+}
+
+impl<T: Number, A: Number + From<T>> SegmentTree<NumSum<T, A>> {
+    /// Add a delta to a leaf node in the segment tree, such as an `i128` amount
+    /// index: Index of the leaf node to update
+    /// delta: Value to add to the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    /// A leaf value that does not fit in the number type is handled by the overflow policy
+    pub fn add(&mut self, index: usize, delta: T) -> Result<(), SegmentTreeError> {
+        self.validate_index(index)?;
+
+        let value = self.leaves[index];
+        let new_value = match self.monoid.overflow_policy {
+            OverflowPolicy::Checked => value.checked_add(delta).ok_or(SegmentTreeError::Overflow { start: index, end: index })?,
+            OverflowPolicy::Saturating => value.saturating_add(delta),
+            OverflowPolicy::Wrapping => value.wrapping_add(delta),
+        };
+        self.set_leaf(index, new_value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NumSum, OverflowPolicy, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_num_sum() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::with_monoid(&[3_i32, -7, 12, 5], NumSum::default())?;
+        assert_eq!(segment_tree.query(0, 3)?, 13);
+        segment_tree.update(1, 20)?;
+        assert_eq!(segment_tree.query(0, 2)?, 35);
+
+        let input = [u64::MAX - 1, 1, 1];
+        let checked = SegmentTree::with_monoid(&input, NumSum::<u64>::default())?;
+        assert_eq!(checked.query(0, 1)?, u64::MAX);
+        assert_eq!(checked.query(0, 2), Err(SegmentTreeError::Overflow { start: 0, end: 2 }));
+        let saturating = SegmentTree::with_monoid(&input, NumSum::new(OverflowPolicy::Saturating))?;
+        assert_eq!(saturating.query(0, 2)?, u64::MAX);
+        let wrapping = SegmentTree::with_monoid(&input, NumSum::new(OverflowPolicy::Wrapping))?;
+        assert_eq!(wrapping.query(0, 2)?, 0);
+
+        // A node that overflows does not poison a range whose own sum fits
+        let mut checked = SegmentTree::with_monoid(&[i64::MAX, 1, -5], NumSum::<i64>::default())?;
+        assert_eq!(checked.query(0, 2)?, i64::MAX - 4);
+        assert_eq!(checked.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        checked.push(i64::MIN)?;
+        checked.push(i64::MAX)?;
+        assert_eq!(checked.query(2, 3), Err(SegmentTreeError::Overflow { start: 2, end: 3 }));
+        assert_eq!(checked.query(1, 4)?, -5);
+        assert_eq!(checked.query(0, 4)?, i64::MAX - 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_i128_sums() -> Result<(), SegmentTreeError> {
+        let amounts = [i64::MAX as i128, i64::MAX as i128, i64::MIN as i128, 5];
+        let mut segment_tree = SegmentTree::with_monoid(&amounts, NumSum::default())?;
+        assert_eq!(segment_tree.query(0, 1)?, 2 * i64::MAX as i128);
+        assert_eq!(segment_tree.query(0, 3)?, i64::MAX as i128 + 4);
+
+        segment_tree.add(3, i64::MAX as i128 * 1000)?;
+        assert_eq!(segment_tree.query(3, 3)?, i64::MAX as i128 * 1000 + 5);
+        assert_eq!(segment_tree.add(0, i128::MAX), Err(SegmentTreeError::Overflow { start: 0, end: 0 }));
+        assert_eq!(segment_tree.add(4, 1), Err(SegmentTreeError::IndexOutOfBounds { index: 4, len: 4 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_leaves() -> Result<(), SegmentTreeError> {
+        let counters = vec![255_u8; 1000];
+        let mut segment_tree = SegmentTree::with_monoid(&counters, NumSum::<u8, u64>::widening(OverflowPolicy::Checked))?;
+        assert_eq!(std::mem::size_of_val(segment_tree.leaves()), 1000);
+        assert_eq!(segment_tree.query(0, 999)?, 255_000);
+
+        segment_tree.update(0, 0)?;
+        assert_eq!(segment_tree.query(0, 9)?, 2295);
+        assert_eq!(segment_tree.add(1, 1), Err(SegmentTreeError::Overflow { start: 1, end: 1 }));
+
+        let segment_tree = SegmentTree::with_monoid(&[i32::MIN, i32::MIN], NumSum::<i32, i64>::widening(OverflowPolicy::Checked))?;
+        assert_eq!(segment_tree.query(0, 1)?, 2 * i32::MIN as i64);
+
+        Ok(())
+    }
+}
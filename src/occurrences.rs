@@ -0,0 +1,271 @@
+//! Value Occurrences
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Deref;
+
+/// Value Index
+/// Sorted positions of every distinct leaf value of a segment tree, returned by `SegmentTree::value_index`
+/// Counts the occurrences of a value in a range with two binary searches, in O(log n).
+/// The index is a snapshot: later updates to the tree are not reflected, so build a new one after them,
+/// or update the tree through a `CountingSegmentTree`, which keeps its index current.
+/// positions: Indices of each value, in increasing order
+/// len: Number of leaves when the index was built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueIndex<T: Eq + Hash> {
+    positions: HashMap<T, Vec<usize>>,
+    len: usize,
+}
+
+impl<T: Eq + Hash> ValueIndex<T> {
+    /// Count the occurrences of a value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Value to count
+    /// Returns the number of indices in the range holding the value, or an error
+    pub fn count_eq(&self, start: usize, end: usize, value: &T) -> Result<usize, SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        Ok(self.positions.get(value).map_or(0, |positions| {
+            positions.partition_point(|&index| index <= end) - positions.partition_point(|&index| index < start)
+        }))
+    }
+
+    /// Get the number of distinct values
+    pub fn distinct(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Record that an index holds a value
+    /// index: Index of the leaf, not already recorded for the value
+    /// value: Value of the leaf
+    fn insert(&mut self, index: usize, value: T) {
+        let positions = self.positions.entry(value).or_default();
+        let position = positions.partition_point(|&other| other < index);
+        positions.insert(position, index);
+    }
+
+    /// Record that an index no longer holds a value, dropping the value once no index holds it
+    /// index: Index of the leaf, recorded for the value
+    /// value: Previous value of the leaf
+    fn remove(&mut self, index: usize, value: &T) {
+        if let Some(positions) = self.positions.get_mut(value) {
+            if let Ok(position) = positions.binary_search(&index) {
+                positions.remove(position);
+            }
+            if positions.is_empty() {
+                self.positions.remove(value);
+            }
+        }
+    }
+}
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Item: Eq + Hash,
+{
+    /// Build an index of the positions of every leaf value, in O(n)
+    /// Returns a `ValueIndex` answering `count_eq` queries on the current values
+    pub fn value_index(&self) -> ValueIndex<M::Item> {
+        let mut positions: HashMap<M::Item, Vec<usize>> = HashMap::new();
+        for (index, value) in self.leaves.iter().enumerate() {
+            positions.entry(value.clone()).or_default().push(index);
+        }
+
+        ValueIndex { positions, len: self.leaf_len }
+    }
+
+    /// Count the occurrences of a value in a range by scanning its leaves, in O(r - l)
+    /// Use `value_index` to answer many counts on the same values in O(log n) each, or a
+    /// `CountingSegmentTree` to keep answering them in O(log n) while the values change
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Value to count
+    /// Returns the number of indices in the range holding the value, or an error
+    pub fn count_eq(&self, start: usize, end: usize, value: &M::Item) -> Result<usize, SegmentTreeError> {
+        self.validate_public_query(start, end)?;
+
+        Ok(self.leaves[start..=end].iter().filter(|&leaf| leaf == value).count())
+    }
+}
+
+/// Counting Segment Tree
+/// Segment tree that keeps a `ValueIndex` of its leaves current through every update, swap, push and
+/// pop, so `count_eq` counts a value in any range in O(log n) on the current values.
+/// Each write moves one index between the sorted positions of two values, in O(log n) plus shifting
+/// the positions after it. Queries go straight to the tree, which it dereferences to.
+/// tree: Segment tree being updated
+/// index: Positions of every leaf value, in step with the tree
+pub struct CountingSegmentTree<M: Monoid = Sum>
+where
+    M::Item: Eq + Hash,
+{
+    tree: SegmentTree<M>,
+    index: ValueIndex<M::Item>,
+}
+
+impl<M: Monoid> CountingSegmentTree<M>
+where
+    M::Item: Eq + Hash,
+{
+    /// Index the leaf values of a segment tree, in O(n)
+    /// tree: Segment tree to count values in
+    pub fn new(tree: SegmentTree<M>) -> CountingSegmentTree<M> {
+        let index = tree.value_index();
+        CountingSegmentTree { tree, index }
+    }
+
+    /// Unwrap the segment tree, dropping the index
+    pub fn into_inner(self) -> SegmentTree<M> {
+        self.tree
+    }
+
+    /// Get the index of the current leaf values
+    pub fn value_index(&self) -> &ValueIndex<M::Item> {
+        &self.index
+    }
+
+    /// Count the occurrences of a value in a range, in O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Value to count
+    /// Returns the number of indices in the range holding the value, or an error
+    pub fn count_eq(&self, start: usize, end: usize, value: &M::Item) -> Result<usize, SegmentTreeError> {
+        self.tree.validate_public_query(start, end)?;
+        self.index.count_eq(start, end, value)
+    }
+
+    /// Update a leaf and move its index to the positions of the new value
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.validate_index(index)?;
+        let old_value = self.tree.leaves[index].clone();
+        self.tree.update(index, new_value.clone())?;
+        self.index.remove(index, &old_value);
+        self.index.insert(index, new_value);
+        Ok(())
+    }
+
+    /// Swap two leaves and their positions in the index
+    /// first: Index of the first leaf node
+    /// second: Index of the second leaf node
+    /// Returns `Ok(())` if the swap was successful, otherwise an error
+    pub fn swap(&mut self, first: usize, second: usize) -> Result<(), SegmentTreeError> {
+        self.tree.swap(first, second)?;
+        if first != second {
+            let (first_value, second_value) = (self.tree.leaves[first].clone(), self.tree.leaves[second].clone());
+            self.index.remove(first, &second_value);
+            self.index.remove(second, &first_value);
+            self.index.insert(first, first_value);
+            self.index.insert(second, second_value);
+        }
+
+        Ok(())
+    }
+
+    /// Append an item and record its index, in amortized O(log n)
+    /// value: Item to append
+    /// Returns `Ok(())` if the push was successful, otherwise an error
+    pub fn push(&mut self, value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.push(value.clone())?;
+        self.index.insert(self.tree.leaf_len - 1, value);
+        self.index.len = self.tree.leaf_len;
+        Ok(())
+    }
+
+    /// Remove the last leaf and its index
+    /// Returns the removed item, or `None` if the tree is empty
+    pub fn pop(&mut self) -> Option<M::Item> {
+        let item = self.tree.pop()?;
+        self.index.remove(self.tree.leaf_len, &item);
+        self.index.len = self.tree.leaf_len;
+        Some(item)
+    }
+}
+
+impl<M: Monoid> Deref for CountingSegmentTree<M>
+where
+    M::Item: Eq + Hash,
+{
+    type Target = SegmentTree<M>;
+
+    fn deref(&self) -> &SegmentTree<M> {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CountingSegmentTree, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_count_eq() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (0..200).map(|i| (i * i) % 11).collect();
+        let mut segment_tree = SegmentTree::new(&input)?;
+        let value_index = segment_tree.value_index();
+        assert_eq!(value_index.distinct(), 6);
+
+        for (start, end) in [(0, 199), (100, 150), (7, 7), (3, 190)] {
+            for value in [0, 3, 4, 5, 7] {
+                let expected = input[start..=end].iter().filter(|&&item| item == value).count();
+                assert_eq!(value_index.count_eq(start, end, &value)?, expected);
+                assert_eq!(segment_tree.count_eq(start, end, &value)?, expected);
+            }
+        }
+
+        // The index is a snapshot, the tree scan sees updates
+        segment_tree.update(10, 7)?;
+        assert_eq!(value_index.count_eq(0, 199, &7)?, 0);
+        assert_eq!(segment_tree.count_eq(0, 199, &7)?, 1);
+        assert_eq!(value_index.count_eq(5, 200, &7), Err(SegmentTreeError::IndexOutOfBounds { index: 200, len: 200 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_counting_segment_tree() -> Result<(), SegmentTreeError> {
+        let mut items: Vec<isize> = (0..300).map(|i| (i * i) % 7).collect();
+        let mut counting = CountingSegmentTree::new(SegmentTree::new(&items)?);
+
+        // Every kind of write keeps the counts in step with a scan of the current values
+        for step in 0..200usize {
+            match step % 4 {
+                0 => {
+                    let index = step * 13 % items.len();
+                    counting.update(index, (step % 9) as isize)?;
+                    items[index] = (step % 9) as isize;
+                }
+                1 => {
+                    let (first, second) = (step * 7 % items.len(), step * 11 % items.len());
+                    counting.swap(first, second)?;
+                    items.swap(first, second);
+                }
+                2 => {
+                    counting.push((step % 5) as isize)?;
+                    items.push((step % 5) as isize);
+                }
+                _ => assert_eq!(counting.pop(), items.pop()),
+            }
+            for value in 0..9 {
+                let (start, end) = (step % 50, items.len() - 1 - step % 30);
+                let expected = items[start..=end].iter().filter(|&&item| item == value).count();
+                assert_eq!(counting.count_eq(start, end, &value)?, expected);
+                assert_eq!(counting.count_eq(start, end, &value)?, counting.tree.count_eq(start, end, &value)?);
+            }
+        }
+        assert_eq!(counting.value_index().distinct(), 9);
+        assert_eq!(counting.query(0, items.len() - 1)?, items.iter().sum::<isize>());
+        assert!(counting.update(items.len(), 1).is_err());
+        assert!(counting.count_eq(0, items.len(), &1).is_err());
+
+        Ok(())
+    }
+}
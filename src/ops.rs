@@ -0,0 +1,128 @@
+//! Operation Sequences
+//!
+//! `Op` describes one operation on a segment tree, so fuzz targets and tests can generate, store
+//! and replay operation sequences. `Op::sequence_from_bytes` turns raw fuzzer input into a sequence
+//! that is valid for a tree of a given length, the way an `Arbitrary` implementation would.
+
+use crate::{Monoid, RangeModel, SegmentTreeError};
+
+/// Op
+/// One operation on a segment tree
+/// Query: Query the range from `start` to `end`, inclusive
+/// Update: Set the leaf at `index` to `item`
+/// Push: Append an item
+/// Pop: Remove the last leaf
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<T> {
+    Query { start: usize, end: usize },
+    Update { index: usize, item: T },
+    Push(T),
+    Pop,
+}
+
+/// Reader of fuzzer bytes, which reads zeros once the bytes run out
+/// data: Remaining bytes
+struct Bytes<'a> {
+    data: &'a [u8],
+}
+
+impl Bytes<'_> {
+    /// Read one byte
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Read an index below a bound
+    /// bound: Number of valid indices, at least 1
+    fn index(&mut self, bound: usize) -> usize {
+        u16::from_le_bytes([self.byte(), self.byte()]) as usize % bound
+    }
+
+    /// Read an item, in the `i32` range so most sums stay in bounds
+    fn item(&mut self) -> isize {
+        i32::from_le_bytes([self.byte(), self.byte(), self.byte(), self.byte()]) as isize
+    }
+}
+
+impl Op<isize> {
+    /// Decode a sequence of operations from fuzzer bytes
+    /// Every index and range is valid for the tree as the earlier operations leave it
+    /// data: Raw bytes, such as the input of a fuzz target
+    /// len: Number of leaves of the tree the sequence starts on
+    /// Returns the operations, about one per 3 to 6 bytes
+    pub fn sequence_from_bytes(data: &[u8], mut len: usize) -> Vec<Op<isize>> {
+        let mut bytes = Bytes { data };
+        let mut ops = Vec::new();
+        while !bytes.data.is_empty() {
+            let op = match (bytes.byte() % 4, len) {
+                (_, 0) | (2, _) => Op::Push(bytes.item()),
+                (0, _) => {
+                    let (a, b) = (bytes.index(len), bytes.index(len));
+                    Op::Query { start: a.min(b), end: a.max(b) }
+                }
+                (1, _) => Op::Update { index: bytes.index(len), item: bytes.item() },
+                _ => Op::Pop,
+            };
+            match op {
+                Op::Push(_) => len += 1,
+                Op::Pop => len -= 1,
+                _ => {}
+            }
+            ops.push(op);
+        }
+        ops
+    }
+}
+
+impl<T: Clone> Op<T> {
+    /// Apply the operation to a segment tree or any other `RangeModel`, such as `NaiveRangeModel`
+    /// tree: Model to apply the operation to
+    /// Returns the result of a query, `None` for the other operations, or an error
+    pub fn apply<M: Monoid<Item = T>, R: RangeModel<M>>(&self, tree: &mut R) -> Result<Option<M::Output>, SegmentTreeError> {
+        match self {
+            Op::Query { start, end } => return tree.query(*start, *end).map(Some),
+            Op::Update { index, item } => tree.update(*index, item.clone())?,
+            Op::Push(item) => tree.push(item.clone())?,
+            Op::Pop => {
+                tree.pop();
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Op, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_sequence_from_bytes() -> Result<(), SegmentTreeError> {
+        let data: Vec<u8> = (0..2000_u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        let ops = Op::sequence_from_bytes(&data, 3);
+        assert!(ops.len() > 300);
+
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        let mut expected = vec![1, 2, 3];
+        for op in &ops {
+            let result = op.apply(&mut segment_tree)?;
+            match *op {
+                Op::Query { start, end } => assert_eq!(result, Some(expected[start..=end].iter().sum())),
+                Op::Update { index, item } => expected[index] = item,
+                Op::Push(item) => expected.push(item),
+                Op::Pop => {
+                    expected.pop();
+                }
+            }
+        }
+        assert_eq!(segment_tree.leaves(), expected);
+        assert_eq!(Op::sequence_from_bytes(&[3], 0), vec![Op::Push(0)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+//! Overflow Policy
+
+use crate::SegmentTreeError;
+
+/// Overflow Policy
+/// How a segment tree handles results that do not fit in `isize`
+/// Checked: Return `SegmentTreeError::Overflow`
+/// Saturating: Clamp the result to `isize::MIN` or `isize::MAX`
+/// Wrapping: Wrap the result around the bounds of `isize` (two's complement)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    Checked,
+    Saturating,
+    Wrapping,
+}
+
+impl OverflowPolicy {
+    /// Convert a sum accumulated in `i128` to `isize`
+    /// sum: Sum of the range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the converted sum, or an overflow error under the checked policy
+    pub(crate) fn narrow(self, sum: i128, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        match self {
+            OverflowPolicy::Checked => isize::try_from(sum).map_err(|_| SegmentTreeError::Overflow { start, end }),
+            OverflowPolicy::Saturating => Ok(sum.clamp(isize::MIN as i128, isize::MAX as i128) as isize),
+            OverflowPolicy::Wrapping => Ok(sum as isize),
+        }
+    }
+
+    /// Add a delta to a leaf value
+    /// value: Current leaf value
+    /// delta: Value to add
+    /// index: Index of the leaf
+    /// Returns the new leaf value, or an overflow error under the checked policy
+    pub(crate) fn add(self, value: isize, delta: isize, index: usize) -> Result<isize, SegmentTreeError> {
+        match self {
+            OverflowPolicy::Checked => value.checked_add(delta).ok_or(SegmentTreeError::Overflow { start: index, end: index }),
+            OverflowPolicy::Saturating => Ok(value.saturating_add(delta)),
+            OverflowPolicy::Wrapping => Ok(value.wrapping_add(delta)),
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Binary Save and Load
+//!
+//! Header, all integers little endian:
+//! - Magic bytes `SEGT`
+//! - Format version, `u16`
+//! - Overflow policy, `u8`
+//! - Number of leaves, `u64`
+//!
+//! Version 1 follows the header with the leaf values as `i64` each.
+//!
+//! Version 2 follows the header with the byte length of the leaves as `u64` and then the leaf
+//! values as zigzag LEB128 varints, so small values take one byte.
+//!
+//! Version 3, the version written by `save_to`, is version 2 followed by a `u64` FNV-1a checksum of
+//! every byte before it. A mismatch is reported as `SegmentTreeError::ChecksumMismatch`.
+//!
+//! Every older version can still be loaded and is migrated to the current tree on load.
+//! Only the leaves and the configuration are stored, the internal nodes are rebuilt on load.
+
+use crate::{OverflowPolicy, SegmentTree, SegmentTreeError, Sum};
+use std::io::{self, Read, Write};
+
+const MAGIC: [u8; 4] = *b"SEGT";
+
+/// Format version written by `save_to`
+const VERSION: u16 = 3;
+
+/// Length of the header shared by all versions
+const HEADER_LEN: usize = 15;
+
+/// Number of leaves encoded or decoded per read or write call
+const CHUNK_LEAVES: usize = 8192;
+
+/// Longest varint encoding of an `i64`
+const MAX_VARINT_LEN: usize = 10;
+
+/// Build an error for malformed input
+/// error: Description of the problem
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// FNV-1a Hasher
+/// 64-bit FNV-1a hash, fed incrementally as bytes are written or read
+/// state: Hash of the bytes so far
+struct Fnv1a {
+    state: u64,
+}
+
+impl Fnv1a {
+    /// Create a hasher with the FNV offset basis
+    fn new() -> Self {
+        Fnv1a { state: 0xcbf2_9ce4_8422_2325 }
+    }
+
+    /// Hash more bytes
+    /// bytes: Next bytes of the data
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = (self.state ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Map a signed value to an unsigned one with small magnitudes first (0, -1, 1, -2, ...)
+/// value: Value to encode
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Reverse `zigzag`
+/// value: Encoded value
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Get the number of bytes in the varint encoding of a value
+/// value: Unsigned value
+fn varint_len(value: u64) -> usize {
+    (64 - value.max(1).leading_zeros() as usize).div_ceil(7)
+}
+
+/// Append the varint encoding of a value
+/// buffer: Destination of the bytes
+/// value: Unsigned value
+fn push_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Convert a stored leaf value to `isize`
+/// value: Stored value
+fn leaf_value(value: i64) -> io::Result<isize> {
+    isize::try_from(value).map_err(|_| invalid_data(format!("Leaf value {} does not fit in isize", value)))
+}
+
+/// Read the leaves of a version 1 file, stored as fixed-width `i64` values
+/// reader: Source of the bytes, positioned after the header
+/// len: Number of leaves
+fn read_leaves_v1<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<isize>> {
+    // The length is untrusted, so memory is only reserved as leaves actually arrive
+    let mut leaves = Vec::with_capacity(len.min(CHUNK_LEAVES));
+    let mut buffer = vec![0u8; len.min(CHUNK_LEAVES) * 8];
+    while leaves.len() < len {
+        let count = (len - leaves.len()).min(CHUNK_LEAVES);
+        let bytes = &mut buffer[..count * 8];
+        reader.read_exact(bytes)?;
+
+        for value in bytes.chunks_exact(8) {
+            leaves.push(leaf_value(i64::from_le_bytes(value.try_into().unwrap()))?);
+        }
+    }
+
+    Ok(leaves)
+}
+
+/// Read the leaves of a version 2 or 3 file, stored as a byte length followed by zigzag varints
+/// reader: Source of the bytes, positioned after the header
+/// len: Number of leaves
+/// hasher: Hasher fed with every byte read
+fn read_leaves_varint<R: Read>(reader: &mut R, len: usize, hasher: &mut Fnv1a) -> io::Result<Vec<isize>> {
+    let mut byte_len = [0u8; 8];
+    reader.read_exact(&mut byte_len)?;
+    hasher.write(&byte_len);
+    let mut remaining = u64::from_le_bytes(byte_len);
+
+    let mut leaves = Vec::with_capacity(len.min(CHUNK_LEAVES));
+    let mut buffer = vec![0u8; CHUNK_LEAVES];
+    // Varints may span chunks, so the partial value is carried between them
+    let mut value = 0u64;
+    let mut shift = 0;
+    while remaining > 0 {
+        let count = remaining.min(buffer.len() as u64) as usize;
+        let bytes = &mut buffer[..count];
+        reader.read_exact(bytes)?;
+        hasher.write(bytes);
+        remaining -= count as u64;
+
+        for &byte in bytes.iter() {
+            // The tenth byte holds only the top bit of the value
+            if shift == 63 && byte > 1 {
+                return Err(invalid_data("Leaf varint is too long"));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if leaves.len() == len {
+                    return Err(invalid_data("More leaf values than the leaf count"));
+                }
+                leaves.push(leaf_value(unzigzag(value))?);
+                value = 0;
+                shift = 0;
+            }
+        }
+    }
+
+    if shift != 0 || leaves.len() != len {
+        return Err(invalid_data("Leaf values do not match the leaf count"));
+    }
+    Ok(leaves)
+}
+
+/// Implementation of saving and loading the sum segment tree
+impl SegmentTree {
+    /// Write the segment tree in the binary format
+    /// writer: Destination of the bytes, which is written to in large chunks
+    /// Returns `Ok(())` if the tree was written, otherwise the I/O error
+    pub fn save_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let policy: u8 = match self.overflow_policy() {
+            OverflowPolicy::Checked => 0,
+            OverflowPolicy::Saturating => 1,
+            OverflowPolicy::Wrapping => 2,
+        };
+
+        let byte_len: usize = self.leaves.iter().map(|value| varint_len(zigzag(*value as i64))).sum();
+
+        let mut header = Vec::with_capacity(HEADER_LEN + 8);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.push(policy);
+        header.extend_from_slice(&(self.leaves.len() as u64).to_le_bytes());
+        header.extend_from_slice(&(byte_len as u64).to_le_bytes());
+        let mut hasher = Fnv1a::new();
+        hasher.write(&header);
+        writer.write_all(&header)?;
+
+        let mut buffer = Vec::with_capacity(CHUNK_LEAVES.min(self.leaves.len()) * MAX_VARINT_LEN);
+        for chunk in self.leaves.chunks(CHUNK_LEAVES) {
+            buffer.clear();
+            for value in chunk {
+                push_varint(&mut buffer, zigzag(*value as i64));
+            }
+            hasher.write(&buffer);
+            writer.write_all(&buffer)?;
+        }
+        writer.write_all(&hasher.state.to_le_bytes())?;
+
+        writer.flush()
+    }
+
+    /// Read a segment tree written by `save_to` in the current or any older format version
+    /// reader: Source of the bytes, which is read from in large chunks
+    /// Returns the segment tree, or an error of kind `InvalidData` if the bytes are not a valid tree.
+    /// A failed checksum is an `InvalidData` error wrapping `SegmentTreeError::ChecksumMismatch`
+    pub fn load_from<R: Read>(mut reader: R) -> io::Result<SegmentTree> {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+
+        if header[0..4] != MAGIC {
+            return Err(invalid_data("Not a segment tree file"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        let overflow_policy = match header[6] {
+            0 => OverflowPolicy::Checked,
+            1 => OverflowPolicy::Saturating,
+            2 => OverflowPolicy::Wrapping,
+            policy => return Err(invalid_data(format!("Unknown overflow policy {}", policy))),
+        };
+        let len = u64::from_le_bytes(header[7..15].try_into().unwrap());
+        let len = usize::try_from(len).map_err(|_| invalid_data(format!("Leaf count {} is too large", len)))?;
+
+        let leaves = match version {
+            1 => read_leaves_v1(&mut reader, len)?,
+            2 => read_leaves_varint(&mut reader, len, &mut Fnv1a::new())?,
+            3 => {
+                let mut hasher = Fnv1a::new();
+                hasher.write(&header);
+                let leaves = read_leaves_varint(&mut reader, len, &mut hasher)?;
+
+                let mut checksum = [0u8; 8];
+                reader.read_exact(&mut checksum)?;
+                let expected = u64::from_le_bytes(checksum);
+                if expected != hasher.state {
+                    return Err(invalid_data(SegmentTreeError::ChecksumMismatch { expected, actual: hasher.state }));
+                }
+                leaves
+            }
+            version => return Err(invalid_data(format!("Unsupported segment tree format version {}", version))),
+        };
+
+        if leaves.is_empty() {
+            // A tree whose values were all popped is still a valid tree
+            return Ok(SegmentTree::empty(Sum { overflow_policy }));
+        }
+
+        SegmentTree::with_overflow_policy(&leaves, overflow_policy).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OverflowPolicy, SegmentTree, SegmentTreeError};
+    use std::io;
+
+    /// Load a segment tree from bytes that are expected to be rejected
+    /// bytes: Encoded tree
+    /// Returns the kind of the load error
+    fn load_error(bytes: &[u8]) -> io::ErrorKind {
+        SegmentTree::load_from(bytes).expect_err("Invalid bytes were loaded").kind()
+    }
+
+    /// Encode a tree in format version 1
+    /// leaves: Leaf values
+    /// policy: Encoded overflow policy
+    fn version_1(leaves: &[i64], policy: u8) -> Vec<u8> {
+        let mut bytes = b"SEGT".to_vec();
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.push(policy);
+        bytes.extend_from_slice(&(leaves.len() as u64).to_le_bytes());
+        for value in leaves {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<(), SegmentTreeError> {
+        let input = vec![isize::MIN, -2, 3, 40, isize::MAX];
+        let segment_tree = SegmentTree::with_overflow_policy(&input, OverflowPolicy::Saturating)?;
+
+        let mut bytes = Vec::new();
+        segment_tree.save_to(&mut bytes).unwrap();
+        // The extreme values take ten bytes each and the small ones one byte
+        assert_eq!(bytes.len(), 15 + 8 + 2 * 10 + 3 + 8);
+        assert_eq!(&bytes[0..4], b"SEGT");
+
+        let loaded = SegmentTree::load_from(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.leaves(), segment_tree.leaves());
+        assert_eq!(loaded.overflow_policy(), OverflowPolicy::Saturating);
+        assert_eq!(loaded.query(1, 3)?, 41);
+
+        let mut empty = SegmentTree::new(&[1])?;
+        empty.pop();
+        let mut bytes = Vec::new();
+        empty.save_to(&mut bytes).unwrap();
+        let mut loaded = SegmentTree::load_from(bytes.as_slice()).unwrap();
+        assert!(loaded.is_empty());
+        loaded.push(5)?;
+        assert_eq!(loaded.query(0, 0)?, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_older_version() -> Result<(), SegmentTreeError> {
+        let bytes = version_1(&[5, -3, 8, i64::MAX], 2);
+        let mut loaded = SegmentTree::load_from(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.leaves(), &[5, -3, 8, isize::MAX]);
+        assert_eq!(loaded.overflow_policy(), OverflowPolicy::Wrapping);
+        assert_eq!(loaded.query(0, 2)?, 10);
+
+        // Saving migrates the tree to the current version
+        loaded.update(3, 1)?;
+        let mut migrated = Vec::new();
+        loaded.save_to(&mut migrated).unwrap();
+        assert_eq!(u16::from_le_bytes([migrated[4], migrated[5]]), 3);
+        assert_eq!(SegmentTree::load_from(migrated.as_slice()).unwrap(), loaded);
+
+        // Version 2 is version 3 without the checksum
+        migrated[4] = 2;
+        migrated.truncate(migrated.len() - 8);
+        assert_eq!(SegmentTree::load_from(migrated.as_slice()).unwrap(), loaded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_invalid() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3])?;
+        let mut bytes = Vec::new();
+        segment_tree.save_to(&mut bytes).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(load_error(&bad_magic), io::ErrorKind::InvalidData);
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        assert_eq!(load_error(&bad_version), io::ErrorKind::InvalidData);
+
+        let mut bad_policy = bytes.clone();
+        bad_policy[6] = 7;
+        assert_eq!(load_error(&bad_policy), io::ErrorKind::InvalidData);
+
+        // A huge leaf count must fail on the missing bytes, not on allocation
+        let mut huge = bytes.clone();
+        huge[7..15].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(load_error(&huge), io::ErrorKind::InvalidData);
+        let mut huge_v1 = version_1(&[1, 2, 3], 0);
+        huge_v1[7..15].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(load_error(&huge_v1), io::ErrorKind::UnexpectedEof);
+
+        // Varints that run past the byte length or past 64 bits
+        let mut unterminated = bytes.clone();
+        let last_leaf = unterminated.len() - 9;
+        unterminated[last_leaf] |= 0x80;
+        assert_eq!(load_error(&unterminated), io::ErrorKind::InvalidData);
+        let mut too_long = bytes[..15].to_vec();
+        too_long[7..15].copy_from_slice(&1u64.to_le_bytes());
+        too_long.extend_from_slice(&11u64.to_le_bytes());
+        too_long.extend_from_slice(&[0xff; 10]);
+        too_long.push(0);
+        assert_eq!(load_error(&too_long), io::ErrorKind::InvalidData);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(load_error(truncated), io::ErrorKind::UnexpectedEof);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_corrupted() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[10, 20, 30])?;
+        let mut bytes = Vec::new();
+        segment_tree.save_to(&mut bytes).unwrap();
+
+        // A flipped bit that still decodes to a valid tree is caught by the checksum
+        for corrupted_byte in [23, 25, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[corrupted_byte] ^= 0x02;
+            let error = SegmentTree::load_from(corrupted.as_slice()).expect_err("Corrupted bytes were loaded");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            let error = error.get_ref().and_then(|error| error.downcast_ref::<SegmentTreeError>());
+            assert!(matches!(error, Some(SegmentTreeError::ChecksumMismatch { .. })));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+//! Persistent Lazy Segment Trees
+
+use crate::{ActedMonoid, SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Link of a leaf to its missing children. The root of the first version is built first, at index 0,
+/// and is never a child, so its index is free to mean none
+const NO_CHILD: usize = 0;
+
+/// Persistent Lazy Node
+/// Node of a persistent lazy segment tree, stored in the arena of its tree and never changed once a
+/// version points at it
+/// value: Aggregate of the range covered by the node, with its pending map applied
+/// map: Pending map of the node, not yet applied to its children
+/// left: Arena index of the left child, or `NO_CHILD` for a leaf
+/// right: Arena index of the right child, or `NO_CHILD` for a leaf
+#[derive(Debug, Clone)]
+struct PersistentLazyNode<V, F> {
+    value: V,
+    map: F,
+    left: usize,
+    right: usize,
+}
+
+/// Persistent Lazy Segment Tree
+/// Lazy segment tree whose range updates create a new version instead of changing the tree, so every
+/// earlier version stays queryable. An update copies the O(log n) nodes on the paths it changes,
+/// including the children its pending maps are pushed into, and shares every other node with the
+/// version it was applied to. Versions are numbered from 0, the version built from the input.
+/// nodes: Arena of nodes shared by every version
+/// roots: Arena index of the root of each version
+/// len: Number of leaves
+/// monoid: Operation used to combine values and apply maps
+#[derive(Debug, Clone)]
+pub struct PersistentLazySegmentTree<A: ActedMonoid> {
+    nodes: Vec<PersistentLazyNode<A::Value, A::Map>>,
+    roots: Vec<usize>,
+    len: usize,
+    monoid: A,
+}
+
+impl<A: ActedMonoid> PersistentLazySegmentTree<A> {
+    /// Create a new persistent lazy segment tree, whose values are version 0
+    /// input: Vector of input values
+    /// monoid: Operation used to combine values and apply maps
+    /// Returns a new `PersistentLazySegmentTree` structure or an error
+    pub fn new(input: &[A::Item], monoid: A) -> Result<PersistentLazySegmentTree<A>, SegmentTreeError> {
+        if input.is_empty() {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+        if input.len() > MAX_INPUT_SIZE {
+            return Err(SegmentTreeError::InputTooLarge { len: input.len(), max: MAX_INPUT_SIZE });
+        }
+        for (index, item) in input.iter().enumerate() {
+            monoid.validate(item, index)?;
+        }
+
+        let mut segment_tree = PersistentLazySegmentTree {
+            nodes: Vec::with_capacity(2 * input.len() - 1),
+            roots: Vec::new(),
+            len: input.len(),
+            monoid,
+        };
+        let root = segment_tree.build_recursive(0, input.len() - 1, input);
+        segment_tree.roots.push(root);
+        Ok(segment_tree)
+    }
+
+    /// Build the nodes of a range, parents before their children (Recursive)
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// input: Vector of input values
+    /// Returns the arena index of the node
+    fn build_recursive(&mut self, lo: usize, hi: usize, input: &[A::Item]) -> usize {
+        let node_idx = self.nodes.len();
+        self.nodes.push(PersistentLazyNode {
+            value: self.monoid.lift(&input[lo]),
+            map: self.monoid.identity_map(),
+            left: NO_CHILD,
+            right: NO_CHILD,
+        });
+        if lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            self.nodes[node_idx].left = self.build_recursive(lo, mid, input);
+            self.nodes[node_idx].right = self.build_recursive(mid + 1, hi, input);
+            self.update_node(node_idx);
+        }
+        node_idx
+    }
+
+    /// Number of values in each version
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the versions hold no values, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the monoid of the segment tree
+    pub fn monoid(&self) -> &A {
+        &self.monoid
+    }
+
+    /// Get the number of versions, so the latest version is one less
+    pub fn versions(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Get the number of nodes allocated in the arena, shared by every version
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Get the root of a version
+    /// version: Version number
+    /// Returns the arena index of the root, or an error if there is no such version
+    fn root(&self, version: usize) -> Result<usize, SegmentTreeError> {
+        self.roots.get(version).copied().ok_or(SegmentTreeError::VersionNotFound { version, versions: self.roots.len() })
+    }
+
+    /// Validate a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns `Ok(())` if the range is valid, otherwise an error
+    fn validate_range(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        Ok(())
+    }
+
+    /// Recompute a node from its children
+    /// node_idx: Arena index of an internal node
+    fn update_node(&mut self, node_idx: usize) {
+        let node = &self.nodes[node_idx];
+        self.nodes[node_idx].value = self.monoid.combine(&self.nodes[node.left].value, &self.nodes[node.right].value);
+    }
+
+    /// Copy a node into a new node owned by the version being built
+    /// node_idx: Arena index of the node to copy
+    /// Returns the arena index of the copy
+    fn copy_node(&mut self, node_idx: usize) -> usize {
+        self.nodes.push(self.nodes[node_idx].clone());
+        self.nodes.len() - 1
+    }
+
+    /// Apply a map to a node and record it as pending for the children
+    /// node_idx: Arena index of a node owned by the version being built
+    /// map: Map to apply
+    /// len: Number of leaves covered by the node
+    fn apply_node(&mut self, node_idx: usize, map: &A::Map, len: usize) {
+        let node = &mut self.nodes[node_idx];
+        node.value = self.monoid.apply(map, &node.value, len);
+        node.map = self.monoid.compose(map, &node.map);
+    }
+
+    /// Push the pending map of an internal node into copies of its children, which the node then links to
+    /// The children of earlier versions are shared, so they are never changed
+    /// node_idx: Arena index of an internal node owned by the version being built
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    fn push_down(&mut self, node_idx: usize, lo: usize, hi: usize) {
+        let map = std::mem::replace(&mut self.nodes[node_idx].map, self.monoid.identity_map());
+        let mid = lo + (hi - lo) / 2;
+        let left = self.copy_node(self.nodes[node_idx].left);
+        let right = self.copy_node(self.nodes[node_idx].right);
+        self.apply_node(left, &map, mid - lo + 1);
+        self.apply_node(right, &map, hi - mid);
+        self.nodes[node_idx].left = left;
+        self.nodes[node_idx].right = right;
+    }
+
+    /// Apply a map to every value in a range of a version, as a new version
+    /// version: Version to apply the map to, which is left unchanged
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    /// Returns the number of the new version, or an error
+    pub fn apply_range(&mut self, version: usize, start: usize, end: usize, map: A::Map) -> Result<usize, SegmentTreeError> {
+        let root = self.root(version)?;
+        self.validate_range(start, end)?;
+
+        let root = self.copy_node(root);
+        self.apply_range_recursive(root, 0, self.len - 1, start, end, &map);
+        self.roots.push(root);
+        Ok(self.roots.len() - 1)
+    }
+
+    /// Apply a map to the nodes covering a range (Recursive)
+    /// node_idx: Arena index of a node owned by the version being built, overlapping the range
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    fn apply_range_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize, map: &A::Map) {
+        if start <= lo && hi <= end {
+            self.apply_node(node_idx, map, hi - lo + 1);
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        if start <= mid {
+            self.apply_range_recursive(self.nodes[node_idx].left, lo, mid, start, end, map);
+        }
+        if mid < end {
+            self.apply_range_recursive(self.nodes[node_idx].right, mid + 1, hi, start, end, map);
+        }
+        self.update_node(node_idx);
+    }
+
+    /// Update the value at an index of a version, as a new version
+    /// version: Version to update, which is left unchanged
+    /// index: Index of the value
+    /// new_value: New value
+    /// Returns the number of the new version, or an error
+    pub fn update(&mut self, version: usize, index: usize, new_value: A::Item) -> Result<usize, SegmentTreeError> {
+        let root = self.root(version)?;
+        if index >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.len });
+        }
+        self.monoid.validate(&new_value, index)?;
+
+        let value = self.monoid.lift(&new_value);
+        let root = self.copy_node(root);
+        self.update_recursive(root, 0, self.len - 1, index, value);
+        self.roots.push(root);
+        Ok(self.roots.len() - 1)
+    }
+
+    /// Write a leaf value, pushing pending maps out of its path first (Recursive)
+    /// node_idx: Arena index of a node owned by the version being built, covering the index
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// index: Index of the value
+    /// value: Lifted new value
+    fn update_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, index: usize, value: A::Value) {
+        if lo == hi {
+            self.nodes[node_idx].value = value;
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        if index <= mid {
+            self.update_recursive(self.nodes[node_idx].left, lo, mid, index, value);
+        } else {
+            self.update_recursive(self.nodes[node_idx].right, mid + 1, hi, index, value);
+        }
+        self.update_node(node_idx);
+    }
+
+    /// Query a range of a version
+    /// Pending maps are applied to the partial results on the way up, so querying allocates nothing
+    /// version: Version to query
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&self, version: usize, start: usize, end: usize) -> Result<A::Output, SegmentTreeError> {
+        let root = self.root(version)?;
+        self.validate_range(start, end)?;
+
+        let value = self.query_recursive(root, 0, self.len - 1, start, end).unwrap_or_else(|| self.monoid.identity());
+        self.monoid.finish(value, start, end)
+    }
+
+    /// Aggregate the part of a range below a node, from left to right (Recursive)
+    /// node_idx: Arena index of the current node
+    /// lo: First leaf covered by the node
+    /// hi: Last leaf covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the aggregate, or `None` if the node does not overlap the range
+    fn query_recursive(&self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize) -> Option<A::Value> {
+        if end < lo || hi < start {
+            return None;
+        }
+        let node = &self.nodes[node_idx];
+        if start <= lo && hi <= end {
+            return Some(node.value.clone());
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        let left = self.query_recursive(node.left, lo, mid, start, end);
+        let right = self.query_recursive(node.right, mid + 1, hi, start, end);
+        let value = match (left, right) {
+            (Some(mut left), Some(right)) => {
+                self.monoid.combine_into(&mut left, &right);
+                left
+            }
+            (Some(value), None) | (None, Some(value)) => value,
+            (None, None) => return None,
+        };
+
+        // The pending map of this node has not reached the children yet
+        let covered = end.min(hi) - start.max(lo) + 1;
+        Some(self.monoid.apply(&node.map, &value, covered))
+    }
+
+    /// Get the value at an index of a version
+    /// version: Version to read
+    /// index: Index of the value
+    /// Returns the result for the single index, or an error
+    pub fn get(&self, version: usize, index: usize) -> Result<A::Output, SegmentTreeError> {
+        self.query(version, index, index)
+    }
+
+    /// Get the result of a whole version in O(1), from its root node
+    /// version: Version to read
+    pub fn total(&self, version: usize) -> Result<A::Output, SegmentTreeError> {
+        let root = self.root(version)?;
+        self.monoid.finish(self.nodes[root].value.clone(), 0, self.len - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PersistentLazySegmentTree, RangeAffineRangeSum, SegmentTreeError};
+
+    #[test]
+    fn test_persistent_lazy_tree() -> Result<(), SegmentTreeError> {
+        let len = 37;
+        let input: Vec<isize> = (0..len as isize).map(|i| (i * 17) % 13 - 6).collect();
+        let mut segment_tree = PersistentLazySegmentTree::new(&input, RangeAffineRangeSum::default())?;
+
+        // Each version is built from an earlier one, and every version is checked against its own copy
+        let mut history = vec![input.clone()];
+        let mut seed = 99u64;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for step in 0..60 {
+            let base = next(history.len());
+            let (a, b) = (next(len), next(len));
+            let (start, end) = (a.min(b), a.max(b));
+            let mut items = history[base].clone();
+            let version = if step % 4 == 3 {
+                items[a] = step as isize;
+                segment_tree.update(base, a, step as isize)?
+            } else {
+                let (mul, add) = (step as isize % 3 - 1, step as isize % 5);
+                items[start..=end].iter_mut().for_each(|item| *item = mul * *item + add);
+                segment_tree.apply_range(base, start, end, Some((mul as i128, add as i128)))?
+            };
+            assert_eq!(version, history.len());
+            history.push(items);
+
+            for (version, items) in history.iter().enumerate() {
+                let (start, end) = (step % len, (step * 7 + 5) % len);
+                let (start, end) = (start.min(end), start.max(end));
+                assert_eq!(segment_tree.query(version, start, end)?, items[start..=end].iter().sum());
+                assert_eq!(segment_tree.total(version)?, items.iter().sum());
+            }
+        }
+
+        // Every update only copies the nodes along the edges of its range
+        assert!(segment_tree.node_count() <= 2 * len - 1 + 60 * (4 * 6 + 1));
+        assert_eq!(segment_tree.versions(), 61);
+        assert_eq!(segment_tree.get(0, 3)?, input[3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_persistent_lazy_errors() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = PersistentLazySegmentTree::new(&[1, 2, 3], RangeAffineRangeSum::default())?;
+        assert_eq!(segment_tree.query(1, 0, 2), Err(SegmentTreeError::VersionNotFound { version: 1, versions: 1 }));
+        assert_eq!(segment_tree.apply_range(0, 2, 1, None), Err(SegmentTreeError::InvalidRange { start: 2, end: 1, len: 3 }));
+        assert_eq!(segment_tree.update(0, 3, 1), Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }));
+        assert_eq!(segment_tree.versions(), 1);
+        assert!(PersistentLazySegmentTree::new(&[], RangeAffineRangeSum::default()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Static 2D Point Sets
+//!
+//! Merge sort trees over points sorted by x: every node holds the y coordinates of the points in
+//! its range, sorted, so a rectangle splits into O(log n) nodes that are each binary searched.
+
+use crate::{SegmentTreeError, MAX_INPUT_SIZE};
+
+/// Call a function on every node covering a range of leaves of a bottom-up tree with `len` leaves
+/// len: Number of leaves, stored at nodes `len..2 * len`
+/// start: First leaf of the range
+/// end: Leaf past the end of the range
+/// f: Function to call with the index of each node
+pub(crate) fn for_each_node(len: usize, start: usize, end: usize, mut f: impl FnMut(usize)) {
+    let (mut lo, mut hi) = (start + len, end + len);
+    while lo < hi {
+        if lo % 2 == 1 {
+            f(lo);
+            lo += 1;
+        }
+        if hi % 2 == 1 {
+            hi -= 1;
+            f(hi);
+        }
+        lo /= 2;
+        hi /= 2;
+    }
+}
+
+/// Merge two sorted lists
+/// left: First sorted list
+/// right: Second sorted list
+/// Returns the sorted list of both
+fn merge<T: Copy + Ord>(left: &[T], right: &[T]) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if left[i] <= right[j] {
+            merged.push(left[i]);
+            i += 1;
+        } else {
+            merged.push(right[j]);
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    merged
+}
+
+/// Build the sorted lists of a bottom-up tree from its leaves
+/// leaves: Item of each leaf
+/// Returns the list of every node, with the leaves at `len..2 * len` and node 0 unused
+pub(crate) fn build_lists<T: Copy + Ord>(leaves: impl ExactSizeIterator<Item = T>) -> Vec<Vec<T>> {
+    let len = leaves.len();
+    let mut lists = vec![Vec::new(); 2 * len];
+    for (list, item) in lists[len..].iter_mut().zip(leaves) {
+        list.push(item);
+    }
+    for node_idx in (1..len).rev() {
+        lists[node_idx] = merge(&lists[2 * node_idx], &lists[2 * node_idx + 1]);
+    }
+    lists
+}
+
+/// Validate the number of points
+/// len: Number of points
+/// Returns `Ok(())` if a point set can hold the points, otherwise an error
+pub(crate) fn validate_len(len: usize) -> Result<(), SegmentTreeError> {
+    if len == 0 {
+        return Err(SegmentTreeError::EmptyInput);
+    }
+    if len > MAX_INPUT_SIZE {
+        return Err(SegmentTreeError::InputTooLarge { len, max: MAX_INPUT_SIZE });
+    }
+
+    Ok(())
+}
+
+/// Point Set 2D
+/// Static set of points that counts the points in an axis-aligned rectangle in O(log² n),
+/// using O(n log n) memory. Suited to sparse points that would not fit in a dense grid.
+/// xs: X coordinates of the points, sorted
+/// ys: Sorted y coordinates of the points below each node of a bottom-up tree, with the leaves at `len..2 * len`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointSet2D {
+    xs: Vec<isize>,
+    ys: Vec<Vec<isize>>,
+}
+
+impl PointSet2D {
+    /// Create a point set in O(n log n)
+    /// points: `(x, y)` coordinates of the points, which may repeat
+    /// Returns a new `PointSet2D` structure or an error
+    pub fn new(points: &[(isize, isize)]) -> Result<PointSet2D, SegmentTreeError> {
+        validate_len(points.len())?;
+
+        let mut sorted = points.to_vec();
+        sorted.sort_unstable();
+        let ys = build_lists(sorted.iter().map(|&(_, y)| y));
+        Ok(PointSet2D { xs: sorted.into_iter().map(|(x, _)| x).collect(), ys })
+    }
+
+    /// Get the number of points
+    pub fn len(&self) -> usize {
+        self.xs.len()
+    }
+
+    /// Returns `true` if the set holds no points, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.xs.is_empty()
+    }
+
+    /// Count the points in a rectangle, bounds included
+    /// A rectangle whose start is past its end in either coordinate is empty
+    /// x_start: Smallest x coordinate
+    /// y_start: Smallest y coordinate
+    /// x_end: Largest x coordinate
+    /// y_end: Largest y coordinate
+    /// Returns the number of points in the rectangle
+    pub fn count(&self, x_start: isize, y_start: isize, x_end: isize, y_end: isize) -> usize {
+        if x_start > x_end || y_start > y_end {
+            return 0;
+        }
+
+        let start = self.xs.partition_point(|&x| x < x_start);
+        let end = self.xs.partition_point(|&x| x <= x_end);
+        let mut count = 0;
+        for_each_node(self.len(), start, end, |node_idx| {
+            let ys = &self.ys[node_idx];
+            count += ys.partition_point(|&y| y <= y_end) - ys.partition_point(|&y| y < y_start);
+        });
+        count
+    }
+}
+
+/// Weighted Point Set 2D
+/// Static set of weighted points that sums the weights of the points in an axis-aligned rectangle
+/// in O(log² n), such as the weights of the points with x in `[a, b]` and y at most `c`.
+/// Every node of the merge sort tree keeps prefix sums of the weights in y order, so each node
+/// answers with two binary searches. Sums are `i128`, so they never overflow.
+/// xs: X coordinates of the points, sorted
+/// ys: Sorted y coordinates of the points below each node of a bottom-up tree, with the leaves at `len..2 * len`
+/// prefix_weights: Sums of the weights of the first k points of each node in y order, for k from 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedPointSet2D {
+    xs: Vec<isize>,
+    ys: Vec<Vec<isize>>,
+    prefix_weights: Vec<Vec<i128>>,
+}
+
+impl WeightedPointSet2D {
+    /// Create a weighted point set in O(n log n)
+    /// points: `(x, y, weight)` of the points, whose coordinates may repeat
+    /// Returns a new `WeightedPointSet2D` structure or an error
+    pub fn new(points: &[(isize, isize, isize)]) -> Result<WeightedPointSet2D, SegmentTreeError> {
+        validate_len(points.len())?;
+
+        let mut sorted = points.to_vec();
+        sorted.sort_unstable();
+        let lists = build_lists(sorted.iter().map(|&(_, y, weight)| (y, weight)));
+
+        let ys = lists.iter().map(|list| list.iter().map(|&(y, _)| y).collect()).collect();
+        let prefix_weights = lists.iter()
+            .map(|list| {
+                std::iter::once(0)
+                    .chain(list.iter().scan(0, |sum, &(_, weight)| {
+                        *sum += weight as i128;
+                        Some(*sum)
+                    }))
+                    .collect()
+            })
+            .collect();
+        Ok(WeightedPointSet2D { xs: sorted.into_iter().map(|(x, _, _)| x).collect(), ys, prefix_weights })
+    }
+
+    /// Get the number of points
+    pub fn len(&self) -> usize {
+        self.xs.len()
+    }
+
+    /// Returns `true` if the set holds no points, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.xs.is_empty()
+    }
+
+    /// Sum the weights of the points in a rectangle, bounds included
+    /// Use `isize::MIN` or `isize::MAX` for an unbounded side, e.g. `sum(a, isize::MIN, b, c)` for y at most c.
+    /// A rectangle whose start is past its end in either coordinate is empty.
+    /// x_start: Smallest x coordinate
+    /// y_start: Smallest y coordinate
+    /// x_end: Largest x coordinate
+    /// y_end: Largest y coordinate
+    /// Returns the sum of the weights in the rectangle
+    pub fn sum(&self, x_start: isize, y_start: isize, x_end: isize, y_end: isize) -> i128 {
+        if x_start > x_end || y_start > y_end {
+            return 0;
+        }
+
+        let start = self.xs.partition_point(|&x| x < x_start);
+        let end = self.xs.partition_point(|&x| x <= x_end);
+        let mut sum = 0;
+        for_each_node(self.len(), start, end, |node_idx| {
+            let (ys, prefix_weights) = (&self.ys[node_idx], &self.prefix_weights[node_idx]);
+            sum += prefix_weights[ys.partition_point(|&y| y <= y_end)] - prefix_weights[ys.partition_point(|&y| y < y_start)];
+        });
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PointSet2D, SegmentTreeError, WeightedPointSet2D};
+
+    #[test]
+    fn test_point_set_count() -> Result<(), SegmentTreeError> {
+        let points: Vec<(isize, isize)> = (0..50).map(|i| ((i * 37) % 23 - 11, (i * 53) % 19 - 9)).collect();
+        let point_set = PointSet2D::new(&points)?;
+        assert_eq!(point_set.len(), 50);
+
+        for x_start in -12..12 {
+            for x_end in x_start - 1..12 {
+                for (y_start, y_end) in [(-10, 10), (-3, 4), (0, 0), (5, -5), (isize::MIN, isize::MAX)] {
+                    let expected = points.iter()
+                        .filter(|&&(x, y)| x_start <= x && x <= x_end && y_start <= y && y <= y_end)
+                        .count();
+                    assert_eq!(point_set.count(x_start, y_start, x_end, y_end), expected);
+                }
+            }
+        }
+
+        assert_eq!(PointSet2D::new(&[]), Err(SegmentTreeError::EmptyInput));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_point_set_sum() -> Result<(), SegmentTreeError> {
+        let points: Vec<(isize, isize, isize)> = (0..40).map(|i| ((i * 17) % 13, (i * 29) % 11, i - 20)).collect();
+        let point_set = WeightedPointSet2D::new(&points)?;
+
+        for x_start in 0..13 {
+            for x_end in x_start..13 {
+                for y_end in -1..12 {
+                    let expected: isize = points.iter()
+                        .filter(|&&(x, y, _)| x_start <= x && x <= x_end && y <= y_end)
+                        .map(|&(_, _, weight)| weight)
+                        .sum();
+                    assert_eq!(point_set.sum(x_start, isize::MIN, x_end, y_end), expected as i128);
+                }
+            }
+        }
+        assert_eq!(point_set.sum(0, 3, 12, 5), points.iter().filter(|&&(_, y, _)| (3..=5).contains(&y)).map(|&(_, _, weight)| weight as i128).sum());
+
+        let point_set = WeightedPointSet2D::new(&[(0, 0, isize::MAX), (0, 0, isize::MAX)])?;
+        assert_eq!(point_set.sum(0, 0, 0, 0), 2 * isize::MAX as i128);
+
+        Ok(())
+    }
+}
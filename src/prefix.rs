@@ -0,0 +1,93 @@
+//! Prefix and Suffix Queries
+
+use crate::{Monoid, SegmentTree, SegmentTreeError};
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Query the prefix ending at an index, the same as `query(0, end)`
+    /// Walks a single root-to-leaf path, combining the left child of every node where the path goes right
+    /// end: Last index of the prefix
+    /// Returns the result of the prefix, or an error if `end` is out of bounds
+    pub fn prefix(&self, end: usize) -> Result<M::Output, SegmentTreeError> {
+        self.validate_index(end)?;
+
+        let mut value: Option<M::Value> = None;
+        let mut node_idx = 0;
+        while let Some((left, right)) = self.children(node_idx) {
+            if end <= self.nodes[left].end {
+                node_idx = left;
+                continue;
+            }
+
+            match &mut value {
+                Some(value) => self.monoid.combine_into(value, &self.nodes[left].value),
+                None => value = Some(self.nodes[left].value.clone()),
+            }
+            node_idx = right;
+        }
+
+        let leaf = &self.nodes[node_idx].value;
+        let value = match value {
+            Some(mut value) => {
+                self.monoid.combine_into(&mut value, leaf);
+                value
+            }
+            None => leaf.clone(),
+        };
+        self.monoid.finish(value, 0, end)
+    }
+
+    /// Query the suffix starting at an index, the same as `query(start, len - 1)`
+    /// Walks from the leaf up to the root, combining the right sibling of every left child on the way.
+    /// Right siblings past the last leaf only hold padding, which is the identity.
+    /// start: First index of the suffix
+    /// Returns the result of the suffix, or an error if `start` is out of bounds
+    pub fn suffix(&self, start: usize) -> Result<M::Output, SegmentTreeError> {
+        self.validate_index(start)?;
+
+        let mut node_idx = self.leaf_indices[start];
+        let mut value = self.nodes[node_idx].value.clone();
+        while node_idx > 0 {
+            // Left children have odd indices, and their right sibling follows them
+            if node_idx % 2 == 1 {
+                self.monoid.combine_into(&mut value, &self.nodes[node_idx + 1].value);
+            }
+            node_idx = (node_idx - 1) / 2;
+        }
+
+        self.monoid.finish(value, start, self.leaf_len - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FnMonoid, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_prefix_and_suffix() -> Result<(), SegmentTreeError> {
+        let input: Vec<isize> = (1..=11).collect();
+        let segment_tree = SegmentTree::new(&input)?;
+
+        for index in 0..input.len() {
+            assert_eq!(segment_tree.prefix(index)?, segment_tree.query(0, index)?);
+            assert_eq!(segment_tree.suffix(index)?, segment_tree.query(index, input.len() - 1)?);
+        }
+
+        assert_eq!(segment_tree.prefix(11), Err(SegmentTreeError::IndexOutOfBounds { index: 11, len: 11 }));
+        assert_eq!(segment_tree.suffix(11), Err(SegmentTreeError::IndexOutOfBounds { index: 11, len: 11 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_and_suffix_order() -> Result<(), SegmentTreeError> {
+        let monoid = FnMonoid::new(String::new(), |left: &String, right: &String| format!("{}{}", left, right));
+        let input: Vec<String> = "abcdefg".chars().map(String::from).collect();
+        let segment_tree = SegmentTree::with_monoid(&input, monoid)?;
+
+        assert_eq!(segment_tree.prefix(4)?, "abcde");
+        assert_eq!(segment_tree.suffix(2)?, "cdefg");
+        assert_eq!(segment_tree.suffix(6)?, "g");
+
+        Ok(())
+    }
+}
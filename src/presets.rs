@@ -0,0 +1,630 @@
+//! Lazy Presets
+//!
+//! Ready-made `ActedMonoid` implementations for `LazySegmentTree`, with the maps and their
+//! composition already worked out.
+
+use crate::{ActedMonoid, LazySegmentTree, Monoid, OverflowPolicy, SegmentTreeError};
+
+/// Add two sums under an overflow policy
+/// Returns the sum, or `None` if either sum has overflowed or the result overflows `i128` under the
+/// checked policy, so an overflow reaches every query that includes it
+fn add(overflow_policy: OverflowPolicy, left: Option<i128>, right: Option<i128>) -> Option<i128> {
+    let (left, right) = (left?, right?);
+    match overflow_policy {
+        OverflowPolicy::Checked => left.checked_add(right),
+        OverflowPolicy::Saturating => Some(left.saturating_add(right)),
+        OverflowPolicy::Wrapping => Some(left.wrapping_add(right)),
+    }
+}
+
+/// Multiply two values under an overflow policy
+/// Returns the product, or `None` if either value has overflowed or the result overflows `i128` under
+/// the checked policy
+fn mul(overflow_policy: OverflowPolicy, left: Option<i128>, right: Option<i128>) -> Option<i128> {
+    let (left, right) = (left?, right?);
+    match overflow_policy {
+        OverflowPolicy::Checked => left.checked_mul(right),
+        OverflowPolicy::Saturating => Some(left.saturating_mul(right)),
+        OverflowPolicy::Wrapping => Some(left.wrapping_mul(right)),
+    }
+}
+
+/// Convert a sum to `isize` under an overflow policy
+/// Returns the converted sum, or an overflow error if the sum has overflowed under the checked policy
+fn finish(overflow_policy: OverflowPolicy, sum: Option<i128>, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+    overflow_policy.narrow(sum.ok_or(SegmentTreeError::Overflow { start, end })?, start, end)
+}
+
+/// Range Multiply Range Sum
+/// Sum of `isize` leaves, with maps multiplying every value of a range by a factor.
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeMulRangeSum {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for RangeMulRangeSum {
+    type Item = isize;
+    /// `None` once the sum has overflowed under the checked policy
+    type Value = Option<i128>;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> Option<i128> {
+        Some(0)
+    }
+
+    fn lift(&self, item: &isize) -> Option<i128> {
+        Some(*item as i128)
+    }
+
+    fn combine(&self, left: &Option<i128>, right: &Option<i128>) -> Option<i128> {
+        add(self.overflow_policy, *left, *right)
+    }
+
+    fn finish(&self, value: Option<i128>, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        finish(self.overflow_policy, value, start, end)
+    }
+}
+
+impl ActedMonoid for RangeMulRangeSum {
+    /// Factor, or `None` once the composed factor has overflowed under the checked policy
+    type Map = Option<i128>;
+
+    fn identity_map(&self) -> Option<i128> {
+        Some(1)
+    }
+
+    fn apply(&self, map: &Option<i128>, value: &Option<i128>, _len: usize) -> Option<i128> {
+        mul(self.overflow_policy, *value, *map)
+    }
+
+    fn compose(&self, outer: &Option<i128>, inner: &Option<i128>) -> Option<i128> {
+        mul(self.overflow_policy, *outer, *inner)
+    }
+}
+
+impl LazySegmentTree<RangeMulRangeSum> {
+    /// Multiply every value in a range by a factor
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// factor: Factor to multiply by
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_mul(&mut self, start: usize, end: usize, factor: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, Some(factor as i128))
+    }
+}
+
+/// Range Add Range Sum
+/// Sum of `isize` leaves, with maps adding a delta to every value of a range
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAddRangeSum {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for RangeAddRangeSum {
+    type Item = isize;
+    /// `None` once the sum has overflowed under the checked policy
+    type Value = Option<i128>;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> Option<i128> {
+        Some(0)
+    }
+
+    fn lift(&self, item: &isize) -> Option<i128> {
+        Some(*item as i128)
+    }
+
+    fn combine(&self, left: &Option<i128>, right: &Option<i128>) -> Option<i128> {
+        add(self.overflow_policy, *left, *right)
+    }
+
+    fn finish(&self, value: Option<i128>, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        finish(self.overflow_policy, value, start, end)
+    }
+}
+
+impl ActedMonoid for RangeAddRangeSum {
+    type Map = i128;
+
+    fn identity_map(&self) -> i128 {
+        0
+    }
+
+    fn apply(&self, map: &i128, value: &Option<i128>, len: usize) -> Option<i128> {
+        add(self.overflow_policy, *value, mul(self.overflow_policy, Some(*map), Some(len as i128)))
+    }
+
+    fn compose(&self, outer: &i128, inner: &i128) -> i128 {
+        // Deltas are `isize`, so a composed delta only reaches the `i128` bounds after 2^63 updates
+        match self.overflow_policy {
+            OverflowPolicy::Wrapping => outer.wrapping_add(*inner),
+            _ => outer.saturating_add(*inner),
+        }
+    }
+}
+
+impl LazySegmentTree<RangeAddRangeSum> {
+    /// Add a delta to every value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_add(&mut self, start: usize, end: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, delta as i128)
+    }
+}
+
+/// Range Add Range Min
+/// Minimum of `isize` leaves, with maps adding a delta to every value of a range
+/// overflow_policy: How minimums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAddRangeMin {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for RangeAddRangeMin {
+    type Item = isize;
+    type Value = i128;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> i128 {
+        i128::MAX
+    }
+
+    fn lift(&self, item: &isize) -> i128 {
+        *item as i128
+    }
+
+    fn combine(&self, left: &i128, right: &i128) -> i128 {
+        *left.min(right)
+    }
+
+    fn finish(&self, value: i128, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        self.overflow_policy.narrow(value, start, end)
+    }
+}
+
+impl ActedMonoid for RangeAddRangeMin {
+    type Map = i128;
+
+    fn identity_map(&self) -> i128 {
+        0
+    }
+
+    fn apply(&self, map: &i128, value: &i128, _len: usize) -> i128 {
+        // A minimum is a single leaf, so it only reaches the `i128` bounds after 2^63 updates, and
+        // `finish` still reports or clamps values outside `isize`
+        match self.overflow_policy {
+            OverflowPolicy::Wrapping => value.wrapping_add(*map),
+            _ => value.saturating_add(*map),
+        }
+    }
+
+    fn compose(&self, outer: &i128, inner: &i128) -> i128 {
+        match self.overflow_policy {
+            OverflowPolicy::Wrapping => outer.wrapping_add(*inner),
+            _ => outer.saturating_add(*inner),
+        }
+    }
+}
+
+impl LazySegmentTree<RangeAddRangeMin> {
+    /// Add a delta to every value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_add(&mut self, start: usize, end: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, delta as i128)
+    }
+}
+
+/// Range Assign Range Sum
+/// Sum of `isize` leaves, with maps setting every value of a range to the same value
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAssignRangeSum {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for RangeAssignRangeSum {
+    type Item = isize;
+    /// `None` once the sum has overflowed under the checked policy
+    type Value = Option<i128>;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> Option<i128> {
+        Some(0)
+    }
+
+    fn lift(&self, item: &isize) -> Option<i128> {
+        Some(*item as i128)
+    }
+
+    fn combine(&self, left: &Option<i128>, right: &Option<i128>) -> Option<i128> {
+        add(self.overflow_policy, *left, *right)
+    }
+
+    fn finish(&self, value: Option<i128>, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        finish(self.overflow_policy, value, start, end)
+    }
+}
+
+impl ActedMonoid for RangeAssignRangeSum {
+    /// Value to assign, or `None` to leave the values unchanged
+    type Map = Option<isize>;
+
+    fn identity_map(&self) -> Option<isize> {
+        None
+    }
+
+    fn apply(&self, map: &Option<isize>, value: &Option<i128>, len: usize) -> Option<i128> {
+        match map {
+            Some(assigned) => mul(self.overflow_policy, Some(*assigned as i128), Some(len as i128)),
+            None => *value,
+        }
+    }
+
+    fn compose(&self, outer: &Option<isize>, inner: &Option<isize>) -> Option<isize> {
+        outer.or(*inner)
+    }
+}
+
+impl LazySegmentTree<RangeAssignRangeSum> {
+    /// Set every value in a range to the same value
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Value to assign
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_assign(&mut self, start: usize, end: usize, value: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, Some(value))
+    }
+}
+
+/// Range Affine Range Sum
+/// Sum of `isize` leaves, with maps replacing every value x of a range by `mul * x + add`
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAffineRangeSum {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for RangeAffineRangeSum {
+    type Item = isize;
+    /// `None` once the sum has overflowed under the checked policy
+    type Value = Option<i128>;
+    type Output = isize;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> Option<i128> {
+        Some(0)
+    }
+
+    fn lift(&self, item: &isize) -> Option<i128> {
+        Some(*item as i128)
+    }
+
+    fn combine(&self, left: &Option<i128>, right: &Option<i128>) -> Option<i128> {
+        add(self.overflow_policy, *left, *right)
+    }
+
+    fn finish(&self, value: Option<i128>, start: usize, end: usize) -> Result<isize, SegmentTreeError> {
+        finish(self.overflow_policy, value, start, end)
+    }
+}
+
+impl ActedMonoid for RangeAffineRangeSum {
+    /// Factor and offset of the affine map, or `None` once the composed map has overflowed under the
+    /// checked policy
+    type Map = Option<(i128, i128)>;
+
+    fn identity_map(&self) -> Option<(i128, i128)> {
+        Some((1, 0))
+    }
+
+    fn apply(&self, map: &Option<(i128, i128)>, value: &Option<i128>, len: usize) -> Option<i128> {
+        let policy = self.overflow_policy;
+        let (factor, offset) = (*map)?;
+        add(policy, mul(policy, Some(factor), *value), mul(policy, Some(offset), Some(len as i128)))
+    }
+
+    fn compose(&self, outer: &Option<(i128, i128)>, inner: &Option<(i128, i128)>) -> Option<(i128, i128)> {
+        // outer(inner(x)) = outer.0 * (inner.0 * x + inner.1) + outer.1
+        let policy = self.overflow_policy;
+        let ((outer_factor, outer_offset), (inner_factor, inner_offset)) = ((*outer)?, (*inner)?);
+        let factor = mul(policy, Some(outer_factor), Some(inner_factor))?;
+        let offset = add(policy, mul(policy, Some(outer_factor), Some(inner_offset)), Some(outer_offset))?;
+        Some((factor, offset))
+    }
+}
+
+impl LazySegmentTree<RangeAffineRangeSum> {
+    /// Replace every value x in a range by `mul * x + add`
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// mul: Factor to multiply by
+    /// add: Value to add after multiplying
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_affine(&mut self, start: usize, end: usize, mul: isize, add: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, Some((mul as i128, add as i128)))
+    }
+}
+
+/// Range Chmin Range Max
+/// Maximum of `isize` leaves, with maps lowering every value of a range to at most a bound.
+/// Clamping never overflows, so there is no overflow policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeChminRangeMax;
+
+impl Monoid for RangeChminRangeMax {
+    type Item = isize;
+    type Value = isize;
+    type Output = isize;
+
+    fn identity(&self) -> isize {
+        isize::MIN
+    }
+
+    fn lift(&self, item: &isize) -> isize {
+        *item
+    }
+
+    fn combine(&self, left: &isize, right: &isize) -> isize {
+        *left.max(right)
+    }
+
+    fn finish(&self, value: isize, _start: usize, _end: usize) -> Result<isize, SegmentTreeError> {
+        Ok(value)
+    }
+}
+
+impl ActedMonoid for RangeChminRangeMax {
+    /// Upper bound of the values
+    type Map = isize;
+
+    fn identity_map(&self) -> isize {
+        isize::MAX
+    }
+
+    fn apply(&self, map: &isize, value: &isize, _len: usize) -> isize {
+        *value.min(map)
+    }
+
+    fn compose(&self, outer: &isize, inner: &isize) -> isize {
+        *outer.min(inner)
+    }
+}
+
+impl LazySegmentTree<RangeChminRangeMax> {
+    /// Lower every value in a range to at most a bound
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// bound: Largest value to keep
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_chmin(&mut self, start: usize, end: usize, bound: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, bound)
+    }
+}
+
+/// Range Assign Segment Count
+/// Number of maximal runs of equal `isize` values in a range, with maps setting every value of a
+/// range to the same value, such as counting the painted stretches of a map
+/// Values are `(runs, first, last)`, with 0 runs for an empty range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAssignSegmentCount;
+
+impl Monoid for RangeAssignSegmentCount {
+    type Item = isize;
+    type Value = (usize, isize, isize);
+    type Output = usize;
+
+    fn identity(&self) -> (usize, isize, isize) {
+        (0, 0, 0)
+    }
+
+    fn lift(&self, item: &isize) -> (usize, isize, isize) {
+        (1, *item, *item)
+    }
+
+    fn combine(&self, left: &(usize, isize, isize), right: &(usize, isize, isize)) -> (usize, isize, isize) {
+        match (left.0, right.0) {
+            (0, _) => *right,
+            (_, 0) => *left,
+            // Runs meeting at the junction with the same value are one run
+            _ => (left.0 + right.0 - usize::from(left.2 == right.1), left.1, right.2),
+        }
+    }
+
+    fn finish(&self, value: (usize, isize, isize), _start: usize, _end: usize) -> Result<usize, SegmentTreeError> {
+        Ok(value.0)
+    }
+}
+
+impl ActedMonoid for RangeAssignSegmentCount {
+    /// Value to assign, or `None` to leave the values unchanged
+    type Map = Option<isize>;
+
+    fn identity_map(&self) -> Option<isize> {
+        None
+    }
+
+    fn apply(&self, map: &Option<isize>, value: &(usize, isize, isize), len: usize) -> (usize, isize, isize) {
+        match map {
+            Some(assigned) if len > 0 => (1, *assigned, *assigned),
+            _ => *value,
+        }
+    }
+
+    fn compose(&self, outer: &Option<isize>, inner: &Option<isize>) -> Option<isize> {
+        outer.or(*inner)
+    }
+}
+
+impl LazySegmentTree<RangeAssignSegmentCount> {
+    /// Set every value in a range to the same value
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Value to assign
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_assign(&mut self, start: usize, end: usize, value: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, Some(value))
+    }
+
+    /// Count the maximal runs of equal values in a range, in O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the number of runs, or an error
+    pub fn segments(&self, start: usize, end: usize) -> Result<usize, SegmentTreeError> {
+        self.query(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        LazySegmentTree, OverflowPolicy, RangeAddRangeMin, RangeAddRangeSum, RangeAffineRangeSum, RangeAssignRangeSum,
+        RangeAssignSegmentCount, RangeChminRangeMax, RangeMulRangeSum, SegmentTreeError,
+    };
+
+    #[test]
+    fn test_range_mul() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = LazySegmentTree::new(&[1, 2, 3, 4, 5, 6, 7], RangeMulRangeSum::default())?;
+
+        segment_tree.range_mul(1, 4, 3)?;
+        assert_eq!(segment_tree.query(0, 6)?, 56);
+        segment_tree.range_mul(3, 6, -2)?;
+        assert_eq!(segment_tree.query(0, 6)?, -64);
+        assert_eq!(segment_tree.query(2, 3)?, -15);
+        segment_tree.update(3, 10)?;
+        assert_eq!(segment_tree.get(3)?, 10);
+        segment_tree.range_mul(0, 6, 0)?;
+        assert_eq!(segment_tree.total()?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_mul_overflow() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = LazySegmentTree::new(&[2, 3], RangeMulRangeSum::default())?;
+        for _ in 0..70 {
+            segment_tree.range_mul(0, 1, 4)?;
+        }
+        assert_eq!(segment_tree.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+
+        // Overflowed leaves of opposite signs must not cancel out
+        let mut segment_tree = LazySegmentTree::new(&[2, -3], RangeMulRangeSum::default())?;
+        for _ in 0..70 {
+            segment_tree.range_mul(0, 0, 4)?;
+            segment_tree.range_mul(1, 1, 4)?;
+        }
+        assert_eq!(segment_tree.query(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        assert_eq!(segment_tree.get(1), Err(SegmentTreeError::Overflow { start: 1, end: 1 }));
+
+        let monoid = RangeMulRangeSum { overflow_policy: OverflowPolicy::Saturating };
+        let mut segment_tree = LazySegmentTree::new(&[2, -3], monoid)?;
+        segment_tree.range_mul(0, 1, isize::MAX)?;
+        assert_eq!(segment_tree.get(0)?, isize::MAX);
+        assert_eq!(segment_tree.get(1)?, isize::MIN);
+
+        let monoid = RangeMulRangeSum { overflow_policy: OverflowPolicy::Wrapping };
+        let mut segment_tree = LazySegmentTree::new(&[2, 3], monoid)?;
+        segment_tree.range_mul(0, 1, isize::MAX)?;
+        segment_tree.range_mul(0, 1, isize::MAX)?;
+        assert_eq!(segment_tree.query(0, 1)?, 5isize.wrapping_mul(isize::MAX).wrapping_mul(isize::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preset_catalog() -> Result<(), SegmentTreeError> {
+        let items: Vec<isize> = (0..19).map(|i| (i * 7) % 5 - 2).collect();
+        let mut add_sum = LazySegmentTree::new(&items, RangeAddRangeSum::default())?;
+        let mut add_min = LazySegmentTree::new(&items, RangeAddRangeMin::default())?;
+        let mut assign_sum = LazySegmentTree::new(&items, RangeAssignRangeSum::default())?;
+        let mut affine_sum = LazySegmentTree::new(&items, RangeAffineRangeSum::default())?;
+        let mut chmin_max = LazySegmentTree::new(&items, RangeChminRangeMax)?;
+        let mut naive = [items.clone(), items.clone(), items.clone(), items.clone(), items.clone()];
+
+        // Deterministic pseudo-random operations, checked against plain vectors
+        let mut seed = 987654321u64;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for _ in 0..200 {
+            let (a, b) = (next(items.len()), next(items.len()));
+            let (start, end) = (a.min(b), a.max(b));
+            let k = next(7) as isize - 3;
+
+            add_sum.range_add(start, end, k)?;
+            add_min.range_add(start, end, k)?;
+            assign_sum.range_assign(start, end, k)?;
+            affine_sum.range_affine(start, end, k, 1)?;
+            chmin_max.range_chmin(start, end, k + 2)?;
+            naive[0][start..=end].iter_mut().for_each(|item| *item += k);
+            naive[1][start..=end].iter_mut().for_each(|item| *item += k);
+            naive[2][start..=end].iter_mut().for_each(|item| *item = k);
+            naive[3][start..=end].iter_mut().for_each(|item| *item = k * *item + 1);
+            naive[4][start..=end].iter_mut().for_each(|item| *item = (*item).min(k + 2));
+
+            let (a, b) = (next(items.len()), next(items.len()));
+            let (start, end) = (a.min(b), a.max(b));
+            assert_eq!(add_sum.query(start, end)?, naive[0][start..=end].iter().sum());
+            assert_eq!(add_min.query(start, end)?, *naive[1][start..=end].iter().min().unwrap());
+            assert_eq!(assign_sum.query(start, end)?, naive[2][start..=end].iter().sum());
+            assert_eq!(affine_sum.query(start, end)?, naive[3][start..=end].iter().sum());
+            assert_eq!(chmin_max.query(start, end)?, *naive[4][start..=end].iter().max().unwrap());
+
+            // Affine values grow quickly, so keep them small
+            if naive[3].iter().any(|item| item.abs() > 1 << 20) {
+                affine_sum.range_affine(0, items.len() - 1, 0, 1)?;
+                naive[3].fill(1);
+            }
+        }
+
+        chmin_max.update(3, 100)?;
+        assert_eq!(chmin_max.total()?, 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments_under_range_assign() -> Result<(), SegmentTreeError> {
+        let mut items: Vec<isize> = vec![1, 1, 2, 2, 3, 1, 1, 4, 4, 4, 5];
+        let mut segment_tree = LazySegmentTree::new(&items, RangeAssignSegmentCount)?;
+
+        let naive = |items: &[isize]| 1 + items.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        for (start, end, value) in [(0, 3, 7), (3, 8, 7), (5, 5, 2), (9, 10, 4), (0, 10, 1), (2, 4, 3)] {
+            segment_tree.range_assign(start, end, value)?;
+            items[start..=end].fill(value);
+            for start in 0..items.len() {
+                for end in start..items.len() {
+                    assert_eq!(segment_tree.segments(start, end)?, naive(&items[start..=end]));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Rust Range Queries
+//!
+//! The core API takes inclusive `start` and `end` indices. These methods take any Rust range
+//! instead, so half-open ranges like `2..5` can be used as they are everywhere else in Rust.
+
+#[cfg(feature = "dynamic")]
+use crate::DynamicSegmentTree;
+use crate::{Monoid, SegmentTree, SegmentTreeError};
+use std::ops::{Bound, RangeBounds};
+
+/// Convert a range into half-open bounds
+/// range: Range of indices
+/// len: Number of indices
+/// Returns the first index and the index past the end, or an error if the range does not fit
+pub(crate) fn half_open_bounds<R: RangeBounds<usize>>(range: &R, len: usize) -> Result<(usize, usize), SegmentTreeError> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1).ok_or(SegmentTreeError::IndexOutOfBounds { index: start, len })?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).ok_or(SegmentTreeError::IndexOutOfBounds { index: end, len })?,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+
+    if start > end {
+        return Err(SegmentTreeError::InvalidRange { start, end, len });
+    }
+    if end > len {
+        return Err(SegmentTreeError::IndexOutOfBounds { index: end - 1, len });
+    }
+
+    Ok((start, end))
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Query a range given as any Rust range, such as `2..5`, `2..=4`, `..5` or `..`
+    /// An empty range such as `3..3` gives the result of the identity
+    /// range: Range of indices
+    /// Returns the result of the range, or an error
+    pub fn query_range<R: RangeBounds<usize>>(&self, range: R) -> Result<M::Output, SegmentTreeError> {
+        match half_open_bounds(&range, self.leaf_len)? {
+            (start, end) if start == end => self.monoid.finish(self.monoid.identity(), start, start),
+            (start, end) => self.query(start, end - 1),
+        }
+    }
+}
+
+#[cfg(feature = "dynamic")]
+impl<M: Monoid> DynamicSegmentTree<M> {
+    /// Query a range given as any Rust range, such as `2..5`, `2..=4`, `..5` or `..`
+    /// An empty range such as `3..3` gives the result of the identity
+    /// range: Range of indices
+    /// Returns the result of the range, or an error
+    pub fn query_range<R: RangeBounds<usize>>(&self, range: R) -> Result<M::Output, SegmentTreeError> {
+        match half_open_bounds(&range, self.len())? {
+            (start, end) if start == end => self.monoid().finish(self.monoid().identity(), start, start),
+            (start, end) => self.query(start, end - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "dynamic")]
+    use crate::DynamicSegmentTree;
+    use crate::{SegmentTree, SegmentTreeError};
+    use std::ops::Bound;
+
+    #[test]
+    fn test_query_range() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+
+        assert_eq!(segment_tree.query_range(1..3)?, 5);
+        assert_eq!(segment_tree.query_range(1..=3)?, 9);
+        assert_eq!(segment_tree.query_range(..2)?, 3);
+        assert_eq!(segment_tree.query_range(3..)?, 9);
+        assert_eq!(segment_tree.query_range(..)?, 15);
+        assert_eq!(segment_tree.query_range(2..2)?, 0);
+        assert_eq!(segment_tree.query_range(5..)?, 0);
+
+        assert_eq!(segment_tree.query_range(0..6), Err(SegmentTreeError::IndexOutOfBounds { index: 5, len: 5 }));
+        assert_eq!(segment_tree.query_range(6..6), Err(SegmentTreeError::IndexOutOfBounds { index: 5, len: 5 }));
+        assert_eq!(
+            segment_tree.query_range((Bound::Included(3), Bound::Excluded(1))),
+            Err(SegmentTreeError::InvalidRange { start: 3, end: 1, len: 5 })
+        );
+        assert_eq!(
+            segment_tree.query_range(..=usize::MAX),
+            Err(SegmentTreeError::IndexOutOfBounds { index: usize::MAX, len: 5 })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "dynamic")]
+    fn test_dynamic_query_range() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = DynamicSegmentTree::new(1 << 30)?;
+        segment_tree.update(10, 4)?;
+        segment_tree.update(20, 6)?;
+
+        assert_eq!(segment_tree.query_range(10..20)?, 4);
+        assert_eq!(segment_tree.query_range(10..=20)?, 10);
+        assert_eq!(segment_tree.query_range(..)?, 10);
+
+        Ok(())
+    }
+}
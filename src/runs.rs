@@ -0,0 +1,193 @@
+//! Run Aggregates
+//!
+//! Monoids measuring runs of contiguous values. Every node keeps the runs touching its ends, so the
+//! run crossing the junction of two children is found when they are combined.
+
+use crate::{Monoid, SegmentTree, SegmentTreeError};
+
+/// Run Summary
+/// Runs of a range of `isize` values, where a run is a maximal stretch of consecutive values that
+/// each join the one before them
+/// len: Number of values, 0 for an empty range
+/// first: First value
+/// last: Last value
+/// prefix: Length of the run starting at the first value
+/// suffix: Length of the run ending at the last value
+/// longest: Length of the longest run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub len: usize,
+    pub first: isize,
+    pub last: isize,
+    pub prefix: usize,
+    pub suffix: usize,
+    pub longest: usize,
+}
+
+impl RunSummary {
+    /// Summary of a single value, a run of length 1
+    /// value: Value
+    fn single(value: isize) -> RunSummary {
+        RunSummary { len: 1, first: value, last: value, prefix: 1, suffix: 1, longest: 1 }
+    }
+
+    /// Combine the summaries of two adjacent ranges
+    /// left: Summary of the range on the left
+    /// right: Summary of the range on the right
+    /// joins: Whether the first value on the right continues a run ending with the last value on the left
+    fn combine(left: &RunSummary, right: &RunSummary, joins: impl Fn(isize, isize) -> bool) -> RunSummary {
+        if left.len == 0 {
+            return *right;
+        }
+        if right.len == 0 {
+            return *left;
+        }
+
+        let mut summary = RunSummary {
+            len: left.len + right.len,
+            first: left.first,
+            last: right.last,
+            prefix: left.prefix,
+            suffix: right.suffix,
+            longest: left.longest.max(right.longest),
+        };
+        if joins(left.last, right.first) {
+            summary.longest = summary.longest.max(left.suffix + right.prefix);
+            if left.prefix == left.len {
+                summary.prefix = left.len + right.prefix;
+            }
+            if right.suffix == right.len {
+                summary.suffix = left.suffix + right.len;
+            }
+        }
+        summary
+    }
+}
+
+/// Longest Equal Run
+/// Length of the longest run of equal `isize` values in a range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LongestEqualRun;
+
+impl Monoid for LongestEqualRun {
+    type Item = isize;
+    type Value = RunSummary;
+    type Output = usize;
+
+    fn identity(&self) -> RunSummary {
+        RunSummary::default()
+    }
+
+    fn lift(&self, item: &isize) -> RunSummary {
+        RunSummary::single(*item)
+    }
+
+    fn combine(&self, left: &RunSummary, right: &RunSummary) -> RunSummary {
+        RunSummary::combine(left, right, |last, first| last == first)
+    }
+
+    fn finish(&self, value: RunSummary, _start: usize, _end: usize) -> Result<usize, SegmentTreeError> {
+        Ok(value.longest)
+    }
+}
+
+impl SegmentTree<LongestEqualRun> {
+    /// Get the length of the longest run of equal values in a range, in O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the length of the run, or an error
+    pub fn longest_equal_run(&self, start: usize, end: usize) -> Result<usize, SegmentTreeError> {
+        self.query(start, end)
+    }
+}
+
+/// Longest Increasing Run
+/// Length of the longest strictly increasing contiguous run of `isize` values in a range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LongestIncreasingRun;
+
+impl Monoid for LongestIncreasingRun {
+    type Item = isize;
+    type Value = RunSummary;
+    type Output = usize;
+
+    fn identity(&self) -> RunSummary {
+        RunSummary::default()
+    }
+
+    fn lift(&self, item: &isize) -> RunSummary {
+        RunSummary::single(*item)
+    }
+
+    fn combine(&self, left: &RunSummary, right: &RunSummary) -> RunSummary {
+        RunSummary::combine(left, right, |last, first| last < first)
+    }
+
+    fn finish(&self, value: RunSummary, _start: usize, _end: usize) -> Result<usize, SegmentTreeError> {
+        Ok(value.longest)
+    }
+}
+
+impl SegmentTree<LongestIncreasingRun> {
+    /// Get the length of the longest strictly increasing contiguous run in a range, in O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the length of the run, or an error
+    pub fn longest_increasing_run(&self, start: usize, end: usize) -> Result<usize, SegmentTreeError> {
+        self.query(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LongestEqualRun, LongestIncreasingRun, SegmentTree, SegmentTreeError};
+
+    /// Length of the longest run of a slice, where `joins` tells if a value continues the run of the one before it
+    fn naive_longest(values: &[isize], joins: impl Fn(isize, isize) -> bool) -> usize {
+        let mut longest = 0;
+        let mut current = 0;
+        for (index, &value) in values.iter().enumerate() {
+            current = if index > 0 && joins(values[index - 1], value) { current + 1 } else { 1 };
+            longest = longest.max(current);
+        }
+        longest
+    }
+
+    #[test]
+    fn test_longest_equal_run() -> Result<(), SegmentTreeError> {
+        let mut input: Vec<isize> = vec![1, 1, 2, 2, 2, 3, 1, 1, 1, 1, 4, 4, 2];
+        let mut segment_tree = SegmentTree::with_monoid(&input, LongestEqualRun)?;
+
+        for step in 0..3 {
+            for start in 0..input.len() {
+                for end in start..input.len() {
+                    let expected = naive_longest(&input[start..=end], |last, value| last == value);
+                    assert_eq!(segment_tree.longest_equal_run(start, end)?, expected);
+                }
+            }
+            input[step * 4 + 2] = 1;
+            segment_tree.update(step * 4 + 2, 1)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_longest_increasing_run() -> Result<(), SegmentTreeError> {
+        let mut input: Vec<isize> = vec![1, 2, 3, 3, 4, 5, 6, 2, 3, 1, 0, 1, 2, 3, 4];
+        let mut segment_tree = SegmentTree::with_monoid(&input, LongestIncreasingRun)?;
+
+        for step in 0..3 {
+            for start in 0..input.len() {
+                for end in start..input.len() {
+                    let expected = naive_longest(&input[start..=end], |last, value| last < value);
+                    assert_eq!(segment_tree.longest_increasing_run(start, end)?, expected);
+                }
+            }
+            input[step * 5 + 3] = 10;
+            segment_tree.update(step * 5 + 3, 10)?;
+        }
+
+        Ok(())
+    }
+}
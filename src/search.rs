@@ -0,0 +1,129 @@
+//! Tree Searches
+//!
+//! Searches that descend only into the subtrees that can hold a match, instead of scanning the range.
+
+use crate::{Max, Min, Monoid, SegmentTree, SegmentTreeError};
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Find the first index of a range whose leaf value passes a predicate
+    /// Nodes whose value fails the predicate are skipped with their whole subtree, so for predicates
+    /// like `value >= x` on a maximum this takes O(log n)
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// predicate: Test on the value of a node, which must pass for a node if it passes for one of its leaves
+    /// Returns the first matching index, or `None`
+    pub(crate) fn find_first<P: Fn(&M::Value) -> bool>(&self, start: usize, end: usize, predicate: &P) -> Option<usize> {
+        self.find_first_recursive(0, start, end, predicate)
+    }
+
+    /// Find the first matching index below a node (Recursive)
+    /// node_idx: Index of the current node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// predicate: Test on the value of a node
+    fn find_first_recursive<P: Fn(&M::Value) -> bool>(&self, node_idx: usize, start: usize, end: usize, predicate: &P) -> Option<usize> {
+        let node = &self.nodes[node_idx];
+        if end < node.start || start > node.end || !predicate(&node.value) {
+            return None;
+        }
+
+        match self.children(node_idx) {
+            Some((left, right)) => self.find_first_recursive(left, start, end, predicate)
+                .or_else(|| self.find_first_recursive(right, start, end, predicate)),
+            _ => Some(node.start),
+        }
+    }
+}
+
+impl SegmentTree<Max> {
+    /// Find the maximum of a range and its first position
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the index and value of the maximum, or an error
+    pub fn argmax(&self, start: usize, end: usize) -> Result<(usize, isize), SegmentTreeError> {
+        let max = self.query(start, end)?;
+        let index = self.find_first(start, end, &|value| *value >= max).unwrap_or(start);
+        Ok((index, max))
+    }
+
+    /// Find the first index at or after `start` whose value is at least `x`
+    /// Descends only into subtrees whose maximum is at least `x`, in O(log n)
+    /// start: First index to consider
+    /// x: Smallest value to find
+    /// Returns the index, `None` if no value from `start` on is at least `x`, or an error if `start` is out of bounds
+    pub fn first_at_least(&self, start: usize, x: isize) -> Result<Option<usize>, SegmentTreeError> {
+        self.validate_index(start)?;
+
+        Ok(self.find_first(start, self.leaf_len - 1, &|value| *value >= x))
+    }
+}
+
+impl SegmentTree<Min> {
+    /// Find the minimum of a range and its first position
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the index and value of the minimum, or an error
+    pub fn argmin(&self, start: usize, end: usize) -> Result<(usize, isize), SegmentTreeError> {
+        let min = self.query(start, end)?;
+        let index = self.find_first(start, end, &|value| *value <= min).unwrap_or(start);
+        Ok((index, min))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Max, Min, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_argmax() -> Result<(), SegmentTreeError> {
+        let input = [3, 9, 2, 9, -4, 7, 1, 8, 8, isize::MIN];
+        let segment_tree = SegmentTree::with_monoid(&input, Max)?;
+
+        for start in 0..input.len() {
+            for end in start..input.len() {
+                let max = *input[start..=end].iter().max().unwrap();
+                let index = start + input[start..=end].iter().position(|&value| value == max).unwrap();
+                assert_eq!(segment_tree.argmax(start, end)?, (index, max));
+            }
+        }
+        assert!(segment_tree.argmax(4, 10).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_argmin() -> Result<(), SegmentTreeError> {
+        let input = [3, -9, 2, -9, 4, 7, isize::MAX, 0, 0, 5, 1];
+        let mut segment_tree = SegmentTree::with_monoid(&input, Min)?;
+
+        for start in 0..input.len() {
+            for end in start..input.len() {
+                let min = *input[start..=end].iter().min().unwrap();
+                let index = start + input[start..=end].iter().position(|&value| value == min).unwrap();
+                assert_eq!(segment_tree.argmin(start, end)?, (index, min));
+            }
+        }
+
+        segment_tree.update(8, -10)?;
+        assert_eq!(segment_tree.argmin(0, 10)?, (8, -10));
+        assert_eq!(segment_tree.argmin(6, 6)?, (6, isize::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_at_least() -> Result<(), SegmentTreeError> {
+        let input = [1, 5, 2, 8, 3, 8, 0, 9, 4];
+        let segment_tree = SegmentTree::with_monoid(&input, Max)?;
+
+        for start in 0..input.len() {
+            for x in -1..11 {
+                let expected = (start..input.len()).find(|&index| input[index] >= x);
+                assert_eq!(segment_tree.first_at_least(start, x)?, expected);
+            }
+        }
+        assert_eq!(segment_tree.first_at_least(9, 0), Err(SegmentTreeError::IndexOutOfBounds { index: 9, len: 9 }));
+
+        Ok(())
+    }
+}
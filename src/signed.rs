@@ -0,0 +1,230 @@
+//! Signed Index Domains
+
+use crate::{DynamicSegmentTree, Monoid, OverflowPolicy, SegmentTreeError, Sum};
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Signed Segment Tree Error
+/// Errors returned by signed segment tree operations, which report signed indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignedSegmentTreeError {
+    /// The domain of the segment tree is empty
+    /// start: First index of the domain
+    /// end: Last index of the domain
+    EmptyDomain { start: i64, end: i64 },
+    /// The domain holds more indices than a segment tree can hold
+    /// start: First index of the domain
+    /// end: Last index of the domain
+    /// max: Maximum number of indices
+    DomainTooLarge { start: i64, end: i64, max: usize },
+    /// An index is outside the domain of the segment tree
+    /// index: Offending index
+    /// start: First index of the domain
+    /// end: Last index of the domain
+    IndexOutOfBounds { index: i64, start: i64, end: i64 },
+    /// A range starts after it ends
+    /// start: Start index of the range
+    /// end: End index of the range
+    InvalidRange { start: i64, end: i64 },
+    /// The result of an operation over a range does not fit in the value type
+    /// start: Start index of the range
+    /// end: End index of the range
+    Overflow { start: i64, end: i64 },
+    /// An item was rejected by the monoid of the segment tree
+    /// index: Index the item was written to
+    InvalidItem { index: i64 },
+}
+
+impl fmt::Display for SignedSegmentTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignedSegmentTreeError::EmptyDomain { start, end } => write!(f, "Domain {}..={} is empty", start, end),
+            SignedSegmentTreeError::DomainTooLarge { start, end, max } => {
+                write!(f, "Domain {}..={} exceeded the maximum of {} indices", start, end, max)
+            }
+            SignedSegmentTreeError::IndexOutOfBounds { index, start, end } => {
+                write!(f, "Index {} is outside the domain {}..={}", index, start, end)
+            }
+            SignedSegmentTreeError::InvalidRange { start, end } => {
+                write!(f, "Start index {} is greater than end index {}", start, end)
+            }
+            SignedSegmentTreeError::Overflow { start, end } => {
+                write!(f, "Result overflowed over range {}..={}", start, end)
+            }
+            SignedSegmentTreeError::InvalidItem { index } => {
+                write!(f, "Item at index {} is not valid for this segment tree", index)
+            }
+        }
+    }
+}
+
+impl Error for SignedSegmentTreeError {}
+
+/// Signed Segment Tree
+/// Dynamic segment tree indexed by any `i64` range, such as `-1_000_000_000..=1_000_000_000`.
+/// Indices are shifted by the start of the domain, so callers with signed coordinates use them as they are,
+/// and errors report the signed indices.
+/// Nodes are only created when a value is written, and unwritten indices hold the identity.
+/// tree: Dynamic tree over the shifted indices
+/// domain: First and last index of the domain
+#[derive(Clone)]
+pub struct SignedSegmentTree<M: Monoid = Sum> {
+    tree: DynamicSegmentTree<M>,
+    domain: RangeInclusive<i64>,
+}
+
+impl SignedSegmentTree {
+    /// Create a signed sum segment tree with checked overflow
+    /// domain: Indices of the tree, which may hold up to `usize::MAX` indices, so every `i64` but one
+    /// Returns the tree, or an error if the domain is empty or too large
+    pub fn new(domain: RangeInclusive<i64>) -> Result<SignedSegmentTree, SignedSegmentTreeError> {
+        SignedSegmentTree::with_monoid(domain, Sum { overflow_policy: OverflowPolicy::Checked })
+    }
+}
+
+impl<M: Monoid> SignedSegmentTree<M> {
+    /// Create a signed segment tree with a specific monoid
+    /// domain: Indices of the tree, which may hold up to `usize::MAX` indices, so every `i64` but one
+    /// monoid: Operation used to combine values
+    /// Returns the tree, or an error if the domain is empty or too large
+    pub fn with_monoid(domain: RangeInclusive<i64>, monoid: M) -> Result<SignedSegmentTree<M>, SignedSegmentTreeError> {
+        let (start, end) = (*domain.start(), *domain.end());
+        if domain.is_empty() {
+            return Err(SignedSegmentTreeError::EmptyDomain { start, end });
+        }
+        let len = usize::try_from(end as i128 - start as i128 + 1)
+            .map_err(|_| SignedSegmentTreeError::DomainTooLarge { start, end, max: usize::MAX })?;
+        let tree = DynamicSegmentTree::with_monoid(len, monoid).map_err(|_| SignedSegmentTreeError::EmptyDomain { start, end })?;
+
+        Ok(SignedSegmentTree { tree, domain })
+    }
+
+    /// Get the indices of the tree
+    pub fn domain(&self) -> RangeInclusive<i64> {
+        self.domain.clone()
+    }
+
+    /// Get the monoid of the tree
+    pub fn monoid(&self) -> &M {
+        self.tree.monoid()
+    }
+
+    /// Shift a signed index to its position in the dynamic tree
+    /// index: Signed index
+    /// Returns the position, or an error if the index is outside the domain
+    fn offset(&self, index: i64) -> Result<usize, SignedSegmentTreeError> {
+        if !self.domain.contains(&index) {
+            return Err(SignedSegmentTreeError::IndexOutOfBounds { index, start: *self.domain.start(), end: *self.domain.end() });
+        }
+
+        Ok((index as i128 - *self.domain.start() as i128) as usize)
+    }
+
+    /// Shift a position in the dynamic tree back to its signed index
+    /// offset: Position from the start of the domain
+    fn index(&self, offset: usize) -> i64 {
+        (*self.domain.start() as i128 + offset as i128) as i64
+    }
+
+    /// Report an error of the dynamic tree by signed indices
+    /// error: Error with positions from the start of the domain
+    /// Returns the same error with signed indices
+    fn signed_error(&self, error: SegmentTreeError) -> SignedSegmentTreeError {
+        let (domain_start, domain_end) = (*self.domain.start(), *self.domain.end());
+        match error {
+            SegmentTreeError::IndexOutOfBounds { index, .. } => {
+                SignedSegmentTreeError::IndexOutOfBounds { index: self.index(index), start: domain_start, end: domain_end }
+            }
+            SegmentTreeError::InvalidRange { start, end, .. } => {
+                SignedSegmentTreeError::InvalidRange { start: self.index(start), end: self.index(end) }
+            }
+            SegmentTreeError::Overflow { start, end } => {
+                SignedSegmentTreeError::Overflow { start: self.index(start), end: self.index(end) }
+            }
+            SegmentTreeError::InvalidItem { index } => SignedSegmentTreeError::InvalidItem { index: self.index(index) },
+            SegmentTreeError::EmptyInput => SignedSegmentTreeError::EmptyDomain { start: domain_start, end: domain_end },
+            error => unreachable!("dynamic segment tree returned {}", error),
+        }
+    }
+
+    /// Update the value at a signed index
+    /// index: Index to update
+    /// new_value: New value for the index
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: i64, new_value: M::Item) -> Result<(), SignedSegmentTreeError> {
+        let offset = self.offset(index)?;
+        self.tree.update(offset, new_value).map_err(|error| self.signed_error(error))
+    }
+
+    /// Query a range of signed indices
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&self, start: i64, end: i64) -> Result<M::Output, SignedSegmentTreeError> {
+        let (start, end) = (self.offset(start)?, self.offset(end)?);
+        self.tree.query(start, end).map_err(|error| self.signed_error(error))
+    }
+
+    /// Get the value at a signed index
+    /// index: Index to read
+    /// Returns the result for the single index, or an error
+    pub fn get(&self, index: i64) -> Result<M::Output, SignedSegmentTreeError> {
+        self.query(index, index)
+    }
+
+    /// Get the result of the whole domain in O(1), from the root node
+    pub fn total(&self) -> Result<M::Output, SignedSegmentTreeError> {
+        self.tree.total().map_err(|error| self.signed_error(error))
+    }
+}
+
+impl<M: Monoid + fmt::Debug> fmt::Debug for SignedSegmentTree<M>
+where
+    M::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedSegmentTree").field("tree", &self.tree).field("domain", &self.domain).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SignedSegmentTree, SignedSegmentTreeError};
+    use std::ops::RangeInclusive;
+
+    #[test]
+    fn test_signed_segment_tree() -> Result<(), SignedSegmentTreeError> {
+        let mut segment_tree = SignedSegmentTree::new(-1_000_000_000..=1_000_000_000)?;
+        segment_tree.update(-1_000_000_000, 3)?;
+        segment_tree.update(-5, 10)?;
+        segment_tree.update(0, 1)?;
+        segment_tree.update(1_000_000_000, 4)?;
+
+        assert_eq!(segment_tree.query(-10, 10)?, 11);
+        assert_eq!(segment_tree.query(-1_000_000_000, -1)?, 13);
+        assert_eq!(segment_tree.get(-5)?, 10);
+        assert_eq!(segment_tree.total()?, 18);
+
+        assert_eq!(
+            segment_tree.update(1_000_000_001, 1),
+            Err(SignedSegmentTreeError::IndexOutOfBounds { index: 1_000_000_001, start: -1_000_000_000, end: 1_000_000_000 })
+        );
+        assert_eq!(segment_tree.query(5, -5), Err(SignedSegmentTreeError::InvalidRange { start: 5, end: -5 }));
+        segment_tree.update(-7, isize::MAX)?;
+        assert_eq!(segment_tree.query(-8, -5), Err(SignedSegmentTreeError::Overflow { start: -8, end: -5 }));
+        assert_eq!(SignedSegmentTree::new(RangeInclusive::new(5, -5)).err(), Some(SignedSegmentTreeError::EmptyDomain { start: 5, end: -5 }));
+        assert!(SignedSegmentTree::new(i64::MIN..=i64::MAX - 1).is_ok());
+        assert_eq!(
+            SignedSegmentTree::new(i64::MIN..=i64::MAX).err(),
+            Some(SignedSegmentTreeError::DomainTooLarge { start: i64::MIN, end: i64::MAX, max: usize::MAX })
+        );
+
+        assert_eq!(
+            SignedSegmentTreeError::IndexOutOfBounds { index: -3, start: 0, end: 9 }.to_string(),
+            "Index -3 is outside the domain 0..=9"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! Sparse Lazy Segment Trees
+
+use crate::{ActedMonoid, RangeAddRangeSum, SegmentTreeError};
+
+/// Link to a missing child. The root is never a child, so its index is free to mean none
+const NO_CHILD: usize = 0;
+
+/// Sparse Lazy Node
+/// Node of a sparse lazy segment tree, stored in the arena of its tree
+/// value: Aggregate of the range covered by the node, with its pending map applied
+/// map: Pending map of the node, not yet applied to its children
+/// left: Arena index of the left child, or `NO_CHILD`
+/// right: Arena index of the right child, or `NO_CHILD`
+#[derive(Debug, Clone)]
+struct SparseLazyNode<V, F> {
+    value: V,
+    map: F,
+    left: usize,
+    right: usize,
+}
+
+/// Sparse Lazy Segment Tree
+/// Lazy segment tree over a large index domain whose nodes are only created when an update needs
+/// to split them, so a range update over the whole domain touches O(log n) nodes and memory grows
+/// with the number of updates rather than with the domain.
+/// Indices that were never written hold the identity, and maps act on missing nodes as on the identity,
+/// so the maps must turn the identity of a range into the right value. This holds for sums, where
+/// unwritten indices are 0, but not for minimums or maximums, whose identity is not a real value.
+/// nodes: Arena of nodes, with the root first
+/// len: Number of indices in the domain
+/// monoid: Operation used to combine values and apply maps
+#[derive(Debug, Clone)]
+pub struct SparseLazySegmentTree<A: ActedMonoid = RangeAddRangeSum> {
+    nodes: Vec<SparseLazyNode<A::Value, A::Map>>,
+    len: usize,
+    monoid: A,
+}
+
+impl SparseLazySegmentTree {
+    /// Create a sparse lazy segment tree with range additions and sum queries, with checked overflow
+    /// len: Number of indices in the domain, which may be as large as `usize::MAX`
+    /// Returns the tree, or an error if `len` is 0
+    pub fn new(len: usize) -> Result<SparseLazySegmentTree, SegmentTreeError> {
+        SparseLazySegmentTree::with_monoid(len, RangeAddRangeSum::default())
+    }
+
+    /// Add a delta to every value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// delta: Value to add
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn range_add(&mut self, start: usize, end: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.apply_range(start, end, delta as i128)
+    }
+}
+
+impl<A: ActedMonoid> SparseLazySegmentTree<A> {
+    /// Create a sparse lazy segment tree with a specific monoid
+    /// len: Number of indices in the domain, which may be as large as `usize::MAX`
+    /// monoid: Operation used to combine values and apply maps
+    /// Returns the tree, or an error if `len` is 0
+    pub fn with_monoid(len: usize, monoid: A) -> Result<SparseLazySegmentTree<A>, SegmentTreeError> {
+        if len == 0 {
+            return Err(SegmentTreeError::EmptyInput);
+        }
+
+        let root = SparseLazyNode { value: monoid.identity(), map: monoid.identity_map(), left: NO_CHILD, right: NO_CHILD };
+        Ok(SparseLazySegmentTree { nodes: vec![root], len, monoid })
+    }
+
+    /// Get the number of indices in the domain
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the domain is empty, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the monoid of the tree
+    pub fn monoid(&self) -> &A {
+        &self.monoid
+    }
+
+    /// Get the number of nodes allocated in the arena
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Reset every index to the identity
+    /// The arena keeps its allocation for reuse
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0] = SparseLazyNode {
+            value: self.monoid.identity(),
+            map: self.monoid.identity_map(),
+            left: NO_CHILD,
+            right: NO_CHILD,
+        };
+    }
+
+    /// Validate a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns `Ok(())` if the range is valid, otherwise an error
+    fn validate_range(&self, start: usize, end: usize) -> Result<(), SegmentTreeError> {
+        if start > end {
+            return Err(SegmentTreeError::InvalidRange { start, end, len: self.len });
+        }
+        if end >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index: end, len: self.len });
+        }
+
+        Ok(())
+    }
+
+    /// Get a child of a node, allocating it in the arena if it does not exist
+    /// node_idx: Arena index of the parent
+    /// left: Whether to get the left child rather than the right one
+    /// Returns the arena index of the child
+    fn child(&mut self, node_idx: usize, left: bool) -> usize {
+        let node = &self.nodes[node_idx];
+        let child = if left { node.left } else { node.right };
+        if child != NO_CHILD {
+            return child;
+        }
+
+        let child = self.nodes.len();
+        self.nodes.push(SparseLazyNode {
+            value: self.monoid.identity(),
+            map: self.monoid.identity_map(),
+            left: NO_CHILD,
+            right: NO_CHILD,
+        });
+        if left {
+            self.nodes[node_idx].left = child;
+        } else {
+            self.nodes[node_idx].right = child;
+        }
+        child
+    }
+
+    /// Apply a map to a node and record it as pending for the children
+    /// node_idx: Arena index of the node
+    /// map: Map to apply
+    /// len: Number of indices covered by the node
+    fn apply_node(&mut self, node_idx: usize, map: &A::Map, len: usize) {
+        let node = &mut self.nodes[node_idx];
+        node.value = self.monoid.apply(map, &node.value, len);
+        node.map = self.monoid.compose(map, &node.map);
+    }
+
+    /// Push the pending map of a node down to its children, allocating them as needed
+    /// node_idx: Arena index of the node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    fn push_down(&mut self, node_idx: usize, lo: usize, hi: usize) {
+        let map = std::mem::replace(&mut self.nodes[node_idx].map, self.monoid.identity_map());
+        let mid = lo + (hi - lo) / 2;
+        let left = self.child(node_idx, true);
+        let right = self.child(node_idx, false);
+        self.apply_node(left, &map, mid - lo + 1);
+        self.apply_node(right, &map, hi - mid);
+    }
+
+    /// Recompute a node from its children, which `push_down` always allocates together
+    /// node_idx: Arena index of the node
+    fn update_node(&mut self, node_idx: usize) {
+        let node = &self.nodes[node_idx];
+        self.nodes[node_idx].value = self.monoid.combine(&self.nodes[node.left].value, &self.nodes[node.right].value);
+    }
+
+    /// Apply a map to every value in a range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    /// Returns `Ok(())` if the map was applied, otherwise an error
+    pub fn apply_range(&mut self, start: usize, end: usize, map: A::Map) -> Result<(), SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        self.apply_range_recursive(0, 0, self.len - 1, start, end, &map);
+        Ok(())
+    }
+
+    /// Apply a map to the nodes covering a range (Recursive)
+    /// node_idx: Arena index of the current node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// map: Map to apply
+    fn apply_range_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize, map: &A::Map) {
+        if end < lo || hi < start {
+            return;
+        }
+        if start <= lo && hi <= end {
+            self.apply_node(node_idx, map, hi - lo + 1);
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        let (left, right) = (self.nodes[node_idx].left, self.nodes[node_idx].right);
+        self.apply_range_recursive(left, lo, mid, start, end, map);
+        self.apply_range_recursive(right, mid + 1, hi, start, end, map);
+        self.update_node(node_idx);
+    }
+
+    /// Update the value at an index, creating the nodes on its path as needed
+    /// index: Index to update
+    /// new_value: New value for the index
+    /// Returns `Ok(())` if the update was successful, otherwise an error
+    pub fn update(&mut self, index: usize, new_value: A::Item) -> Result<(), SegmentTreeError> {
+        if index >= self.len {
+            return Err(SegmentTreeError::IndexOutOfBounds { index, len: self.len });
+        }
+        self.monoid.validate(&new_value, index)?;
+
+        let value = self.monoid.lift(&new_value);
+        self.update_recursive(0, 0, self.len - 1, index, value);
+        Ok(())
+    }
+
+    /// Write a leaf value, pushing pending maps out of its path first (Recursive)
+    /// node_idx: Arena index of the current node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    /// index: Index to update
+    /// value: Lifted value for the index
+    fn update_recursive(&mut self, node_idx: usize, lo: usize, hi: usize, index: usize, value: A::Value) {
+        if lo == hi {
+            self.nodes[node_idx].value = value;
+            return;
+        }
+
+        self.push_down(node_idx, lo, hi);
+        let mid = lo + (hi - lo) / 2;
+        if index <= mid {
+            self.update_recursive(self.nodes[node_idx].left, lo, mid, index, value);
+        } else {
+            self.update_recursive(self.nodes[node_idx].right, mid + 1, hi, index, value);
+        }
+        self.update_node(node_idx);
+    }
+
+    /// Query a range of the tree
+    /// Pending maps are applied to the partial results on the way up, so querying allocates nothing
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range, or an error
+    pub fn query(&self, start: usize, end: usize) -> Result<A::Output, SegmentTreeError> {
+        self.validate_range(start, end)?;
+
+        let value = self.query_recursive(0, 0, self.len - 1, start, end).unwrap_or_else(|| self.monoid.identity());
+        self.monoid.finish(value, start, end)
+    }
+
+    /// Aggregate the part of a range below a node, from left to right (Recursive)
+    /// Missing children hold the identity, which the pending maps above them still act on
+    /// node_idx: Arena index of the current node
+    /// lo: First index covered by the node
+    /// hi: Last index covered by the node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the aggregate, or `None` if the node does not overlap the range
+    fn query_recursive(&self, node_idx: usize, lo: usize, hi: usize, start: usize, end: usize) -> Option<A::Value> {
+        if end < lo || hi < start {
+            return None;
+        }
+        let node = &self.nodes[node_idx];
+        if start <= lo && hi <= end {
+            return Some(node.value.clone());
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        let part = |child: usize, lo: usize, hi: usize| match child {
+            _ if end < lo || hi < start => None,
+            NO_CHILD => Some(self.monoid.identity()),
+            child => self.query_recursive(child, lo, hi, start, end),
+        };
+        let value = match (part(node.left, lo, mid), part(node.right, mid + 1, hi)) {
+            (Some(mut left), Some(right)) => {
+                self.monoid.combine_into(&mut left, &right);
+                left
+            }
+            (Some(value), None) | (None, Some(value)) => value,
+            (None, None) => return None,
+        };
+
+        let covered = end.min(hi) - start.max(lo) + 1;
+        Some(self.monoid.apply(&node.map, &value, covered))
+    }
+
+    /// Get the value at an index
+    /// index: Index to read
+    /// Returns the result for the single index, or an error
+    pub fn get(&self, index: usize) -> Result<A::Output, SegmentTreeError> {
+        self.query(index, index)
+    }
+
+    /// Get the result of the whole domain in O(1), from the root node
+    pub fn total(&self) -> Result<A::Output, SegmentTreeError> {
+        self.monoid.finish(self.nodes[0].value.clone(), 0, self.len - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RangeAffineRangeSum, SegmentTreeError, SparseLazySegmentTree};
+
+    #[test]
+    fn test_sparse_lazy_tree() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SparseLazySegmentTree::new(usize::MAX)?;
+        segment_tree.range_add(0, usize::MAX - 1, 1)?;
+        assert_eq!(segment_tree.node_count(), 1);
+        assert_eq!(segment_tree.query(10, 19)?, 10);
+
+        let day = 86_400;
+        segment_tree.range_add(1 << 40, (1 << 40) + day - 1, 2)?;
+        segment_tree.update(5, 100)?;
+        assert_eq!(segment_tree.get(5)?, 100);
+        assert_eq!(segment_tree.get(6)?, 1);
+        assert_eq!(segment_tree.query(0, 9)?, 109);
+        assert_eq!(segment_tree.query(1 << 40, (1 << 41) - 1)?, (1 << 40) + 2 * day as isize);
+        assert_eq!(segment_tree.query((1 << 40) - 10, (1 << 40) + 9)?, 20 + 2 * 10);
+        // Each update only splits the nodes along the edges of its range
+        assert!(segment_tree.node_count() <= 4 * 2 * 64 + 1);
+
+        assert_eq!(segment_tree.total(), Err(SegmentTreeError::Overflow { start: 0, end: usize::MAX - 1 }));
+        assert_eq!(segment_tree.range_add(3, usize::MAX, 1), Err(SegmentTreeError::IndexOutOfBounds { index: usize::MAX, len: usize::MAX }));
+        assert!(SparseLazySegmentTree::new(0).is_err());
+
+        segment_tree.clear();
+        assert_eq!(segment_tree.query(0, 1 << 50)?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_lazy_tree_matches_dense() -> Result<(), SegmentTreeError> {
+        let len = 45;
+        let mut segment_tree = SparseLazySegmentTree::with_monoid(len, RangeAffineRangeSum::default())?;
+        let mut dense = vec![0isize; len];
+        for (step, (start, end)) in [(3, 30), (0, 44), (17, 17), (10, 40), (20, 21), (0, 5)].into_iter().enumerate() {
+            let (mul, add) = (step as isize % 3 - 1, step as isize + 1);
+            segment_tree.apply_range(start, end, Some((mul as i128, add as i128)))?;
+            dense[start..=end].iter_mut().for_each(|value| *value = mul * *value + add);
+            segment_tree.update(step * 7, -3)?;
+            dense[step * 7] = -3;
+
+            for start in 0..len {
+                for end in start..len {
+                    assert_eq!(segment_tree.query(start, end)?, dense[start..=end].iter().sum());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
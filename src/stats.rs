@@ -0,0 +1,88 @@
+//! Query Instrumentation
+
+use crate::{Monoid, SegmentTree, SegmentTreeError};
+
+/// Query Stats
+/// Work done by a single query, returned by `SegmentTree::query_with_stats`
+/// nodes_visited: Number of nodes the query looked at, including ones it skipped
+/// max_depth: Deepest level reached, the root is at depth 0
+/// segments: Inclusive ranges of the canonical nodes whose values were combined, from left to right
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    pub nodes_visited: usize,
+    pub max_depth: usize,
+    pub segments: Vec<(usize, usize)>,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Query the segment tree and report the work done
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the result of the range with its `QueryStats`, or an error
+    pub fn query_with_stats(&self, start: usize, end: usize) -> Result<(M::Output, QueryStats), SegmentTreeError> {
+        self.validate_public_query(start, end)?;
+
+        let mut stats = QueryStats::default();
+        let mut value = None;
+        self.query_with_stats_recursive(0, 0, start, end, &mut value, &mut stats);
+        let value = value.unwrap_or_else(|| self.monoid.identity());
+
+        Ok((self.monoid.finish(value, start, end)?, stats))
+    }
+
+    /// Function to query the segment tree while recording stats (Recursive)
+    /// node_idx: Index of the current node
+    /// depth: Depth of the current node
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// value: Accumulated aggregate of the nodes visited so far, `None` before the first one
+    /// stats: Stats of the query so far
+    fn query_with_stats_recursive(&self, node_idx: usize, depth: usize, start: usize, end: usize, value: &mut Option<M::Value>, stats: &mut QueryStats) {
+        let node = &self.nodes[node_idx];
+        stats.nodes_visited += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+
+        if start <= node.start && end >= node.end {
+            match value {
+                Some(value) => self.monoid.combine_into(value, &node.value),
+                None => *value = Some(node.value.clone()),
+            }
+            stats.segments.push((node.start, node.end));
+            return;
+        }
+
+        if end < node.start || start > node.end {
+            return;
+        }
+
+        self.query_with_stats_recursive(2 * node_idx + 1, depth + 1, start, end, value, stats);
+        self.query_with_stats_recursive(2 * node_idx + 2, depth + 1, start, end, value, stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_query_with_stats() -> Result<(), SegmentTreeError> {
+        let input = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let segment_tree = SegmentTree::new(&input)?;
+
+        let (sum, stats) = segment_tree.query_with_stats(1, 6)?;
+        assert_eq!(sum, 27);
+        assert_eq!(sum, segment_tree.query(1, 6)?);
+        assert_eq!(stats.segments, vec![(1, 1), (2, 3), (4, 5), (6, 6)]);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.nodes_visited, 11);
+
+        let (sum, stats) = segment_tree.query_with_stats(0, 7)?;
+        assert_eq!(sum, 36);
+        assert_eq!(stats.segments, vec![(0, 7)]);
+        assert_eq!((stats.nodes_visited, stats.max_depth), (1, 0));
+
+        assert!(segment_tree.query_with_stats(0, 8).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Range Summaries
+
+use crate::{Monoid, OverflowPolicy, SegmentTree, SegmentTreeError};
+
+/// Range Summary
+/// Sum, minimum, maximum and count of a range, returned by `SegmentTree::query_summary`
+/// sum: Sum of the leaves
+/// min: Smallest leaf, `isize::MAX` for an empty range
+/// max: Largest leaf, `isize::MIN` for an empty range
+/// count: Number of leaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeSummary {
+    pub sum: isize,
+    pub min: isize,
+    pub max: isize,
+    pub count: usize,
+}
+
+/// Summary
+/// Sum, minimum, maximum and count of `isize` leaves in one node, so one tree answers all four.
+/// Values are `(sum, min, max, count)`, with sums accumulated in `i128` like `Sum`
+/// overflow_policy: How sums that do not fit in `isize` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Monoid for Summary {
+    type Item = isize;
+    type Value = (i128, isize, isize, usize);
+    type Output = RangeSummary;
+
+    fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    fn identity(&self) -> (i128, isize, isize, usize) {
+        (0, isize::MAX, isize::MIN, 0)
+    }
+
+    fn lift(&self, item: &isize) -> (i128, isize, isize, usize) {
+        (*item as i128, *item, *item, 1)
+    }
+
+    fn combine(&self, left: &(i128, isize, isize, usize), right: &(i128, isize, isize, usize)) -> (i128, isize, isize, usize) {
+        (left.0 + right.0, left.1.min(right.1), left.2.max(right.2), left.3 + right.3)
+    }
+
+    fn finish(&self, value: (i128, isize, isize, usize), start: usize, end: usize) -> Result<RangeSummary, SegmentTreeError> {
+        let (sum, min, max, count) = value;
+        Ok(RangeSummary { sum: self.overflow_policy.narrow(sum, start, end)?, min, max, count })
+    }
+}
+
+impl SegmentTree<Summary> {
+    /// Query the sum, minimum, maximum and count of a range at once
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns the `RangeSummary` of the range, or an error
+    pub fn query_summary(&self, start: usize, end: usize) -> Result<RangeSummary, SegmentTreeError> {
+        self.query(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OverflowPolicy, RangeSummary, SegmentTree, SegmentTreeError, Summary};
+
+    #[test]
+    fn test_query_summary() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::with_monoid(&[4, -7, 2, 9, -1], Summary::default())?;
+
+        assert_eq!(segment_tree.query_summary(0, 4)?, RangeSummary { sum: 7, min: -7, max: 9, count: 5 });
+        assert_eq!(segment_tree.query_summary(2, 3)?, RangeSummary { sum: 11, min: 2, max: 9, count: 2 });
+        segment_tree.update(3, -10)?;
+        assert_eq!(segment_tree.query_summary(1, 4)?, RangeSummary { sum: -16, min: -10, max: 2, count: 4 });
+        assert_eq!(segment_tree.query_range(2..2)?, RangeSummary { sum: 0, min: isize::MAX, max: isize::MIN, count: 0 });
+
+        let overflowing = SegmentTree::with_monoid(&[isize::MAX, 1], Summary::default())?;
+        assert_eq!(overflowing.query_summary(0, 1), Err(SegmentTreeError::Overflow { start: 0, end: 1 }));
+        let saturating = SegmentTree::with_monoid(&[isize::MAX, 1], Summary { overflow_policy: OverflowPolicy::Saturating })?;
+        assert_eq!(saturating.query_summary(0, 1)?.sum, isize::MAX);
+
+        Ok(())
+    }
+}
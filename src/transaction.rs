@@ -0,0 +1,174 @@
+//! Transactions
+
+use crate::{Monoid, SegmentTree, SegmentTreeError, Sum};
+use std::ops::Deref;
+
+/// Transaction
+/// Group of updates that are kept with `commit` or undone with `abort`, returned by `SegmentTree::begin`
+/// Updates are applied to the tree as they are made, so queries through the transaction see them.
+/// A transaction that is dropped without being committed is aborted.
+/// Transactions nest: committing a nested transaction hands its updates to its parent, which can still abort them.
+/// tree: Segment tree being updated
+/// undo: Index and previous value of every update, in the order they were made
+/// parent: Undo log of the enclosing transaction, if this one is nested
+/// finished: Whether the transaction was committed or aborted
+pub struct Transaction<'a, M: Monoid = Sum> {
+    tree: &'a mut SegmentTree<M>,
+    undo: Vec<(usize, M::Item)>,
+    parent: Option<&'a mut Vec<(usize, M::Item)>>,
+    finished: bool,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Start a transaction on the segment tree
+    /// Returns a `Transaction` that must be committed for its updates to be kept
+    pub fn begin(&mut self) -> Transaction<'_, M> {
+        Transaction {
+            tree: self,
+            undo: Vec::new(),
+            parent: None,
+            finished: false,
+        }
+    }
+}
+
+impl<M: Monoid> Transaction<'_, M> {
+    /// Start a transaction nested in this one
+    /// Returns a `Transaction` whose committed updates become part of this transaction
+    pub fn begin(&mut self) -> Transaction<'_, M> {
+        Transaction {
+            tree: &mut *self.tree,
+            undo: Vec::new(),
+            parent: Some(&mut self.undo),
+            finished: false,
+        }
+    }
+
+    /// Update a leaf node within the transaction
+    /// index: Index of the leaf node to update
+    /// new_value: New value for the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error and nothing is recorded
+    pub fn update(&mut self, index: usize, new_value: M::Item) -> Result<(), SegmentTreeError> {
+        self.tree.validate_index(index)?;
+        self.tree.monoid.validate(&new_value, index)?;
+
+        let previous = std::mem::replace(&mut self.tree.leaves[index], new_value);
+        self.tree.commit_leaf(index);
+        self.undo.push((index, previous));
+        Ok(())
+    }
+
+    /// Keep the updates of the transaction
+    /// A nested transaction hands its updates to its parent instead
+    pub fn commit(mut self) {
+        if let Some(parent) = self.parent.take() {
+            parent.append(&mut self.undo);
+        }
+        self.undo.clear();
+        self.finished = true;
+    }
+
+    /// Undo the updates of the transaction, restoring the values from before it began
+    pub fn abort(mut self) {
+        self.rollback();
+    }
+
+    /// Restore the previous values of all updates, latest first
+    fn rollback(&mut self) {
+        while let Some((index, previous)) = self.undo.pop() {
+            self.tree.set_leaf(index, previous);
+        }
+        self.finished = true;
+    }
+}
+
+impl Transaction<'_> {
+    /// Add a delta to a leaf node within the transaction
+    /// index: Index of the leaf node to update
+    /// delta: Value to add to the leaf node
+    /// Returns `Ok(())` if the update was successful, otherwise an error and nothing is recorded
+    pub fn add(&mut self, index: usize, delta: isize) -> Result<(), SegmentTreeError> {
+        self.tree.validate_index(index)?;
+
+        let new_value = self.tree.monoid.overflow_policy.add(self.tree.leaves[index], delta, index)?;
+        self.update(index, new_value)
+    }
+}
+
+/// Read access to the segment tree, including the updates made so far
+impl<M: Monoid> Deref for Transaction<'_, M> {
+    type Target = SegmentTree<M>;
+
+    fn deref(&self) -> &SegmentTree<M> {
+        self.tree
+    }
+}
+
+impl<M: Monoid> Drop for Transaction<'_, M> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.rollback();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_transaction_commit_and_abort() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4])?;
+
+        let mut transaction = segment_tree.begin();
+        transaction.update(0, 10)?;
+        transaction.add(0, 5)?;
+        transaction.add(3, -4)?;
+        assert_eq!(transaction.query(0, 3)?, 20);
+        assert!(transaction.update(4, 1).is_err());
+        transaction.abort();
+        assert_eq!(segment_tree.leaves(), &[1, 2, 3, 4]);
+        assert_eq!(segment_tree.query(0, 3)?, 10);
+
+        let mut transaction = segment_tree.begin();
+        transaction.update(1, 20)?;
+        transaction.commit();
+        assert_eq!(segment_tree.query(0, 3)?, 28);
+
+        // Dropping without committing aborts
+        {
+            let mut transaction = segment_tree.begin();
+            transaction.update(2, 30)?;
+        }
+        assert_eq!(segment_tree.leaves(), &[1, 20, 3, 4]);
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_transactions() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4])?;
+
+        let mut outer = segment_tree.begin();
+        outer.update(0, 10)?;
+
+        let mut inner = outer.begin();
+        inner.update(1, 20)?;
+        inner.abort();
+        assert_eq!(outer.leaves(), &[10, 2, 3, 4]);
+
+        let mut inner = outer.begin();
+        inner.update(1, 20)?;
+        inner.update(0, 100)?;
+        inner.commit();
+        assert_eq!(outer.leaves(), &[100, 20, 3, 4]);
+
+        // The outer abort also undoes the committed inner transaction
+        outer.abort();
+        assert_eq!(segment_tree.leaves(), &[1, 2, 3, 4]);
+        assert_eq!(segment_tree.query(0, 3)?, 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Tree Traversal
+//!
+//! Read-only access to the nodes of a segment tree, for custom logic built on its structure.
+
+use crate::{Monoid, Node, SegmentTree, SegmentTreeError};
+
+/// Node Ref
+/// Handle to a node of a segment tree, exposing the range it covers and its aggregate
+/// node: Node of the tree
+#[derive(Debug)]
+pub struct NodeRef<'a, V> {
+    node: &'a Node<V>,
+}
+
+impl<V> Clone for NodeRef<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for NodeRef<'_, V> {}
+
+impl<'a, V> NodeRef<'a, V> {
+    /// Get the first leaf index covered by the node
+    pub fn start(&self) -> usize {
+        self.node.start
+    }
+
+    /// Get the last leaf index covered by the node
+    pub fn end(&self) -> usize {
+        self.node.end
+    }
+
+    /// Get the aggregate of the range covered by the node, as combined by the monoid of the tree
+    /// Ranges past the last leaf hold the identity
+    pub fn value(&self) -> &'a V {
+        &self.node.value
+    }
+
+    /// Returns `true` if the node is a leaf
+    pub fn is_leaf(&self) -> bool {
+        self.node.is_leaf()
+    }
+}
+
+/// Step
+/// What `SegmentTree::walk` does after visiting a node
+/// Descend: Visit the children of the node, left first. Same as `Skip` for leaves
+/// Skip: Do not visit the children of the node, and go on with the next node
+/// Stop: End the walk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Descend,
+    Skip,
+    Stop,
+}
+
+/// Decompose
+/// Iterator over the canonical nodes covering a range, from left to right, returned by `SegmentTree::decompose`
+/// tree: Segment tree being decomposed
+/// stack: Nodes still to visit, with the leftmost last
+/// start: Start index of the range
+/// end: End index of the range
+#[derive(Debug)]
+pub struct Decompose<'a, M: Monoid> {
+    tree: &'a SegmentTree<M>,
+    stack: Vec<usize>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, M: Monoid> Iterator for Decompose<'a, M> {
+    type Item = NodeRef<'a, M::Value>;
+
+    fn next(&mut self) -> Option<NodeRef<'a, M::Value>> {
+        while let Some(node_idx) = self.stack.pop() {
+            let node = &self.tree.nodes[node_idx];
+            if self.end < node.start || self.start > node.end {
+                continue;
+            }
+            if self.start <= node.start && node.end <= self.end {
+                return Some(NodeRef { node });
+            }
+
+            self.stack.push(2 * node_idx + 2);
+            self.stack.push(2 * node_idx + 1);
+        }
+        None
+    }
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// Get the canonical nodes covering a range, the O(log n) nodes a query combines
+    /// Combining their values from left to right gives the aggregate of the range
+    /// start: Start index of the range
+    /// end: End index of the range
+    /// Returns an iterator over the nodes from left to right, or an error
+    pub fn decompose(&self, start: usize, end: usize) -> Result<Decompose<'_, M>, SegmentTreeError> {
+        self.validate_public_query(start, end)?;
+
+        Ok(Decompose { tree: self, stack: vec![0], start, end })
+    }
+
+    /// Walk the nodes of the segment tree in pre-order, from the root and left to right
+    /// The visitor decides for each node whether to descend into it, skip it or stop,
+    /// so it can implement custom searches and pruned scans. Nodes past the last leaf are never visited.
+    /// visitor: Function called with each node, returning the next `Step`
+    pub fn walk<F: FnMut(NodeRef<'_, M::Value>) -> Step>(&self, mut visitor: F) {
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if node.start >= self.leaf_len {
+                continue;
+            }
+
+            match visitor(NodeRef { node }) {
+                Step::Descend => {
+                    if let Some((left, right)) = self.children(node_idx) {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+                Step::Skip => {}
+                Step::Stop => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SegmentTree, SegmentTreeError, Step};
+
+    #[test]
+    fn test_decompose() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11])?;
+
+        let nodes: Vec<_> = segment_tree.decompose(1, 9)?.collect();
+        let ranges: Vec<_> = nodes.iter().map(|node| (node.start(), node.end())).collect();
+        assert_eq!(ranges, vec![(1, 1), (2, 3), (4, 7), (8, 9)]);
+        assert_eq!(nodes.iter().map(|node| node.value()).sum::<i128>(), 54);
+        assert!(nodes[0].is_leaf());
+
+        for start in 0..11 {
+            for end in start..11 {
+                let (_, stats) = segment_tree.query_with_stats(start, end)?;
+                let ranges: Vec<_> = segment_tree.decompose(start, end)?.map(|node| (node.start(), node.end())).collect();
+                assert_eq!(ranges, stats.segments);
+            }
+        }
+
+        assert!(segment_tree.decompose(3, 11).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[3, 1, 4, 1, 5, 9, 2, 6, 5, 3])?;
+
+        // Find the first index whose prefix sum reaches 20, descending into one child per level
+        let mut remaining = 20;
+        let mut found = None;
+        segment_tree.walk(|node| {
+            if *node.value() < remaining {
+                remaining -= node.value();
+                return Step::Skip;
+            }
+            if node.is_leaf() {
+                found = Some(node.start());
+                return Step::Stop;
+            }
+            Step::Descend
+        });
+        assert_eq!(found, Some(5));
+
+        // A full walk visits every node over real leaves once, in pre-order
+        let mut ranges = Vec::new();
+        segment_tree.walk(|node| {
+            ranges.push((node.start(), node.end()));
+            Step::Descend
+        });
+        assert_eq!(ranges.len(), 1 + 2 + 3 + 5 + 10);
+        assert_eq!(&ranges[..4], &[(0, 15), (0, 7), (0, 3), (0, 1)]);
+        assert_eq!(ranges.iter().filter(|(start, end)| start == end).count(), 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Invariant Checking
+
+use crate::{InvariantError, Monoid, SegmentTree};
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Value: PartialEq,
+{
+    /// Check the internal invariants of the segment tree in O(n)
+    /// The storage sizes must match the number of leaves, every internal node must be split in two
+    /// by its children at `2i + 1` and `2i + 2`, every internal node value must be the
+    /// combination of its children, every leaf node must hold its lifted item (or the identity past
+    /// the last leaf), and the leaf index table must point at the leaf node of every leaf
+    /// Returns `Ok(())` if every invariant holds, otherwise the first broken one
+    pub fn verify(&self) -> Result<(), InvariantError> {
+        let expected_nodes = 2 * self.leaf_capacity - 1;
+        if !self.leaf_capacity.is_power_of_two() || self.nodes.len() != expected_nodes {
+            return Err(InvariantError::SizeMismatch { expected: expected_nodes, actual: self.nodes.len() });
+        }
+        if self.leaves.len() != self.leaf_len || self.leaf_len > self.leaf_capacity {
+            return Err(InvariantError::SizeMismatch { expected: self.leaf_len, actual: self.leaves.len() });
+        }
+        if self.leaf_indices.len() != self.leaf_capacity {
+            return Err(InvariantError::SizeMismatch { expected: self.leaf_capacity, actual: self.leaf_indices.len() });
+        }
+
+        if self.nodes[0].start != 0 || self.nodes[0].end != self.leaf_capacity - 1 {
+            return Err(InvariantError::InvalidNodeRange { node: 0 });
+        }
+
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            if node.start > node.end {
+                return Err(InvariantError::InvalidNodeRange { node: node_idx });
+            }
+
+            let Some((left, right)) = self.children(node_idx) else {
+                self.verify_leaf(node_idx)?;
+                continue;
+            };
+
+            let (left_node, right_node) = (&self.nodes[left], &self.nodes[right]);
+            let mid = node.start + (node.end - node.start) / 2;
+            if left_node.start != node.start || left_node.end != mid {
+                return Err(InvariantError::InvalidNodeRange { node: left });
+            }
+            if right_node.start != mid + 1 || right_node.end != node.end {
+                return Err(InvariantError::InvalidNodeRange { node: right });
+            }
+
+            if node.value != self.monoid.combine(&left_node.value, &right_node.value) {
+                return Err(InvariantError::StaleValue { node: node_idx });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a leaf node and its entry in the leaf index table
+    /// node_idx: Index of the leaf node
+    /// Returns `Ok(())` if the leaf is consistent, otherwise the broken invariant
+    fn verify_leaf(&self, node_idx: usize) -> Result<(), InvariantError> {
+        let node = &self.nodes[node_idx];
+        if node.start != node.end {
+            return Err(InvariantError::InvalidNodeRange { node: node_idx });
+        }
+
+        let index = node.start;
+        if self.leaf_indices[index] != node_idx {
+            return Err(InvariantError::LeafIndex { index });
+        }
+
+        let expected = match self.leaves.get(index) {
+            Some(item) => self.monoid.lift(item),
+            None => self.monoid.identity(),
+        };
+        if node.value != expected {
+            return Err(InvariantError::StaleValue { node: node_idx });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InvariantError, SegmentTree, SegmentTreeError};
+
+    #[test]
+    fn test_verify() -> Result<(), SegmentTreeError> {
+        let mut segment_tree = SegmentTree::new(&[1, 2, 3, 4, 5])?;
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        segment_tree.update(2, 30)?;
+        segment_tree.update_batch(&[(0, 10), (4, 50)])?;
+        segment_tree.push(6)?;
+        segment_tree.swap(1, 3)?;
+        segment_tree.pop();
+        segment_tree.extend([7, 8, 9, 10]);
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_corrupted() -> Result<(), SegmentTreeError> {
+        let segment_tree = SegmentTree::new(&[1, 2, 3, 4])?;
+
+        let mut stale = SegmentTree::new(&[1, 2, 3, 4])?;
+        stale.nodes[1].value = 100;
+        assert_eq!(stale.verify(), Err(InvariantError::StaleValue { node: 0 }));
+
+        let mut stale_leaf = SegmentTree::new(&[1, 2, 3, 4])?;
+        stale_leaf.leaves[3] = 40;
+        assert_eq!(stale_leaf.verify(), Err(InvariantError::StaleValue { node: 6 }));
+
+        let mut leaf_index = SegmentTree::new(&[1, 2, 3, 4])?;
+        leaf_index.leaf_indices.swap(0, 1);
+        assert_eq!(leaf_index.verify(), Err(InvariantError::LeafIndex { index: 0 }));
+
+        let mut range = SegmentTree::new(&[1, 2, 3, 4])?;
+        range.nodes[2].start = 1;
+        assert_eq!(range.verify(), Err(InvariantError::InvalidNodeRange { node: 2 }));
+
+        let mut size = SegmentTree::new(&[1, 2, 3, 4])?;
+        size.nodes.pop();
+        assert_eq!(size.verify(), Err(InvariantError::SizeMismatch { expected: 7, actual: 6 }));
+
+        assert_eq!(segment_tree.verify(), Ok(()));
+
+        Ok(())
+    }
+}